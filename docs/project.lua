@@ -36,6 +36,7 @@ env {
 
 task {
     name = "calc_docs_src_files",
+    private = true,
     always_run = true,
     actions = {
         function (c)
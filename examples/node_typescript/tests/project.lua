@@ -4,6 +4,7 @@ local tblext = require("tblext")
 
 task {
     name = "calc_node_typescript_test_repo_files",
+    private = true,
     always_run = true,
     actions = { function (c)
         local deps = {
@@ -3,6 +3,7 @@ local iter = require("iter")
 
 task {
     name = "calc_python_poetry_test_repo_files",
+    private = true,
     always_run = true,
     actions = { function (c)
         return {
@@ -16,12 +16,14 @@ end
 
 task {
     name = "find_cobble_source_files",
+    private = true,
     always_run = true,
     actions = { function (c) return { files = path.glob("src/**/*.*") } end }
 }
 
 task {
     name = "calc_build_dep",
+    private = true,
     actions = { function (c)
         return { tasks = { (c.vars["cobble.build"] == "release" and "build_release") or "build_debug" } }
     end },
@@ -30,6 +32,8 @@ task {
 
 task {
     name = "build_release",
+    description = "Build the cobl binary in release mode",
+    group = "build",
     actions = { { tool = "cargo", "build", "--release" } },
     deps = { calc = { "find_cobble_source_files" } },
     artifacts = { "target/release/cobl" .. (PLATFORM.os_family == "windows" and ".exe" or "") }
@@ -38,6 +42,8 @@ task {
 if PLATFORM.os_family == "windows" then
 task {
     name = "build_release_linux",
+    description = "Build the linux cobl binary in release mode, using WSL",
+    group = "build",
     actions = { { tool = "wsl", "--shell-type", "login", "--", "cargo", "build", "--release" } },
     deps = { calc = { "find_cobble_source_files" } },
     artifacts = { "target/release/cobl" }
@@ -46,6 +52,8 @@ end
 
 task {
     name = "build_debug",
+    description = "Build the cobl binary in debug mode",
+    group = "build",
     actions = { { tool = "cargo", "build" } },
     deps = { calc = { "find_cobble_source_files" } },
     artifacts = { "target/debug/cobl" .. (PLATFORM.os_family == "windows" and ".exe" or "") }
@@ -53,6 +61,8 @@ task {
 
 task {
     name = "build",
+    description = "Build the cobl binary, in the mode given by the cobble.build variable",
+    group = "build",
     actions = {},
     deps = { calc = { "calc_build_dep" } }
 }
@@ -63,6 +73,8 @@ local noop = function () end
 
 task {
     name = "version_tag",
+    description = "Create and push a git tag for the version in Cargo.toml",
+    group = "release",
     output = "always",
     always_run = true,
     actions = {
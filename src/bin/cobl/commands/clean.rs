@@ -8,37 +8,43 @@ use std::path::PathBuf;
 use std::sync::Arc;
 
 use cobble::calc_artifacts::calculate_artifacts;
-use cobble::config::{get_workspace_config, TaskOutputCondition, WorkspaceConfigArgs};
+use cobble::config::{get_workspace_config, OutputMode, TaskOutputCondition, WorkspaceConfigArgs};
 use cobble::dependency::resolve_calculated_dependencies_in_subtrees;
 use cobble::execute::execute::TaskExecutor;
 use cobble::load::load_projects;
 use cobble::task_selection::compute_selected_tasks;
+use cobble::vars::resolve_declared_vars;
 use cobble::workspace::create_workspace;
 
 pub struct CleanCommandInput {
     pub cwd: PathBuf,
     pub tasks: Vec<String>,
+    pub exclude: Vec<String>,
     pub num_threads: Option<u8>,
     pub show_stdout: Option<TaskOutputCondition>,
     pub show_stderr: Option<TaskOutputCondition>,
+    pub output_mode: Option<OutputMode>,
 }
 
 pub fn clean_command<'a>(input: CleanCommandInput) -> anyhow::Result<()> {
     let CleanCommandInput {
         cwd,
         tasks,
+        exclude,
         num_threads,
         show_stdout,
         show_stderr,
+        output_mode,
     } = input;
 
     let ws_config_args = WorkspaceConfigArgs {
         num_threads: num_threads,
         show_stdout,
         show_stderr,
+        output_mode,
         ..Default::default()
     };
-    let config = Arc::new(get_workspace_config(cwd.as_path(), &ws_config_args)?);
+    let mut config = get_workspace_config(cwd.as_path(), &ws_config_args)?;
     set_current_dir(&config.workspace_dir)
         .expect("found the workspace directory, so we should be able to set that as the cwd");
 
@@ -47,9 +53,12 @@ pub fn clean_command<'a>(input: CleanCommandInput) -> anyhow::Result<()> {
         config.root_projects.iter().map(|s| s.as_str()),
     )?;
     let mut workspace = create_workspace(projects.values());
+    resolve_declared_vars(&workspace.vars, &mut config)?;
+    let config = Arc::new(config);
 
     let selected_tasks = compute_selected_tasks(
         &tasks.iter().map(|s| s.as_str()).collect(),
+        &exclude.iter().map(|s| s.as_str()).collect(),
         &workspace,
         cwd.as_path(),
         &config.workspace_dir,
@@ -0,0 +1,493 @@
+// Cobble Build Automation
+// Copyright (C) 2024 Jeremiah Darais
+//
+// This program is licensed under the GPLv3.0 license (https://github.com/jdarais/cobble/blob/main/COPYING)
+
+use std::collections::BTreeSet;
+use std::env::set_current_dir;
+use std::path::{Path, PathBuf};
+
+use clap::{Arg, Command};
+
+use cobble::config::{find_nearest_project_dir, get_workspace_config, WorkspaceConfig};
+use cobble::load::load_projects;
+use cobble::resolve::project_path_to_project_name;
+use cobble::workspace::{create_workspace, TaskType, Workspace, INTERNAL_PROJECT_NAME};
+
+/// Name of the hidden subcommand that the completion scripts call to get completion candidates
+pub const COMPLETE_COMMAND_NAME: &str = "__complete";
+
+const TAG_PREFIX: &str = "tag:";
+
+const BASH_COMPLETION_SCRIPT: &str = r#"_cobl() {
+    local cur words cword
+    if declare -F _get_comp_words_by_ref >/dev/null 2>&1; then
+        _get_comp_words_by_ref -n =: cur words cword
+    else
+        cur="${COMP_WORDS[COMP_CWORD]}"
+        words=("${COMP_WORDS[@]}")
+        cword=$COMP_CWORD
+    fi
+
+    local IFS=$'\n'
+    COMPREPLY=($(compgen -W "$(cobl __complete "$cword" "${words[@]}" 2>/dev/null)" -- "$cur"))
+
+    if [[ "${COMPREPLY[0]}" == *= ]]; then
+        compopt -o nospace 2>/dev/null
+    fi
+    if declare -F __ltrim_colon_completions >/dev/null 2>&1; then
+        __ltrim_colon_completions "$cur"
+    fi
+}
+complete -o default -F _cobl cobl
+"#;
+
+const ZSH_COMPLETION_SCRIPT: &str = r#"#compdef cobl
+
+_cobl() {
+    local -a candidates no_space
+    candidates=("${(@f)$(cobl __complete $((CURRENT - 1)) "${words[@]}" 2>/dev/null)}")
+    candidates=(${candidates:#})
+    if (( ${#candidates} == 0 )); then
+        _files
+        return
+    fi
+
+    no_space=(${(M)candidates:#*=})
+    candidates=(${candidates:#*=})
+    compadd -Q -- $candidates
+    compadd -Q -S '' -- $no_space
+}
+
+if [ "$funcstack[1]" = "_cobl" ]; then
+    _cobl "$@"
+else
+    compdef _cobl cobl
+fi
+"#;
+
+const FISH_COMPLETION_SCRIPT: &str = r#"function __cobl_complete
+    set -l tokens (commandline -opc) (commandline -ct)
+    cobl __complete (math (count $tokens) - 1) $tokens 2>/dev/null
+end
+
+complete -c cobl -f -a '(__cobl_complete)'
+complete -c cobl -l var-file -r -F
+complete -c cobl -l trace -r -F
+"#;
+
+pub struct CompletionsCommandInput {
+    pub shell: String,
+}
+
+pub fn completions_command(input: CompletionsCommandInput) -> anyhow::Result<()> {
+    let script = match input.shell.as_str() {
+        "bash" => BASH_COMPLETION_SCRIPT,
+        "zsh" => ZSH_COMPLETION_SCRIPT,
+        "fish" => FISH_COMPLETION_SCRIPT,
+        shell => return Err(anyhow::anyhow!("Unsupported shell: {}", shell)),
+    };
+
+    print!("{}", script);
+    Ok(())
+}
+
+pub struct CompleteCommandInput {
+    pub cwd: PathBuf,
+    pub command: Command,
+    /// Index in `words` of the word being completed
+    pub index: usize,
+    /// The words on the command line, starting with the program name
+    pub words: Vec<String>,
+}
+
+/// Print the completion candidates for the word being completed, one per line
+pub fn complete_command(input: CompleteCommandInput) -> anyhow::Result<()> {
+    let CompleteCommandInput {
+        cwd,
+        mut command,
+        index,
+        words,
+    } = input;
+    command.build();
+
+    let mut names_source = CompletionNames::new(cwd);
+    for candidate in find_completion_candidates(&command, &words, index, &mut names_source) {
+        println!("{}", candidate);
+    }
+
+    Ok(())
+}
+
+/// Loads the workspace the first time names from the workspace are needed.  If the workspace
+/// can't be loaded, there are no names to complete.
+struct CompletionNames {
+    cwd: PathBuf,
+    loaded: Option<Option<(WorkspaceConfig, Workspace, String)>>,
+}
+
+impl CompletionNames {
+    fn new(cwd: PathBuf) -> Self {
+        CompletionNames { cwd, loaded: None }
+    }
+
+    fn get(&mut self) -> Option<&(WorkspaceConfig, Workspace, String)> {
+        if self.loaded.is_none() {
+            self.loaded = Some(load_workspace_for_completion(self.cwd.as_path()));
+        }
+        self.loaded.as_ref().unwrap().as_ref()
+    }
+
+    fn task_names(&mut self, cur: &str) -> Vec<String> {
+        let (_, workspace, project_name) = match self.get() {
+            Some(loaded) => loaded,
+            None => return Vec::new(),
+        };
+
+        if cur.starts_with(TAG_PREFIX) {
+            return workspace
+                .tasks
+                .values()
+                .flat_map(|t| t.tags.iter())
+                .map(|tag| format!("{}{}", TAG_PREFIX, tag))
+                .collect();
+        }
+
+        let names = workspace
+            .tasks
+            .values()
+            .filter(|t| !t.is_private && !matches!(t.task_type, TaskType::BuildEnv))
+            .map(|t| t.name.as_ref());
+        relative_or_absolute_names(names, project_name, cur)
+    }
+
+    fn env_names(&mut self, cur: &str) -> Vec<String> {
+        let (_, workspace, project_name) = match self.get() {
+            Some(loaded) => loaded,
+            None => return Vec::new(),
+        };
+        relative_or_absolute_names(workspace.build_envs.keys().map(|n| n.as_ref()), project_name, cur)
+    }
+
+    fn tool_names(&mut self) -> Vec<String> {
+        match self.get() {
+            Some((_, workspace, _)) => workspace.tools.keys().map(|n| n.to_string()).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn var_names(&mut self) -> Vec<String> {
+        match self.get() {
+            Some((_, workspace, _)) => workspace.vars.keys().map(|n| format!("{}=", n)).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn profile_names(&mut self) -> Vec<String> {
+        match self.get() {
+            Some((config, _, _)) => config.profiles.keys().cloned().collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+fn load_workspace_for_completion(cwd: &Path) -> Option<(WorkspaceConfig, Workspace, String)> {
+    let config = get_workspace_config(cwd, &Default::default()).ok()?;
+    set_current_dir(&config.workspace_dir).ok()?;
+
+    let projects = load_projects(
+        config.workspace_dir.as_path(),
+        config.root_projects.iter().map(|s| s.as_str()),
+    )
+    .ok()?;
+    let workspace = create_workspace(projects.values());
+
+    let project_dir = find_nearest_project_dir(cwd, &config.workspace_dir).ok()?;
+    let project_name = project_path_to_project_name(project_dir.as_path()).ok()?;
+
+    Some((config, workspace, project_name))
+}
+
+/// Absolute names are offered when the word being completed starts with "/".  Otherwise, names
+/// are offered relative to the current project.
+fn relative_or_absolute_names<'a, I>(names: I, project_name: &str, cur: &str) -> Vec<String>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let names = names.filter(|n| !n.starts_with(INTERNAL_PROJECT_NAME));
+
+    if cur.starts_with('/') {
+        return names.map(String::from).collect();
+    }
+
+    names
+        .filter_map(|n| {
+            n.strip_prefix(project_name)
+                .and_then(|rest| rest.strip_prefix('/'))
+                .filter(|rest| !rest.is_empty())
+                .map(String::from)
+        })
+        .collect()
+}
+
+fn find_arg<'a>(commands: &[&'a Command], word: &str) -> Option<&'a Arg> {
+    let matches_arg = |arg: &&Arg| match word.strip_prefix("--") {
+        Some(long) => arg.get_long() == Some(long),
+        None => {
+            let mut chars = word.chars().skip(1);
+            match (chars.next(), chars.next()) {
+                (Some(short), None) => arg.get_short() == Some(short),
+                _ => false,
+            }
+        }
+    };
+
+    // Options of the innermost command take precedence
+    commands
+        .iter()
+        .rev()
+        .find_map(|cmd| cmd.get_arguments().find(matches_arg))
+}
+
+fn arg_takes_value(arg: &Arg) -> bool {
+    arg.get_num_args()
+        .map(|n| n.takes_values())
+        .unwrap_or_else(|| arg.get_action().takes_values())
+}
+
+fn option_names(commands: &[&Command]) -> Vec<String> {
+    let current_command = commands[commands.len() - 1];
+
+    let mut names: Vec<String> = Vec::new();
+    for arg in current_command
+        .get_arguments()
+        .filter(|a| !a.is_hide_set() && !a.is_positional())
+    {
+        if let Some(long) = arg.get_long() {
+            names.push(format!("--{}", long));
+        }
+        if let Some(short) = arg.get_short() {
+            names.push(format!("-{}", short));
+        }
+    }
+    names
+}
+
+fn option_value_candidates(arg: &Arg, cur: &str, names: &mut CompletionNames) -> Vec<String> {
+    match arg.get_id().as_str() {
+        "var" | "secret_var" => return names.var_names(),
+        "profile" => return names.profile_names(),
+        "exclude" | "from" | "force_only" => return names.task_names(cur),
+        _ => {}
+    }
+
+    let possible_values = arg.get_possible_values();
+    if !possible_values.is_empty() {
+        return possible_values
+            .iter()
+            .map(|v| v.get_name().to_owned())
+            .collect();
+    }
+
+    // Other options that accept a fixed set of values show them in the value name, (e.g.
+    // "always|never")
+    arg.get_value_names()
+        .and_then(|value_names| value_names.first())
+        .filter(|value_name| value_name.contains('|'))
+        .map(|value_name| value_name.split('|').map(String::from).collect())
+        .unwrap_or_default()
+}
+
+fn positional_candidates(
+    commands: &[&Command],
+    num_positionals: usize,
+    cur: &str,
+    names: &mut CompletionNames,
+) -> Vec<String> {
+    let current_command = commands[commands.len() - 1];
+    if current_command.has_subcommands() {
+        return current_command
+            .get_subcommands()
+            .filter(|c| !c.is_hide_set())
+            .map(|c| String::from(c.get_name()))
+            .collect();
+    }
+
+    let command_path: Vec<&str> = commands[1..].iter().map(|c| c.get_name()).collect();
+    match command_path.as_slice() {
+        ["list"] | ["run"] | ["clean"] | ["invalidate"] | ["status"] | ["show"] => {
+            names.task_names(cur)
+        }
+        ["log"] if num_positionals == 0 => names.task_names(cur),
+        ["tool", "check"] => names.tool_names(),
+        ["env", "run"] => names.env_names(cur),
+        _ => Vec::new(),
+    }
+}
+
+/// Find the candidates for the word at `index` in `words`, given the words before it.  `words`
+/// starts with the program name.  `command` must be built, so that global args and help
+/// subcommands are filled in.
+fn find_completion_candidates(
+    command: &Command,
+    words: &[String],
+    index: usize,
+    names: &mut CompletionNames,
+) -> Vec<String> {
+    let cur = words.get(index).map(|w| w.as_str()).unwrap_or("");
+
+    let mut commands: Vec<&Command> = vec![command];
+    let mut num_positionals: usize = 0;
+    let mut options_done = false;
+    let mut i = 1;
+    while i < index.min(words.len()) {
+        let word = words[i].as_str();
+        i += 1;
+
+        if word == "--" {
+            options_done = true;
+            continue;
+        }
+
+        if !options_done && word.starts_with('-') && word.len() > 1 {
+            if let Some(arg) = find_arg(&commands, word) {
+                if arg_takes_value(arg) && !word.contains('=') {
+                    // The next word is the option's value
+                    i += 1;
+                }
+            }
+            continue;
+        }
+
+        let current_command = commands[commands.len() - 1];
+        match current_command.find_subcommand(word) {
+            Some(subcommand) if num_positionals == 0 => {
+                commands.push(subcommand);
+            }
+            _ => {
+                num_positionals += 1;
+            }
+        }
+    }
+
+    // Words after "--" are passed through, (e.g. to `cobl env run`)
+    if options_done {
+        return Vec::new();
+    }
+
+    let candidates = if i > index {
+        // The word being completed is the value of the option before it
+        match find_arg(&commands, words[index - 1].as_str()) {
+            Some(arg) => option_value_candidates(arg, cur, names),
+            None => Vec::new(),
+        }
+    } else if let Some((option, value)) = cur.split_once('=').filter(|_| cur.starts_with("--")) {
+        match find_arg(&commands, option) {
+            Some(arg) => option_value_candidates(arg, value, names)
+                .into_iter()
+                .map(|v| format!("{}={}", option, v))
+                .collect(),
+            None => Vec::new(),
+        }
+    } else if cur.starts_with('-') {
+        option_names(&commands)
+    } else {
+        positional_candidates(&commands, num_positionals, cur, names)
+    };
+
+    let candidates: BTreeSet<String> = candidates
+        .into_iter()
+        .filter(|c| c.starts_with(cur))
+        .collect();
+    candidates.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{Parser, Subcommand};
+
+    use super::*;
+
+    #[derive(Parser)]
+    struct TestCli {
+        #[command(subcommand)]
+        command: Option<TestCommand>,
+
+        #[arg(long, value_names(["always|never"]), global(true))]
+        output: Option<String>,
+
+        #[arg(short, long, global(true))]
+        profile: Option<String>,
+    }
+
+    #[derive(Subcommand)]
+    enum TestCommand {
+        Run {
+            tasks: Vec<String>,
+
+            #[arg(short, long)]
+            force: bool,
+        },
+        Tool {
+            #[command(subcommand)]
+            tool_cmd: TestToolCommand,
+        },
+    }
+
+    #[derive(Subcommand)]
+    enum TestToolCommand {
+        Check { names: Vec<String> },
+    }
+
+    fn complete(words: &[&str]) -> Vec<String> {
+        use clap::CommandFactory;
+
+        let words: Vec<String> = words.iter().map(|w| String::from(*w)).collect();
+        // No workspace is loaded for these tests, so names from the workspace are never offered
+        let mut names = CompletionNames {
+            cwd: PathBuf::from("."),
+            loaded: Some(None),
+        };
+        let mut command = TestCli::command();
+        command.build();
+        find_completion_candidates(&command, &words, words.len() - 1, &mut names)
+    }
+
+    #[test]
+    fn test_complete_subcommands() {
+        assert_eq!(complete(&["cobl", ""]), vec!["help", "run", "tool"]);
+        assert_eq!(complete(&["cobl", "r"]), vec!["run"]);
+        assert_eq!(complete(&["cobl", "tool", ""]), vec!["check", "help"]);
+        assert_eq!(complete(&["cobl", "--output", "never", "r"]), vec!["run"]);
+    }
+
+    #[test]
+    fn test_complete_options() {
+        assert_eq!(complete(&["cobl", "run", "--f"]), vec!["--force"]);
+        assert_eq!(
+            complete(&["cobl", "run", "-"]),
+            vec!["--force", "--help", "--output", "--profile", "-f", "-h", "-p"]
+        );
+        assert_eq!(complete(&["cobl", "run", "--output", ""]), vec!["always", "never"]);
+        assert_eq!(complete(&["cobl", "run", "--output=n"]), vec!["--output=never"]);
+        assert!(complete(&["cobl", "run", "--", ""]).is_empty());
+    }
+
+    #[test]
+    fn test_relative_or_absolute_names() {
+        let internal_tool_name = format!("{}/cmd", INTERNAL_PROJECT_NAME);
+        let names = ["/app/test", "/libs/a/test", "/libs/a", internal_tool_name.as_str()];
+
+        let mut relative = relative_or_absolute_names(names.iter().cloned(), "/libs", "");
+        relative.sort();
+        assert_eq!(relative, vec!["a", "a/test"]);
+
+        let mut from_root = relative_or_absolute_names(names.iter().cloned(), "", "");
+        from_root.sort();
+        assert_eq!(from_root, vec!["app/test", "libs/a", "libs/a/test"]);
+
+        let mut absolute = relative_or_absolute_names(names.iter().cloned(), "/libs", "/");
+        absolute.sort();
+        assert_eq!(absolute, vec!["/app/test", "/libs/a", "/libs/a/test"]);
+    }
+}
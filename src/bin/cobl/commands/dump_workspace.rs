@@ -0,0 +1,80 @@
+// Cobble Build Automation
+// Copyright (C) 2024 Jeremiah Darais
+//
+// This program is licensed under the GPLv3.0 license (https://github.com/jdarais/cobble/blob/main/COPYING)
+
+use std::env::set_current_dir;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use cobble::calc_artifacts::calculate_artifacts;
+use cobble::config::{get_workspace_config, TaskOutputCondition, WorkspaceConfigArgs};
+use cobble::dependency::resolve_calculated_dependencies_in_subtrees;
+use cobble::execute::execute::TaskExecutor;
+use cobble::load::load_projects;
+use cobble::vars::resolve_declared_vars;
+use cobble::workspace::create_workspace;
+use cobble::workspace_json::WorkspaceJson;
+
+pub struct DumpWorkspaceCommandInput {
+    pub cwd: PathBuf,
+    pub vars: Vec<String>,
+    pub secret_vars: Vec<String>,
+    pub var_files: Vec<PathBuf>,
+    pub profile: Option<String>,
+    pub calc: bool,
+}
+
+pub fn dump_workspace_command(input: DumpWorkspaceCommandInput) -> anyhow::Result<()> {
+    let DumpWorkspaceCommandInput {
+        cwd,
+        vars,
+        secret_vars,
+        var_files,
+        profile,
+        calc,
+    } = input;
+
+    let ws_config_args = WorkspaceConfigArgs {
+        vars,
+        secret_vars,
+        var_files,
+        profile,
+        show_stdout: Some(TaskOutputCondition::Never),
+        show_stderr: Some(TaskOutputCondition::Never),
+        ..Default::default()
+    };
+    let mut config = get_workspace_config(cwd.as_path(), &ws_config_args)?;
+    set_current_dir(&config.workspace_dir)
+        .expect("found the workspace directory, so we should be able to set that as the cwd");
+
+    let projects = load_projects(
+        config.workspace_dir.as_path(),
+        config.root_projects.iter().map(|s| s.as_str()),
+    )?;
+    let mut workspace = create_workspace(projects.values());
+    resolve_declared_vars(&workspace.vars, &mut config)?;
+    let config = Arc::new(config);
+
+    // Dumping the workspace shouldn't run anything unless --calc is given.  Without it, the dump
+    // shows each task's calc tasks instead of what they would resolve to.
+    if calc {
+        let mut executor = TaskExecutor::new(
+            config.clone(),
+            config.workspace_dir.join(".cobble.db").as_path(),
+        )?;
+        executor.set_job_output_to_stderr();
+        calculate_artifacts(&mut workspace, &mut executor)?;
+
+        let all_tasks: Vec<Arc<str>> = workspace.tasks.keys().cloned().collect();
+        resolve_calculated_dependencies_in_subtrees(
+            all_tasks.iter(),
+            &mut workspace,
+            &mut executor,
+        )?;
+    }
+
+    println!("{}", serde_json::to_string_pretty(&WorkspaceJson::from(&workspace))?);
+
+    Ok(())
+}
@@ -0,0 +1,356 @@
+// Cobble Build Automation
+// Copyright (C) 2024 Jeremiah Darais
+//
+// This program is licensed under the GPLv3.0 license (https://github.com/jdarais/cobble/blob/main/COPYING)
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use clap::builder::PossibleValuesParser;
+
+use cobble::config::{
+    find_nearest_project_dir, find_nearest_workspace_config_file_from, get_workspace_config,
+    WorkspaceConfig, PROJECT_FILE_NAME, WORKSPACE_CONFIG_FILE_NAME,
+};
+use cobble::load::load_projects;
+use cobble::workspace::create_workspace;
+
+pub struct ProjectTemplate {
+    pub name: &'static str,
+    /// Tools and variables shared by all projects that use the template.  These are written to
+    /// the root project, or to a new subproject if the workspace doesn't define the tools yet.
+    pub workspace_source: &'static str,
+    /// Build envs and tasks for a single project
+    pub project_source: &'static str,
+    pub tools: &'static [&'static str],
+}
+
+pub const DEFAULT_TEMPLATE_NAME: &str = "default";
+
+pub const PROJECT_TEMPLATES: &[ProjectTemplate] = &[
+    ProjectTemplate {
+        name: DEFAULT_TEMPLATE_NAME,
+        workspace_source: "",
+        project_source: include_str!("../templates/project.lua"),
+        tools: &[],
+    },
+    ProjectTemplate {
+        name: "python_poetry",
+        workspace_source: include_str!("../templates/python_poetry/workspace.lua"),
+        project_source: include_str!("../templates/python_poetry/project.lua"),
+        tools: &["poetry"],
+    },
+    ProjectTemplate {
+        name: "node_typescript",
+        workspace_source: include_str!("../templates/node_typescript/workspace.lua"),
+        project_source: include_str!("../templates/node_typescript/project.lua"),
+        tools: &["npm"],
+    },
+];
+
+/// Accepts the name of one of the PROJECT_TEMPLATES
+pub fn project_template_parser() -> PossibleValuesParser {
+    PossibleValuesParser::new(PROJECT_TEMPLATES.iter().map(|t| t.name))
+}
+
+const WORKSPACE_CONFIG_SOURCE: &str = r#"# Cobble workspace configuration
+#
+# The workspace includes the project defined in project.lua in this directory, along with any
+# subprojects it adds with project_dir().
+root_projects = ["."]
+
+[vars]
+"#;
+
+fn find_template(name: &str) -> anyhow::Result<&'static ProjectTemplate> {
+    PROJECT_TEMPLATES
+        .iter()
+        .find(|t| t.name == name)
+        .ok_or_else(|| anyhow::anyhow!("Unknown project template \"{}\"", name))
+}
+
+/// Join the workspace and project parts of a template into the contents of a project file
+fn template_source(template: &ProjectTemplate, include_workspace_source: bool) -> String {
+    if include_workspace_source && !template.workspace_source.is_empty() {
+        format!("{}\n{}", template.workspace_source, template.project_source)
+    } else {
+        template.project_source.to_owned()
+    }
+}
+
+/// Returns the path of project_dir relative to parent_dir, with '/' separators, as passed to
+/// project_dir() in a project file
+fn relative_project_path(parent_dir: &Path, project_dir: &Path) -> Option<String> {
+    let rel_path = project_dir.strip_prefix(parent_dir).ok()?;
+    let parts: Vec<&str> = rel_path
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(part) => part.to_str(),
+            _ => None,
+        })
+        .collect();
+
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join("/"))
+    }
+}
+
+/// Canonicalize a path that may not exist yet, by canonicalizing its closest existing ancestor
+fn canonicalize_new_dir(path: &Path) -> anyhow::Result<PathBuf> {
+    let mut existing_ancestor = path;
+    let mut new_parts: Vec<&std::ffi::OsStr> = Vec::new();
+    while !existing_ancestor.exists() {
+        match (existing_ancestor.file_name(), existing_ancestor.parent()) {
+            (Some(name), Some(parent)) => {
+                new_parts.push(name);
+                existing_ancestor = parent;
+            }
+            _ => return Err(anyhow::anyhow!("Invalid directory {}", path.display())),
+        }
+    }
+
+    let mut canonical_path = dunce::canonicalize(existing_ancestor)?;
+    canonical_path.extend(new_parts.iter().rev());
+    Ok(canonical_path)
+}
+
+pub struct InitCommandInput {
+    pub cwd: PathBuf,
+    pub template: Option<String>,
+}
+
+pub fn init_command(input: InitCommandInput) -> anyhow::Result<()> {
+    let InitCommandInput { cwd, template } = input;
+
+    // Workspaces can't be nested, since commands run in the inner workspace would never see the
+    // outer one
+    if let Ok(existing_config_path) = find_nearest_workspace_config_file_from(cwd.as_path()) {
+        return Err(anyhow::anyhow!(
+            "{} is already in the workspace defined by {}",
+            cwd.display(),
+            existing_config_path.display()
+        ));
+    }
+
+    let config_path = cwd.join(WORKSPACE_CONFIG_FILE_NAME);
+
+    let project_path = cwd.join(PROJECT_FILE_NAME);
+    let write_project_file = match (project_path.exists(), template.as_ref()) {
+        (false, _) => true,
+        (true, None) => false,
+        (true, Some(_)) => {
+            return Err(anyhow::anyhow!(
+                "{} already exists in {}",
+                PROJECT_FILE_NAME,
+                cwd.display()
+            ));
+        }
+    };
+
+    let template = find_template(template.as_deref().unwrap_or(DEFAULT_TEMPLATE_NAME))?;
+
+    fs::write(&config_path, WORKSPACE_CONFIG_SOURCE)?;
+    println!("Created {}", config_path.display());
+
+    if write_project_file {
+        fs::write(&project_path, template_source(template, true))?;
+        println!("Created {}", project_path.display());
+    }
+
+    Ok(())
+}
+
+pub struct InitProjectCommandInput {
+    pub cwd: PathBuf,
+    pub dir: PathBuf,
+    pub template: Option<String>,
+}
+
+pub fn init_project_command(input: InitProjectCommandInput) -> anyhow::Result<()> {
+    let config = get_workspace_config(input.cwd.as_path(), &Default::default())?;
+    init_project(input, &config)
+}
+
+fn init_project(input: InitProjectCommandInput, config: &WorkspaceConfig) -> anyhow::Result<()> {
+    let InitProjectCommandInput { cwd, dir, template } = input;
+
+    let template = find_template(template.as_deref().unwrap_or(DEFAULT_TEMPLATE_NAME))?;
+
+    let workspace_dir = dunce::canonicalize(&config.workspace_dir)?;
+
+    let project_dir = canonicalize_new_dir(cwd.join(dir.as_path()).as_path())?;
+    if !project_dir.starts_with(&workspace_dir) || project_dir == workspace_dir {
+        return Err(anyhow::anyhow!(
+            "{} is not a subdirectory of the workspace at {}",
+            dir.display(),
+            workspace_dir.display()
+        ));
+    }
+
+    if project_dir.join(PROJECT_FILE_NAME).exists() {
+        return Err(anyhow::anyhow!(
+            "{} already exists in {}",
+            PROJECT_FILE_NAME,
+            project_dir.display()
+        ));
+    }
+
+    // Register the new project with the closest project that contains it.  Directories that
+    // don't exist yet can't contain a project, so start from the closest one that does.
+    let parent_search_dir = project_dir
+        .ancestors()
+        .skip(1)
+        .find(|p| p.exists())
+        .expect("workspace dir is an ancestor of the project dir, and exists");
+    let parent_project_dir =
+        workspace_dir.join(find_nearest_project_dir(parent_search_dir, &workspace_dir)?);
+    let parent_project_dir = dunce::canonicalize(parent_project_dir)?;
+    let parent_project_path = parent_project_dir.join(PROJECT_FILE_NAME);
+    if !parent_project_path.exists() {
+        return Err(anyhow::anyhow!(
+            "No project found that contains {}.  Add it to root_projects in {} instead.",
+            dir.display(),
+            WORKSPACE_CONFIG_FILE_NAME
+        ));
+    }
+    let rel_project_path = relative_project_path(&parent_project_dir, &project_dir)
+        .expect("project dir is a subdirectory of its parent project's dir");
+
+    // Tool names are global, so only define the template's tools if the workspace doesn't
+    // already have them
+    let include_workspace_source = if template.tools.is_empty() {
+        false
+    } else {
+        let projects = load_projects(
+            config.workspace_dir.as_path(),
+            config.root_projects.iter().map(|s| s.as_str()),
+        )?;
+        let workspace = create_workspace(projects.values());
+        template
+            .tools
+            .iter()
+            .any(|t| !workspace.tools.contains_key(*t))
+    };
+
+    fs::create_dir_all(&project_dir)?;
+    let project_path = project_dir.join(PROJECT_FILE_NAME);
+    fs::write(&project_path, template_source(template, include_workspace_source))?;
+    println!("Created {}", project_path.display());
+
+    let mut parent_source = fs::read_to_string(&parent_project_path)?;
+    if !parent_source.is_empty() && !parent_source.ends_with('\n') {
+        parent_source.push('\n');
+    }
+    parent_source.push_str(format!("\nproject_dir(\"{}\")\n", rel_project_path).as_str());
+    fs::write(&parent_project_path, parent_source)?;
+    println!(
+        "Added project_dir(\"{}\") to {}",
+        rel_project_path,
+        parent_project_path.display()
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use cobble::config::parse_workspace_config_file;
+
+    use super::*;
+
+    #[test]
+    fn test_templates_load() {
+        for template in PROJECT_TEMPLATES {
+            let tmpdir = mktemp::Temp::new_dir().unwrap();
+            let workspace_dir = dunce::canonicalize(tmpdir.as_path()).unwrap();
+            fs::write(workspace_dir.join(WORKSPACE_CONFIG_FILE_NAME), WORKSPACE_CONFIG_SOURCE)
+                .unwrap();
+            fs::write(
+                workspace_dir.join(PROJECT_FILE_NAME),
+                template_source(template, true),
+            )
+            .unwrap();
+
+            let projects = load_projects(workspace_dir.as_path(), std::iter::once("."))
+                .unwrap_or_else(|e| panic!("template {} failed to load: {}", template.name, e));
+            let workspace = create_workspace(projects.values());
+            for tool in template.tools {
+                assert!(workspace.tools.contains_key(*tool));
+            }
+        }
+    }
+
+    #[test]
+    fn test_init_project() {
+        let tmpdir = mktemp::Temp::new_dir().unwrap();
+        let workspace_dir = dunce::canonicalize(tmpdir.as_path()).unwrap();
+        init_command(InitCommandInput {
+            cwd: workspace_dir.clone(),
+            template: None,
+        })
+        .unwrap();
+
+        // Read the config without the user config or environment, so that the test doesn't
+        // depend on the machine it runs on
+        let config = parse_workspace_config_file(
+            workspace_dir.join(WORKSPACE_CONFIG_FILE_NAME).as_path(),
+            None,
+        )
+        .unwrap();
+        init_project(
+            InitProjectCommandInput {
+                cwd: workspace_dir.clone(),
+                dir: PathBuf::from("libs/a"),
+                template: Some(String::from("python_poetry")),
+            },
+            &config,
+        )
+        .unwrap();
+        init_project(
+            InitProjectCommandInput {
+                cwd: workspace_dir.clone(),
+                dir: PathBuf::from("libs/b"),
+                template: Some(String::from("python_poetry")),
+            },
+            &config,
+        )
+        .unwrap();
+
+        let root_source = fs::read_to_string(workspace_dir.join(PROJECT_FILE_NAME)).unwrap();
+        assert!(root_source.contains("project_dir(\"libs/a\")"));
+        assert!(root_source.contains("project_dir(\"libs/b\")"));
+
+        // Only the first project that uses the template should define its tools
+        let a_source =
+            fs::read_to_string(workspace_dir.join("libs/a").join(PROJECT_FILE_NAME)).unwrap();
+        let b_source =
+            fs::read_to_string(workspace_dir.join("libs/b").join(PROJECT_FILE_NAME)).unwrap();
+        assert!(a_source.contains("name = \"poetry\""));
+        assert!(!b_source.contains("name = \"poetry\""));
+
+        let projects = load_projects(workspace_dir.as_path(), std::iter::once(".")).unwrap();
+        let workspace = create_workspace(projects.values());
+        assert!(workspace.tasks.contains_key("/libs/a/test"));
+        assert!(workspace.tasks.contains_key("/libs/b/test"));
+
+        assert!(init_project(
+            InitProjectCommandInput {
+                cwd: workspace_dir.clone(),
+                dir: PathBuf::from("libs/a"),
+                template: None,
+            },
+            &config,
+        )
+        .is_err());
+
+        // A workspace can't be created inside another one
+        assert!(init_command(InitCommandInput {
+            cwd: workspace_dir.join("libs/a"),
+            template: None,
+        })
+        .is_err());
+        assert!(!workspace_dir.join("libs/a").join(WORKSPACE_CONFIG_FILE_NAME).exists());
+    }
+}
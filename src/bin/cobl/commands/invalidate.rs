@@ -0,0 +1,68 @@
+// Cobble Build Automation
+// Copyright (C) 2024 Jeremiah Darais
+//
+// This program is licensed under the GPLv3.0 license (https://github.com/jdarais/cobble/blob/main/COPYING)
+
+use std::env::set_current_dir;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use cobble::config::get_workspace_config;
+use cobble::execute::execute::TaskExecutor;
+use cobble::load::load_projects;
+use cobble::query::{expand_project_tasks, find_reverse_dependencies};
+use cobble::task_selection::compute_selected_tasks;
+use cobble::workspace::create_workspace;
+
+pub struct InvalidateCommandInput {
+    pub cwd: PathBuf,
+    pub tasks: Vec<String>,
+    pub exclude: Vec<String>,
+    pub rdeps: bool,
+}
+
+pub fn invalidate_command(input: InvalidateCommandInput) -> anyhow::Result<()> {
+    let InvalidateCommandInput {
+        cwd,
+        tasks,
+        exclude,
+        rdeps,
+    } = input;
+
+    let config = get_workspace_config(cwd.as_path(), &Default::default())?;
+    set_current_dir(&config.workspace_dir)
+        .expect("found the workspace directory, so we should be able to set that as the cwd");
+
+    let projects = load_projects(
+        config.workspace_dir.as_path(),
+        config.root_projects.iter().map(|s| s.as_str()),
+    )?;
+    let workspace = create_workspace(projects.values());
+    let config = Arc::new(config);
+
+    let selected_tasks = compute_selected_tasks(
+        &tasks.iter().map(|s| s.as_str()).collect(),
+        &exclude.iter().map(|s| s.as_str()).collect(),
+        &workspace,
+        cwd.as_path(),
+        &config.workspace_dir,
+    )?;
+
+    let mut invalidated_tasks = expand_project_tasks(&workspace, selected_tasks);
+    if rdeps {
+        invalidated_tasks = find_reverse_dependencies(&workspace, invalidated_tasks);
+    }
+
+    let executor = TaskExecutor::new(
+        config.clone(),
+        config.workspace_dir.join(".cobble.db").as_path(),
+    )?;
+    executor.invalidate_tasks(invalidated_tasks.iter())?;
+
+    invalidated_tasks.sort();
+    for task_name in invalidated_tasks {
+        println!("Invalidated {}", task_name);
+    }
+
+    Ok(())
+}
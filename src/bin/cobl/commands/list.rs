@@ -3,18 +3,29 @@
 //
 // This program is licensed under the GPLv3.0 license (https://github.com/jdarais/cobble/blob/main/COPYING)
 
+use std::collections::BTreeMap;
 use std::env::set_current_dir;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use cobble::config::{find_nearest_project_dir, get_workspace_config};
-use cobble::workspace::create_workspace;
+use cobble::workspace::{create_workspace, Task, TaskType, Workspace};
+use cobble::workspace_json::TaskSummaryJson;
 use cobble::load::load_projects;
-use cobble::query::{find_tasks_for_dir, find_tasks_for_query};
+use cobble::query::{
+    exclude_tasks_for_query, find_tasks_for_dir, find_tasks_for_query, RECURSIVE_QUERY_SEPARATOR,
+};
 use cobble::resolve::project_path_to_project_name;
 
+const TAB: &str = "  ";
+
 pub struct ListCommandInput {
     pub cwd: PathBuf,
     pub tasks: Vec<String>,
+    pub exclude: Vec<String>,
+    pub all: bool,
+    pub tree: bool,
+    pub json: bool,
 }
 
 pub fn list_command(input: ListCommandInput) -> anyhow::Result<()> {
@@ -33,6 +44,12 @@ pub fn list_command(input: ListCommandInput) -> anyhow::Result<()> {
     let project_name = project_path_to_project_name(project_dir.as_path()).unwrap();
 
     let mut tasks = match input.tasks.len() {
+        // The tree shows the current project along with all of its subprojects
+        0 if input.tree => find_tasks_for_query(
+            &workspace,
+            project_name.as_str(),
+            std::iter::once(RECURSIVE_QUERY_SEPARATOR),
+        )?,
         0 => find_tasks_for_dir(
             &workspace,
             config.workspace_dir.as_path(),
@@ -42,21 +59,161 @@ pub fn list_command(input: ListCommandInput) -> anyhow::Result<()> {
             &workspace,
             project_name.as_str(),
             input.tasks.iter().map(|s| s.as_str()),
-        )
-        .unwrap(),
+        )?,
     };
+    if !input.exclude.is_empty() {
+        tasks = exclude_tasks_for_query(
+            &workspace,
+            project_name.as_str(),
+            tasks,
+            input.exclude.iter().map(|s| s.as_str()),
+        )?;
+    }
+    if !input.all {
+        tasks.retain(|t| {
+            workspace
+                .tasks
+                .get(t)
+                .map(|task| !task.is_private)
+                .unwrap_or(true)
+        });
+    }
     tasks.sort();
     let tasks = tasks;
 
+    if input.json {
+        let entries: Vec<TaskSummaryJson> = tasks
+            .iter()
+            .filter_map(|t| workspace.tasks.get(t))
+            .map(|task| TaskSummaryJson::from(task.as_ref()))
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+    } else if input.tree {
+        print_task_tree(&workspace, &tasks);
+    } else {
+        print_task_list(&workspace, project_name.as_str(), &tasks);
+    }
+
+    Ok(())
+}
+
+fn relative_task_name<'a>(name: &'a str, project_name: &str) -> &'a str {
+    name.strip_prefix(project_name)
+        .map(|n| n.strip_prefix("/").unwrap_or(n))
+        .map(|n| if !n.is_empty() { n } else { "(default)" })
+        .unwrap_or(name)
+}
+
+fn print_task_line(indent: &str, name: &str, description: &Option<Arc<str>>, name_width: usize) {
+    match description {
+        Some(description) => println!("{indent}{name:name_width$}{TAB}{description}"),
+        None => println!("{indent}{name}"),
+    }
+}
+
+/// Print the tasks in a flat list, relative to the current project.  Tasks in a group are
+/// listed under the group name, after the tasks that aren't in a group.
+fn print_task_list(workspace: &Workspace, project_name: &str, tasks: &[Arc<str>]) {
+    let mut ungrouped_tasks: Vec<(&str, &Task)> = Vec::new();
+    let mut grouped_tasks: BTreeMap<&str, Vec<(&str, &Task)>> = BTreeMap::new();
     for name in tasks {
-        let maybe_rel_name = name
-            .strip_prefix(project_name.as_str())
-            .map(|n| n.strip_prefix("/").unwrap_or(n))
-            .map(|n| if n.len() > 0 { n } else { "(default)" })
-            .unwrap_or(name.as_ref());
+        let task = match workspace.tasks.get(name) {
+            Some(task) => task,
+            None => continue,
+        };
+        let rel_name = relative_task_name(name.as_ref(), project_name);
+        match &task.group {
+            Some(group) => grouped_tasks
+                .entry(group.as_ref())
+                .or_default()
+                .push((rel_name, task)),
+            None => ungrouped_tasks.push((rel_name, task)),
+        }
+    }
+
+    let name_width = ungrouped_tasks
+        .iter()
+        .chain(grouped_tasks.values().flatten())
+        .filter(|(_, task)| task.description.is_some())
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0);
 
-        println!("{}", maybe_rel_name);
+    for (name, task) in ungrouped_tasks.iter() {
+        print_task_line("", name, &task.description, name_width);
     }
 
-    Ok(())
+    for (i, (group, group_tasks)) in grouped_tasks.iter().enumerate() {
+        if i > 0 || !ungrouped_tasks.is_empty() {
+            println!();
+        }
+        println!("{group}:");
+        for (name, task) in group_tasks.iter() {
+            print_task_line(TAB, name, &task.description, name_width);
+        }
+    }
+}
+
+/// Print the tasks grouped by project, with each project indented under the closest listed
+/// project that contains it
+fn print_task_tree(workspace: &Workspace, tasks: &[Arc<str>]) {
+    let mut project_tasks: BTreeMap<&str, Vec<(&str, &Task)>> = BTreeMap::new();
+    for name in tasks {
+        let task = match workspace.tasks.get(name) {
+            Some(task) => task,
+            None => continue,
+        };
+        match task.task_type {
+            TaskType::Project => {
+                project_tasks.entry(task.name.as_ref()).or_default();
+            }
+            TaskType::Task | TaskType::BuildEnv => {
+                let rel_name = relative_task_name(name.as_ref(), task.project_name.as_ref());
+                project_tasks
+                    .entry(task.project_name.as_ref())
+                    .or_default()
+                    .push((rel_name, task));
+            }
+        }
+    }
+
+    let name_width = project_tasks
+        .values()
+        .flatten()
+        .filter(|(_, task)| task.description.is_some())
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0);
+
+    // Sort by path segments, so that subprojects come right after their parent project
+    let mut project_names: Vec<&str> = project_tasks.keys().cloned().collect();
+    project_names.sort_by_key(|p| p.split('/').collect::<Vec<&str>>());
+
+    for project_name in project_names.iter() {
+        let tasks = &project_tasks[project_name];
+        let depth = project_names
+            .iter()
+            .filter(|p| is_ancestor_project(p, project_name))
+            .count();
+        let indent = TAB.repeat(depth);
+
+        // The root project's name is empty
+        let heading = if project_name.is_empty() { "/" } else { project_name };
+        println!("{indent}{heading}");
+        for (name, task) in tasks.iter() {
+            print_task_line(format!("{indent}{TAB}").as_str(), name, &task.description, name_width);
+        }
+    }
+}
+
+fn is_ancestor_project(ancestor: &str, project_name: &str) -> bool {
+    if ancestor == project_name {
+        return false;
+    }
+
+    ancestor.is_empty()
+        || project_name
+            .strip_prefix(ancestor)
+            .map(|rest| rest.starts_with('/'))
+            .unwrap_or(false)
 }
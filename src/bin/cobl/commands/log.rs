@@ -0,0 +1,107 @@
+// Cobble Build Automation
+// Copyright (C) 2024 Jeremiah Darais
+//
+// This program is licensed under the GPLv3.0 license (https://github.com/jdarais/cobble/blob/main/COPYING)
+
+use std::env::set_current_dir;
+use std::path::PathBuf;
+
+use cobble::config::get_workspace_config;
+use cobble::execute::job_log::{job_log_path, list_job_log_runs, read_job_log, LogStream};
+use cobble::load::load_projects;
+use cobble::task_selection::compute_selected_tasks;
+use cobble::workspace::create_workspace;
+
+pub struct LogCommandInput {
+    pub cwd: PathBuf,
+    pub task: String,
+    pub run_id: Option<String>,
+    pub list: bool,
+    pub timestamps: bool,
+}
+
+pub fn log_command(input: LogCommandInput) -> anyhow::Result<()> {
+    let LogCommandInput {
+        cwd,
+        task,
+        run_id,
+        list,
+        timestamps,
+    } = input;
+
+    let config = get_workspace_config(cwd.as_path(), &Default::default())?;
+    set_current_dir(&config.workspace_dir)
+        .expect("found the workspace directory, so we should be able to set that as the cwd");
+
+    let projects = load_projects(
+        config.workspace_dir.as_path(),
+        config.root_projects.iter().map(|s| s.as_str()),
+    )?;
+    let workspace = create_workspace(projects.values());
+
+    let selected_tasks = compute_selected_tasks(
+        &vec![task.as_str()],
+        &Vec::new(),
+        &workspace,
+        cwd.as_path(),
+        &config.workspace_dir,
+    )?;
+
+    let task_name = match selected_tasks.as_slice() {
+        [task_name] => task_name.clone(),
+        [] => {
+            return Err(anyhow::anyhow!("No tasks found that match \"{}\"", task));
+        }
+        _ => {
+            return Err(anyhow::anyhow!(
+                "\"{}\" matches more than one task: {}",
+                task,
+                selected_tasks.join(", ")
+            ));
+        }
+    };
+
+    let run_ids = list_job_log_runs(&config.workspace_dir, &task_name)?;
+
+    if list {
+        for run_id in run_ids.iter() {
+            println!("{}", run_id);
+        }
+        return Ok(());
+    }
+
+    let run_id = match run_id {
+        Some(run_id) => {
+            if !run_ids.contains(&run_id) {
+                return Err(anyhow::anyhow!(
+                    "No log found for run \"{}\" of {}",
+                    run_id,
+                    task_name
+                ));
+            }
+            run_id
+        }
+        None => match run_ids.last() {
+            Some(run_id) => run_id.clone(),
+            None => {
+                return Err(anyhow::anyhow!("No logs found for {}", task_name));
+            }
+        },
+    };
+
+    let log_lines = read_job_log(&job_log_path(&config.workspace_dir, &task_name, &run_id))?;
+    for line in log_lines {
+        let text = if timestamps {
+            format!("[{}] {}", line.time, line.text)
+        } else {
+            line.text
+        };
+
+        match line.stream {
+            LogStream::Stderr => eprintln!("{}", text),
+            LogStream::Stdout | LogStream::Status => println!("{}", text),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,241 @@
+// Cobble Build Automation
+// Copyright (C) 2024 Jeremiah Darais
+//
+// This program is licensed under the GPLv3.0 license (https://github.com/jdarais/cobble/blob/main/COPYING)
+
+use std::env::set_current_dir;
+use std::ffi::c_void;
+use std::io::{stdin, stdout, Write};
+use std::path::PathBuf;
+
+use cobble::config::{find_nearest_project_dir, get_workspace_config};
+use cobble::load::{init_lua_for_project_config, load_project_files};
+use cobble::lua::cmd::kill_spawned_processes;
+use cobble::lua::lua_env::create_lua_env;
+use cobble::resolve::project_path_to_project_name;
+
+const TAB: &str = "  ";
+
+pub struct LuaCommandInput {
+    pub cwd: PathBuf,
+    pub script: Option<PathBuf>,
+    pub args: Vec<String>,
+}
+
+/// Start a Lua environment like the one that project files are run in, with the project files of
+/// the workspace already loaded.  If a script is given, run it, otherwise read statements and
+/// expressions from stdin.
+pub fn lua_command(input: LuaCommandInput) -> anyhow::Result<()> {
+    let LuaCommandInput { cwd, script, args } = input;
+
+    // Resolve the script relative to where cobl was run, before changing directories
+    let script = script.map(|s| cwd.join(s));
+
+    let config = get_workspace_config(cwd.as_path(), &Default::default())?;
+
+    let lua = create_lua_env(config.workspace_dir.as_path())?;
+    init_lua_for_project_config(&lua, config.workspace_dir.as_path())?;
+
+    // A project file with an error is a good reason to start a REPL, so keep going with whatever
+    // was loaded before the error
+    let load_res = load_project_files(
+        &lua,
+        config.workspace_dir.as_path(),
+        config.root_projects.iter().map(|s| s.as_str()),
+    );
+    if let Err(e) = load_res {
+        eprintln!("Error loading project files: {}", e);
+    }
+
+    // Act as if we're in the project file of the current project, so that relative paths and
+    // PROJECT behave the same way they would there
+    let project_dir = find_nearest_project_dir(cwd.as_path(), &config.workspace_dir)?;
+    let project_name = match project_path_to_project_name(project_dir.as_path())?.as_str() {
+        "" => String::from("/"),
+        name => name.to_owned(),
+    };
+    set_current_dir(config.workspace_dir.join(project_dir.as_path()))?;
+
+    let projects: mlua::Table = lua.globals().get::<_, mlua::Table>("cobble")?.get("projects")?;
+    let project: mlua::Value = projects.get(project_name.as_str())?;
+    lua.globals().set("PROJECT", project)?;
+
+    let result = match script {
+        Some(script) => run_lua_script(&lua, script, args),
+        None => run_lua_repl(&lua, project_name.as_str()),
+    };
+
+    kill_spawned_processes(&lua)?;
+    result
+}
+
+fn run_lua_script(lua: &mlua::Lua, script: PathBuf, args: Vec<String>) -> anyhow::Result<()> {
+    // Set up the arg table the same way the standalone lua interpreter does
+    let arg_table = lua.create_table()?;
+    arg_table.set(0, script.display().to_string())?;
+    for (i, arg) in args.into_iter().enumerate() {
+        arg_table.set(i + 1, arg)?;
+    }
+    lua.globals().set("arg", arg_table)?;
+
+    let script_source = std::fs::read(&script)
+        .map_err(|e| anyhow::anyhow!("Unable to read {}: {}", script.display(), e))?;
+    lua.load(script_source)
+        .set_name(format!("@{}", script.display()))
+        .exec()?;
+
+    Ok(())
+}
+
+fn print_values(values: mlua::MultiValue) {
+    if values.is_empty() {
+        return;
+    }
+
+    let formatted: Vec<String> = values
+        .iter()
+        .map(|v| format_lua_value(v, 0, &mut Vec::new()))
+        .collect();
+    println!("{}", formatted.join("\t"));
+}
+
+fn run_lua_repl(lua: &mlua::Lua, project_name: &str) -> anyhow::Result<()> {
+    println!("Lua REPL in project {}.  Press ctrl-d to exit.", project_name);
+
+    let mut buffer = String::new();
+    loop {
+        print!("{}", if buffer.is_empty() { "> " } else { ">> " });
+        stdout().flush()?;
+
+        let mut line = String::new();
+        if stdin().read_line(&mut line)? == 0 {
+            println!();
+            return Ok(());
+        }
+        buffer.push_str(line.as_str());
+
+        // Try the input as an expression first, so that its value gets printed.  Only fall back
+        // to running it as a statement if it doesn't compile, so that it doesn't run twice.
+        let expr_func = lua
+            .load(format!("return {}", buffer))
+            .set_name("=stdin")
+            .into_function();
+        let result = match expr_func {
+            Ok(func) => func.call::<_, mlua::MultiValue>(()),
+            Err(_) => lua.load(buffer.as_str()).set_name("=stdin").eval(),
+        };
+
+        match result {
+            Ok(values) => print_values(values),
+            Err(mlua::Error::SyntaxError {
+                incomplete_input: true,
+                ..
+            }) => continue,
+            Err(e) => eprintln!("{}", e),
+        }
+        buffer.clear();
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+fn format_table_key(key: &mlua::Value) -> String {
+    match key {
+        mlua::Value::String(s) => match s.to_str() {
+            Ok(s) if is_identifier(s) => s.to_owned(),
+            _ => format!("[{}]", format_lua_value(key, 0, &mut Vec::new())),
+        },
+        _ => format!("[{}]", format_lua_value(key, 0, &mut Vec::new())),
+    }
+}
+
+/// Sort integer keys first, in order, followed by the other keys sorted by their formatted value
+fn table_key_sort_key(key: &mlua::Value) -> (bool, i64, String) {
+    match key {
+        mlua::Value::Integer(i) => (false, *i, String::new()),
+        _ => (true, 0, format_table_key(key)),
+    }
+}
+
+/// Format a Lua value the way it would be written in Lua source, including the contents of
+/// tables.  Tables that contain themselves are only expanded once.
+pub fn format_lua_value(value: &mlua::Value, indent: usize, seen: &mut Vec<*const c_void>) -> String {
+    match value {
+        mlua::Value::String(s) => format!("{:?}", s.to_string_lossy()),
+        mlua::Value::Table(t) => {
+            if seen.contains(&value.to_pointer()) {
+                return format!("<cycle: {:?}>", value.to_pointer());
+            }
+
+            let mut pairs: Vec<(mlua::Value, mlua::Value)> = t
+                .clone()
+                .pairs::<mlua::Value, mlua::Value>()
+                .filter_map(|pair| pair.ok())
+                .collect();
+            if pairs.is_empty() {
+                return String::from("{}");
+            }
+            pairs.sort_by_cached_key(|(k, _)| table_key_sort_key(k));
+
+            seen.push(value.to_pointer());
+            let item_indent = TAB.repeat(indent + 1);
+            let mut formatted = String::from("{\n");
+            for (k, v) in pairs.iter() {
+                formatted.push_str(
+                    format!(
+                        "{}{} = {},\n",
+                        item_indent,
+                        format_table_key(k),
+                        format_lua_value(v, indent + 1, seen)
+                    )
+                    .as_str(),
+                );
+            }
+            formatted.push_str(format!("{}}}", TAB.repeat(indent)).as_str());
+            seen.pop();
+
+            formatted
+        }
+        _ => value
+            .to_string()
+            .unwrap_or_else(|_| String::from(value.type_name())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_lua_value() {
+        let lua = mlua::Lua::new();
+        let value: mlua::Value = lua
+            .load(r#"{ "a", 2, name = "x", ["two words"] = true, nested = { empty = {} } }"#)
+            .eval()
+            .unwrap();
+
+        assert_eq!(
+            format_lua_value(&value, 0, &mut Vec::new()),
+            r#"{
+  [1] = "a",
+  [2] = 2,
+  ["two words"] = true,
+  name = "x",
+  nested = {
+    empty = {},
+  },
+}"#
+        );
+
+        let cycle: mlua::Value = lua.load("local t = {}; t.t = t; return t").eval().unwrap();
+        assert!(format_lua_value(&cycle, 0, &mut Vec::new()).contains("t = <cycle"));
+    }
+}
@@ -4,8 +4,16 @@
 // This program is licensed under the GPLv3.0 license (https://github.com/jdarais/cobble/blob/main/COPYING)
 
 pub mod clean;
+pub mod completions;
+pub mod dump_workspace;
 pub mod env;
+pub mod init;
+pub mod invalidate;
 pub mod list;
+pub mod log;
+pub mod lua;
 pub mod run;
 pub mod show;
+pub mod status;
 pub mod tool;
+pub mod vars;
@@ -3,58 +3,125 @@
 //
 // This program is licensed under the GPLv3.0 license (https://github.com/jdarais/cobble/blob/main/COPYING)
 
+use std::collections::HashMap;
 use std::env::set_current_dir;
 use std::path::PathBuf;
 use std::sync::Arc;
 
 use cobble::calc_artifacts::calculate_artifacts;
-use cobble::config::{get_workspace_config, TaskOutputCondition, WorkspaceConfigArgs};
+use cobble::changed_files::{filter_tasks_affected_by_files, find_changed_files};
+use cobble::config::{get_workspace_config, OutputMode, TaskOutputCondition, WorkspaceConfigArgs};
 use cobble::dependency::resolve_calculated_dependencies_in_subtrees;
-use cobble::execute::execute::TaskExecutor;
+use cobble::execute::execute::{DependencyMode, ExecuteOptions, TaskExecutor};
 use cobble::load::load_projects;
-use cobble::task_selection::compute_selected_tasks;
+use cobble::query::expand_project_tasks;
+use cobble::task_selection::{
+    compute_selected_tasks, read_shard_durations, select_shard, write_shard_durations, Shard,
+};
+use cobble::trace::{TraceSpan, Tracer, MAIN_THREAD_ID};
+use cobble::vars::resolve_declared_vars;
 use cobble::workspace::create_workspace;
 
 pub struct RunCommandInput {
     pub cwd: PathBuf,
     pub tasks: Vec<String>,
+    pub exclude: Vec<String>,
+    pub changed_since: Option<String>,
+    pub changed_files: Vec<String>,
+    pub shard: Option<Shard>,
+    pub shard_durations: Option<PathBuf>,
+    pub save_durations: Option<PathBuf>,
     pub vars: Vec<String>,
+    pub secret_vars: Vec<String>,
+    pub var_files: Vec<PathBuf>,
+    pub profile: Option<String>,
     pub force_run_tasks: bool,
+    pub no_deps: bool,
+    pub only_deps: bool,
+    pub from: Vec<String>,
+    pub force_only: Vec<String>,
     pub num_threads: Option<u8>,
     pub show_stdout: Option<TaskOutputCondition>,
-    pub show_stderr: Option<TaskOutputCondition>
+    pub show_stderr: Option<TaskOutputCondition>,
+    pub output_mode: Option<OutputMode>,
+    pub trace: Option<PathBuf>,
+}
+
+fn trace_span<'a>(tracer: &'a Option<Arc<Tracer>>, name: &str) -> Option<TraceSpan<'a>> {
+    tracer.as_ref().map(|t| t.span(name, "phase", MAIN_THREAD_ID))
 }
 
 pub fn run_command(input: RunCommandInput) -> anyhow::Result<()> {
+    let trace_path = input.trace.as_ref().map(|p| input.cwd.join(p));
+    let tracer = trace_path.as_ref().map(|_| Arc::new(Tracer::new()));
+
+    // The executor is dropped by the time run_tasks returns, so all workers have finished
+    // recording their spans before the trace is written
+    let result = run_tasks(input, &tracer);
+
+    if let (Some(tracer), Some(trace_path)) = (tracer, trace_path) {
+        if let Err(e) = tracer.write_to_file(trace_path.as_path()) {
+            eprintln!("Error writing trace to {}: {}", trace_path.display(), e);
+        }
+    }
+
+    result
+}
+
+fn run_tasks(input: RunCommandInput, tracer: &Option<Arc<Tracer>>) -> anyhow::Result<()> {
     let RunCommandInput {
         cwd,
         tasks,
+        exclude,
+        changed_since,
+        changed_files,
+        shard,
+        shard_durations,
+        save_durations,
         vars,
+        secret_vars,
+        var_files,
+        profile,
         force_run_tasks,
+        no_deps,
+        only_deps,
+        from,
+        force_only,
         num_threads,
         show_stdout,
-        show_stderr
+        show_stderr,
+        output_mode,
+        trace: _,
     } = input;
 
     let ws_config_args = WorkspaceConfigArgs {
         vars,
+        secret_vars,
+        var_files,
+        profile,
         force_run_tasks: Some(force_run_tasks),
         num_threads: num_threads,
         show_stdout,
-        show_stderr
+        show_stderr,
+        output_mode,
     };
-    let config = Arc::new(get_workspace_config(cwd.as_path(), &ws_config_args)?);
+    let mut config = get_workspace_config(cwd.as_path(), &ws_config_args)?;
     set_current_dir(&config.workspace_dir)
         .expect("found the workspace directory, so we should be able to set that as the cwd");
 
+    let load_span = trace_span(tracer, "load_projects");
     let projects = load_projects(
         config.workspace_dir.as_path(),
         config.root_projects.iter().map(|s| s.as_str()),
     )?;
+    drop(load_span);
     let mut workspace = create_workspace(projects.values());
+    resolve_declared_vars(&workspace.vars, &mut config)?;
+    let config = Arc::new(config);
 
     let selected_tasks = compute_selected_tasks(
         &tasks.iter().map(|s| s.as_str()).collect(),
+        &exclude.iter().map(|s| s.as_str()).collect(),
         &workspace,
         cwd.as_path(),
         &config.workspace_dir,
@@ -64,24 +131,132 @@ pub fn run_command(input: RunCommandInput) -> anyhow::Result<()> {
         return Err(anyhow::anyhow!("No tasks found that match \"{}\"", tasks.join(" ")));
     }
 
+    let dependency_mode = match (no_deps, only_deps) {
+        (true, _) => DependencyMode::NoDeps,
+        (_, true) => DependencyMode::OnlyDeps,
+        _ => DependencyMode::All,
+    };
+
+    // A project only runs its tasks through its dependencies, so run the project's tasks directly
+    // when dependencies are left out
+    let selected_tasks = match dependency_mode {
+        DependencyMode::All => selected_tasks,
+        _ => expand_project_tasks(&workspace, selected_tasks),
+    };
+
+    let force_run_from = match from.len() {
+        0 => Vec::new(),
+        _ => compute_selected_tasks(
+            &from.iter().map(|s| s.as_str()).collect(),
+            &Vec::new(),
+            &workspace,
+            cwd.as_path(),
+            &config.workspace_dir,
+        )?,
+    };
+
+    let force_run_tasks = match force_only.len() {
+        0 => Vec::new(),
+        _ => expand_project_tasks(
+            &workspace,
+            compute_selected_tasks(
+                &force_only.iter().map(|s| s.as_str()).collect(),
+                &Vec::new(),
+                &workspace,
+                cwd.as_path(),
+                &config.workspace_dir,
+            )?,
+        ),
+    };
+
+    let changed_files = find_changed_files(
+        cwd.as_path(),
+        &config.workspace_dir,
+        changed_since.as_deref(),
+        &changed_files,
+    )?;
+
     // Resolve calculated artifacts and dependencies
     let mut executor = TaskExecutor::new(
         config.clone(),
         config.workspace_dir.join(".cobble.db").as_path(),
     )?;
+    if let Some(tracer) = tracer {
+        executor.set_tracer(tracer.clone());
+    }
 
     println!("# Computing calculated artifacts #");
+    let calc_artifacts_span = trace_span(tracer, "calculate_artifacts");
     calculate_artifacts(&mut workspace, &mut executor)?;
+    drop(calc_artifacts_span);
 
     println!("# Computing calculated dependencies #");
+    let calc_deps_span = trace_span(tracer, "resolve_calculated_dependencies");
     resolve_calculated_dependencies_in_subtrees(
         selected_tasks.iter(),
         &mut workspace,
         &mut executor,
     )?;
+    drop(calc_deps_span);
+
+    // Calculated artifacts and dependencies need to be known before the changed files can be
+    // matched to tasks
+    let selected_tasks = match changed_files {
+        Some(changed_files) => {
+            let affected_tasks =
+                filter_tasks_affected_by_files(&workspace, selected_tasks, &changed_files);
+            if affected_tasks.is_empty() {
+                println!("No selected tasks are affected by the changed files");
+                return Ok(());
+            }
+            affected_tasks
+        }
+        None => selected_tasks,
+    };
+
+    let selected_tasks = match shard {
+        Some(shard) => {
+            let tasks = expand_project_tasks(&workspace, selected_tasks);
+            let durations = match &shard_durations {
+                Some(path) => read_shard_durations(cwd.join(path).as_path()).map_err(|e| {
+                    anyhow::anyhow!("Error reading shard durations from {}: {}", path.display(), e)
+                })?,
+                None => HashMap::new(),
+            };
+            let task_count = tasks.len();
+            let shard_tasks = select_shard(tasks, shard, &durations);
+            if shard_tasks.is_empty() {
+                println!("No tasks were assigned to shard {}", shard);
+                return Ok(());
+            }
+            println!(
+                "# Running {} of {} selected tasks in shard {} #",
+                shard_tasks.len(),
+                task_count,
+                shard
+            );
+            shard_tasks
+        }
+        None => selected_tasks,
+    };
 
     println!("# Executing tasks #");    
-    executor.execute_tasks(&workspace, selected_tasks.iter())?;
+    let execute_span = trace_span(tracer, "execute_tasks");
+    let execute_options = ExecuteOptions {
+        dependency_mode,
+        force_run_from,
+        force_run_tasks,
+    };
+    executor.execute_tasks_with_options(&workspace, selected_tasks.iter(), &execute_options)?;
+    drop(execute_span);
+
+    if let Some(path) = save_durations {
+        let tasks = expand_project_tasks(&workspace, selected_tasks);
+        write_shard_durations(cwd.join(&path).as_path(), &executor.task_durations(tasks.iter()))
+            .map_err(|e| {
+                anyhow::anyhow!("Error writing task durations to {}: {}", path.display(), e)
+            })?;
+    }
 
     Ok(())
 }
@@ -1,33 +1,46 @@
 use std::{env::set_current_dir, path::PathBuf, sync::Arc};
 
-use cobble::{calc_artifacts::calculate_artifacts, config::{get_workspace_config, TaskOutputCondition, WorkspaceConfigArgs}, dependency::resolve_calculated_dependencies_in_subtrees, execute::execute::TaskExecutor, load::load_projects, task_selection::compute_selected_tasks, workspace::create_workspace};
+use cobble::{calc_artifacts::calculate_artifacts, config::{get_workspace_config, TaskOutputCondition, WorkspaceConfigArgs}, dependency::resolve_calculated_dependencies_in_subtrees, execute::execute::TaskExecutor, load::load_projects, task_selection::compute_selected_tasks, vars::resolve_declared_vars, workspace::create_workspace, workspace_json::TaskJson};
 
 const TAB: &str = "  ";
 
 pub struct ShowTaskInput {
     pub cwd: PathBuf,
     pub tasks: Vec<String>,
+    pub exclude: Vec<String>,
     pub vars: Vec<String>,
-    pub num_threads: Option<u8>
+    pub secret_vars: Vec<String>,
+    pub var_files: Vec<PathBuf>,
+    pub profile: Option<String>,
+    pub num_threads: Option<u8>,
+    pub json: bool,
 }
 
 pub fn show_task_command(input: ShowTaskInput) -> anyhow::Result<()> {
     let ShowTaskInput {
         cwd,
         tasks,
+        exclude,
         vars,
-        num_threads
+        secret_vars,
+        var_files,
+        profile,
+        num_threads,
+        json,
     } = input;
 
     let ws_config_args = WorkspaceConfigArgs {
         vars,
+        secret_vars,
+        var_files,
+        profile,
         num_threads: num_threads,
         show_stdout: Some(TaskOutputCondition::Never),
         show_stderr: Some(TaskOutputCondition::Never),
         ..Default::default()
     };
 
-    let config = Arc::new(get_workspace_config(cwd.as_path(), &ws_config_args)?);
+    let mut config = get_workspace_config(cwd.as_path(), &ws_config_args)?;
     set_current_dir(&config.workspace_dir)
         .expect("found the workspace directory, so we should be able to set that as the cwd");
 
@@ -36,9 +49,12 @@ pub fn show_task_command(input: ShowTaskInput) -> anyhow::Result<()> {
         config.root_projects.iter().map(|s| s.as_str()),
     )?;
     let mut workspace = create_workspace(projects.values());
+    resolve_declared_vars(&workspace.vars, &mut config)?;
+    let config = Arc::new(config);
 
     let selected_tasks = compute_selected_tasks(
         &tasks.iter().map(|s| s.as_str()).collect(),
+        &exclude.iter().map(|s| s.as_str()).collect(),
         &workspace,
         cwd.as_path(),
         &config.workspace_dir,
@@ -54,6 +70,10 @@ pub fn show_task_command(input: ShowTaskInput) -> anyhow::Result<()> {
         config.workspace_dir.join(".cobble.db").as_path(),
     )?;
 
+    if json {
+        executor.set_job_output_to_stderr();
+    }
+
     calculate_artifacts(&mut workspace, &mut executor)?;
 
     resolve_calculated_dependencies_in_subtrees(
@@ -62,10 +82,39 @@ pub fn show_task_command(input: ShowTaskInput) -> anyhow::Result<()> {
         &mut executor,
     )?;
 
+    if json {
+        let task_json: Vec<TaskJson> = selected_tasks
+            .iter()
+            .map(|t| TaskJson::from(workspace.tasks.get(t).unwrap().as_ref()))
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&task_json)?);
+        return Ok(());
+    }
+
     for task_name in selected_tasks.iter() {
         let task = workspace.tasks.get(task_name).unwrap();
         println!("Task: {task_name}");
 
+        if let Some(description) = &task.description {
+            println!("{TAB}Description: {description}");
+        }
+
+        if let Some(group) = &task.group {
+            println!("{TAB}Group: {group}");
+        }
+
+        if task.is_private {
+            println!("{TAB}Private: true");
+        }
+
+        if !task.tags.is_empty() {
+            println!("{TAB}Tags: {}", task.tags.join(", "));
+        }
+
+        if task.description.is_some() || task.group.is_some() || task.is_private || !task.tags.is_empty() {
+            println!();
+        }
+
         println!("{TAB}Artifacts:");
         if task.artifacts.files.len() == 0 {
             println!("{TAB}{TAB}<none>");
@@ -0,0 +1,171 @@
+// Cobble Build Automation
+// Copyright (C) 2024 Jeremiah Darais
+//
+// This program is licensed under the GPLv3.0 license (https://github.com/jdarais/cobble/blob/main/COPYING)
+
+use std::env::set_current_dir;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use cobble::calc_artifacts::calculate_artifacts;
+use cobble::config::{get_workspace_config, WorkspaceConfigArgs};
+use cobble::dependency::resolve_calculated_dependencies_in_subtrees;
+use cobble::execute::execute::TaskExecutor;
+use cobble::execute::status::{StaleReason, TaskStatus};
+use cobble::load::load_projects;
+use cobble::task_selection::compute_selected_tasks;
+use cobble::vars::resolve_declared_vars;
+use cobble::workspace::{create_workspace, TaskType};
+
+pub struct StatusCommandInput {
+    pub cwd: PathBuf,
+    pub tasks: Vec<String>,
+    pub exclude: Vec<String>,
+    pub vars: Vec<String>,
+    pub secret_vars: Vec<String>,
+    pub var_files: Vec<PathBuf>,
+    pub profile: Option<String>,
+    pub calc: bool,
+}
+
+pub fn status_command(input: StatusCommandInput) -> anyhow::Result<()> {
+    let StatusCommandInput {
+        cwd,
+        tasks,
+        exclude,
+        vars,
+        secret_vars,
+        var_files,
+        profile,
+        calc,
+    } = input;
+
+    let ws_config_args = WorkspaceConfigArgs {
+        vars,
+        secret_vars,
+        var_files,
+        profile,
+        ..Default::default()
+    };
+    let mut config = get_workspace_config(cwd.as_path(), &ws_config_args)?;
+    set_current_dir(&config.workspace_dir)
+        .expect("found the workspace directory, so we should be able to set that as the cwd");
+
+    let projects = load_projects(
+        config.workspace_dir.as_path(),
+        config.root_projects.iter().map(|s| s.as_str()),
+    )?;
+    let mut workspace = create_workspace(projects.values());
+    resolve_declared_vars(&workspace.vars, &mut config)?;
+    let config = Arc::new(config);
+
+    let selected_tasks = compute_selected_tasks(
+        &tasks.iter().map(|s| s.as_str()).collect(),
+        &exclude.iter().map(|s| s.as_str()).collect(),
+        &workspace,
+        cwd.as_path(),
+        &config.workspace_dir,
+    )?;
+
+    if selected_tasks.is_empty() {
+        return Err(anyhow::anyhow!("No tasks found that match \"{}\"", tasks.join(" ")));
+    }
+
+    let mut executor = TaskExecutor::new(
+        config.clone(),
+        config.workspace_dir.join(".cobble.db").as_path(),
+    )?;
+
+    // Resolving calculated artifacts and dependencies means running tasks, so only do it when
+    // asked.  Otherwise, tasks with calculated artifacts or dependencies are reported as unresolved.
+    if calc {
+        calculate_artifacts(&mut workspace, &mut executor)?;
+        resolve_calculated_dependencies_in_subtrees(
+            selected_tasks.iter(),
+            &mut workspace,
+            &mut executor,
+        )?;
+    }
+
+    let statuses = executor.task_statuses(&workspace, selected_tasks.iter(), calc)?;
+
+    let mut task_names: Vec<&Arc<str>> = statuses
+        .keys()
+        .filter(|name| {
+            workspace
+                .tasks
+                .get(*name)
+                .map(|t| !matches!(t.task_type, TaskType::Project))
+                .unwrap_or(false)
+        })
+        .collect();
+    task_names.sort();
+
+    let name_width = task_names.iter().map(|n| n.len()).max().unwrap_or(0);
+
+    let mut num_up_to_date: usize = 0;
+    let mut num_stale: usize = 0;
+    let mut num_always_run: usize = 0;
+    let mut num_maybe_stale: usize = 0;
+    let mut num_never_run: usize = 0;
+    let mut num_unresolved: usize = 0;
+    for task_name in task_names {
+        let status = &statuses[task_name];
+        match status {
+            TaskStatus::UpToDate => {
+                num_up_to_date += 1;
+                println!("{:width$}  {}", task_name, status, width = name_width);
+            }
+            TaskStatus::Stale(StaleReason::AlwaysRun) => {
+                num_always_run += 1;
+                println!("{:width$}  always run", task_name, width = name_width);
+            }
+            TaskStatus::Stale(reason) => {
+                num_stale += 1;
+                println!("{:width$}  {}: {}", task_name, status, reason, width = name_width);
+            }
+            TaskStatus::MaybeStale(dep) => {
+                num_maybe_stale += 1;
+                println!(
+                    "{:width$}  {}: dependency {} is not up to date",
+                    task_name,
+                    status,
+                    dep,
+                    width = name_width
+                );
+            }
+            TaskStatus::NeverRun => {
+                num_never_run += 1;
+                println!("{:width$}  {}", task_name, status, width = name_width);
+            }
+            TaskStatus::Unresolved => {
+                num_unresolved += 1;
+                println!(
+                    "{:width$}  {} (use --calc to run calc tasks)",
+                    task_name,
+                    status,
+                    width = name_width
+                );
+            }
+        }
+    }
+
+    println!();
+    println!(
+        "{} up to date, {} stale, {} maybe stale, {} never run, {} unresolved, {} always run",
+        num_up_to_date, num_stale, num_maybe_stale, num_never_run, num_unresolved, num_always_run
+    );
+
+    // Maybe stale tasks don't count, since the dependency that makes them maybe stale is in the
+    // dependency graph too, and is counted on its own.  Always run tasks can never be up to date,
+    // so counting them would make the status fail for any workspace that has one.
+    let num_not_up_to_date = num_stale + num_never_run + num_unresolved;
+    if num_not_up_to_date > 0 {
+        return Err(anyhow::anyhow!(
+            "{} task(s) are not up to date",
+            num_not_up_to_date
+        ));
+    }
+
+    Ok(())
+}
@@ -6,7 +6,7 @@
 use std::{env::set_current_dir, path::PathBuf, sync::Arc};
 
 use cobble::{
-    config::{get_workspace_config, TaskOutputCondition, WorkspaceConfigArgs},
+    config::{get_workspace_config, OutputMode, TaskOutputCondition, WorkspaceConfigArgs},
     execute::execute::TaskExecutor,
     load::load_projects,
     workspace::create_workspace,
@@ -18,6 +18,7 @@ pub struct CheckToolInput {
     pub num_threads: Option<u8>,
     pub show_stdout: Option<TaskOutputCondition>,
     pub show_stderr: Option<TaskOutputCondition>,
+    pub output_mode: Option<OutputMode>,
 }
 
 pub fn check_tool_command(input: CheckToolInput) -> anyhow::Result<()> {
@@ -27,12 +28,14 @@ pub fn check_tool_command(input: CheckToolInput) -> anyhow::Result<()> {
         num_threads,
         show_stdout,
         show_stderr,
+        output_mode,
     } = input;
 
     let ws_config_args = WorkspaceConfigArgs {
         num_threads: num_threads,
         show_stdout,
         show_stderr,
+        output_mode,
         ..Default::default()
     };
     let config = Arc::new(get_workspace_config(cwd.as_path(), &ws_config_args)?);
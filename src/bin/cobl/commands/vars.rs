@@ -0,0 +1,87 @@
+// Cobble Build Automation
+// Copyright (C) 2024 Jeremiah Darais
+//
+// This program is licensed under the GPLv3.0 license (https://github.com/jdarais/cobble/blob/main/COPYING)
+
+use std::env::set_current_dir;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use cobble::config::{get_workspace_config, WorkspaceConfigArgs};
+use cobble::load::load_projects;
+use cobble::vars::{get_var, is_secret_var, resolve_declared_vars};
+use cobble::workspace::create_workspace;
+
+const TAB: &str = "  ";
+
+pub struct VarsCommandInput {
+    pub cwd: PathBuf,
+    pub vars: Vec<String>,
+    pub secret_vars: Vec<String>,
+    pub var_files: Vec<PathBuf>,
+    pub profile: Option<String>,
+}
+
+pub fn vars_command(input: VarsCommandInput) -> anyhow::Result<()> {
+    let VarsCommandInput {
+        cwd,
+        vars,
+        secret_vars,
+        var_files,
+        profile,
+    } = input;
+
+    let ws_config_args = WorkspaceConfigArgs {
+        vars,
+        secret_vars,
+        var_files,
+        profile,
+        ..Default::default()
+    };
+
+    let mut config = get_workspace_config(cwd.as_path(), &ws_config_args)?;
+    set_current_dir(&config.workspace_dir)
+        .expect("found the workspace directory, so we should be able to set that as the cwd");
+
+    let projects = load_projects(
+        config.workspace_dir.as_path(),
+        config.root_projects.iter().map(|s| s.as_str()),
+    )?;
+    let workspace = create_workspace(projects.values());
+    resolve_declared_vars(&workspace.vars, &mut config)?;
+
+    if let Some(profile) = config.profile.as_ref() {
+        println!("Profile: {}", profile);
+    }
+
+    let mut var_names: Vec<&Arc<str>> = workspace.vars.keys().collect();
+    var_names.sort();
+
+    for var_name in var_names {
+        let var_def = &workspace.vars[var_name];
+
+        let value = match get_var(var_name, &config.vars) {
+            Ok(_) if is_secret_var(&config, var_name.as_ref()) => String::from("****"),
+            Ok(value) => value.to_string(),
+            Err(_) => String::from("<not set>"),
+        };
+
+        match config.var_source(var_name) {
+            Some(source) => println!(
+                "{} = {}  [{}, from {}]",
+                var_name, value, var_def.var_type, source
+            ),
+            None => println!("{} = {}  [{}]", var_name, value, var_def.var_type),
+        }
+
+        if let Some(description) = var_def.description.as_ref() {
+            println!("{TAB}{description}");
+        }
+
+        if let Some(choices) = var_def.choices.as_ref() {
+            println!("{TAB}Choices: {}", choices.join(", "));
+        }
+    }
+
+    Ok(())
+}
@@ -5,20 +5,36 @@
 
 mod commands;
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
 
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
 
-use cobble::config::{get_workspace_config, parse_output_condition, DEFAULT_NUM_THREADS};
+use cobble::config::{get_workspace_config, parse_output_condition, parse_output_mode, DEFAULT_NUM_THREADS};
 use cobble::load::load_projects;
+use cobble::lua::cmd::kill_all_spawned_processes;
+use cobble::task_selection::{parse_shard, Shard};
 
 use crate::commands::clean::{clean_command, CleanCommandInput};
+use crate::commands::completions::{
+    complete_command, completions_command, CompleteCommandInput, CompletionsCommandInput,
+    COMPLETE_COMMAND_NAME,
+};
+use crate::commands::dump_workspace::{dump_workspace_command, DumpWorkspaceCommandInput};
 use crate::commands::env::{run_env_command, RunEnvInput};
+use crate::commands::init::{
+    init_command, init_project_command, project_template_parser, InitCommandInput,
+    InitProjectCommandInput,
+};
+use crate::commands::invalidate::{invalidate_command, InvalidateCommandInput};
 use crate::commands::list::{list_command, ListCommandInput};
+use crate::commands::lua::{lua_command, LuaCommandInput};
+use crate::commands::log::{log_command, LogCommandInput};
 use crate::commands::run::{run_command, RunCommandInput};
 use crate::commands::tool::{check_tool_command, CheckToolInput};
 use crate::commands::show::{show_task_command, ShowTaskInput};
+use crate::commands::status::{status_command, StatusCommandInput};
+use crate::commands::vars::{vars_command, VarsCommandInput};
 
 
 const VERSION: &'static str = env!("CARGO_PKG_VERSION");
@@ -36,6 +52,18 @@ struct Cli {
     #[arg(short, long, value_names(["VAR=VALUE"]), global(true), action=clap::ArgAction::Append)]
     var: Vec<String>,
 
+    /// Set the value for a variable, and keep the value out of the database and task output
+    #[arg(long, value_names(["VAR=VALUE"]), global(true), action=clap::ArgAction::Append)]
+    secret_var: Vec<String>,
+
+    /// Set variables from a TOML or JSON file
+    #[arg(long, value_name("FILE"), global(true), action=clap::ArgAction::Append)]
+    var_file: Vec<PathBuf>,
+
+    /// Use the variables from a profile defined in cobble.toml or cobble.local.toml
+    #[arg(short, long, value_name("NAME"), global(true))]
+    profile: Option<String>,
+
     #[arg(long, value_names(["always|never|on_fail"]), global(true))]
     task_output: Option<String>,
 
@@ -45,6 +73,10 @@ struct Cli {
     #[arg(long, value_names(["always|never|on_fail"]), global(true))]
     task_stderr: Option<String>,
 
+    /// How to display output from tasks running at the same time
+    #[arg(long, value_names(["grouped|prefixed|tui"]), global(true))]
+    output_mode: Option<String>,
+
     /// Display the version of this application and exit
     #[arg(long)]
     version: bool,
@@ -56,20 +88,110 @@ enum CoblCommand {
     List {
         /// If provided, display only the matched tasks
         tasks: Vec<String>,
+
+        /// Leave out tasks matching the query, (can be given multiple times)
+        #[arg(short = 'x', long, value_name("QUERY"), action=clap::ArgAction::Append)]
+        exclude: Vec<String>,
+
+        /// Include private tasks
+        #[arg(short, long)]
+        all: bool,
+
+        /// Display tasks grouped by project
+        #[arg(long, conflicts_with("json"))]
+        tree: bool,
+
+        /// Display tasks as JSON
+        #[arg(long)]
+        json: bool,
     },
     /// Run tasks
     Run {
         /// If not provided, run all tasks in the project
         tasks: Vec<String>,
 
+        /// Leave out tasks matching the query, (can be given multiple times)
+        #[arg(short = 'x', long, value_name("QUERY"), action=clap::ArgAction::Append)]
+        exclude: Vec<String>,
+
         /// Run tasks even if they are up-to-date
         #[arg(short, long)]
         force: bool,
+
+        /// Run only the selected tasks, using the recorded outputs of their dependencies
+        #[arg(long, conflicts_with("only_deps"))]
+        no_deps: bool,
+
+        /// Run only the dependencies of the selected tasks
+        #[arg(long)]
+        only_deps: bool,
+
+        /// Run the given task, and all tasks that depend on it, even if they are up-to-date
+        #[arg(long, value_name("TASK"), action=clap::ArgAction::Append)]
+        from: Vec<String>,
+
+        /// Run tasks matching the query even if they are up-to-date, without forcing other tasks
+        #[arg(long, value_name("QUERY"), action=clap::ArgAction::Append)]
+        force_only: Vec<String>,
+
+        /// Only run tasks affected by files changed since the given git revision
+        #[arg(long, value_name("REV"))]
+        changed_since: Option<String>,
+
+        /// Only run tasks affected by the given files, (comma-separated, or "-" to read from stdin)
+        #[arg(long, value_name("FILES"), action=clap::ArgAction::Append)]
+        changed_files: Vec<String>,
+
+        /// Split the selected tasks into COUNT slices, and run only slice INDEX, (e.g. 2/5)
+        #[arg(long, value_name("INDEX/COUNT"), value_parser = parse_shard)]
+        shard: Option<Shard>,
+
+        /// Balance shards using task durations from a file written by --save-durations
+        #[arg(long, value_name("FILE"), requires("shard"))]
+        shard_durations: Option<PathBuf>,
+
+        /// Write how long each selected task took to a file, for use with --shard-durations
+        #[arg(long, value_name("FILE"))]
+        save_durations: Option<PathBuf>,
+
+        /// Write a timeline of the run to a file, in Chrome Trace Event format
+        #[arg(long, value_name("FILE"))]
+        trace: Option<PathBuf>,
     },
     /// Clean tasks
     Clean {
         /// If not provided, cleans all default tasks, (dependencies are excluded)
         tasks: Vec<String>,
+
+        /// Leave out tasks matching the query, (can be given multiple times)
+        #[arg(short = 'x', long, value_name("QUERY"), action=clap::ArgAction::Append)]
+        exclude: Vec<String>,
+    },
+    /// Clear the recorded results of tasks, so that they run the next time they are selected
+    Invalidate {
+        /// If not provided, invalidates all default tasks in the project
+        tasks: Vec<String>,
+
+        /// Leave out tasks matching the query, (can be given multiple times)
+        #[arg(short = 'x', long, value_name("QUERY"), action=clap::ArgAction::Append)]
+        exclude: Vec<String>,
+
+        /// Also invalidate all tasks that depend on the selected tasks
+        #[arg(long)]
+        rdeps: bool,
+    },
+    /// Report which tasks are up to date, without running them
+    Status {
+        /// If not provided, reports on all default tasks in the project
+        tasks: Vec<String>,
+
+        /// Leave out tasks matching the query, (can be given multiple times)
+        #[arg(short = 'x', long, value_name("QUERY"), action=clap::ArgAction::Append)]
+        exclude: Vec<String>,
+
+        /// Run calc tasks to resolve calculated artifacts and dependencies
+        #[arg(long)]
+        calc: bool,
     },
     /// Interact with tools defined in the workspace
     Tool {
@@ -85,6 +207,75 @@ enum CoblCommand {
     Show {
         /// Task(s) to show info for
         tasks: Vec<String>,
+
+        /// Leave out tasks matching the query, (can be given multiple times)
+        #[arg(short = 'x', long, value_name("QUERY"), action=clap::ArgAction::Append)]
+        exclude: Vec<String>,
+
+        /// Display tasks as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print the resolved workspace, including all tasks, build envs, tools and variables
+    DumpWorkspace {
+        /// Print the workspace as JSON, (currently the only supported format)
+        #[arg(long, required(true))]
+        json: bool,
+
+        /// Run calc tasks to resolve calculated artifacts and dependencies
+        #[arg(long)]
+        calc: bool,
+    },
+    /// Show the output logged for a task in a previous run
+    Log {
+        /// Task to show the log for
+        task: String,
+
+        /// The run to show the log for.  (Default: the most recent run)
+        #[arg(short, long, value_name("RUN_ID"))]
+        run: Option<String>,
+
+        /// List the runs that have logs available for the task
+        #[arg(short, long)]
+        list: bool,
+
+        /// Show the time at which each line was output
+        #[arg(short, long)]
+        timestamps: bool,
+    },
+    /// List the variables declared in the workspace, with their current values
+    Vars,
+    /// Create a new workspace in the current directory
+    #[command(args_conflicts_with_subcommands(true))]
+    Init {
+        #[command(subcommand)]
+        init_cmd: Option<InitCommand>,
+
+        /// Set up the root project with tools, build envs and tasks for a common stack
+        #[arg(long, value_parser = project_template_parser())]
+        template: Option<String>,
+    },
+    /// Run a Lua script, or start a Lua REPL, in the environment that project files are run in
+    Lua {
+        /// Script to run.  If not provided, reads Lua statements and expressions from stdin.
+        script: Option<PathBuf>,
+
+        /// Arguments passed to the script in the "arg" table
+        #[arg(allow_hyphen_values(true), trailing_var_arg(true))]
+        args: Vec<String>,
+    },
+    /// Print a shell completion script
+    Completions {
+        #[arg(value_parser(["bash", "zsh", "fish"]))]
+        shell: String,
+    },
+    /// Print completion candidates for the word at INDEX.  Called by the completion scripts.
+    #[command(name = COMPLETE_COMMAND_NAME, hide(true))]
+    Complete {
+        index: usize,
+
+        #[arg(allow_hyphen_values(true), trailing_var_arg(true))]
+        words: Vec<String>,
     },
 }
 
@@ -96,6 +287,19 @@ enum ToolCommand {
     },
 }
 
+#[derive(Subcommand)]
+enum InitCommand {
+    /// Create a new project in a subdirectory, and add it to the project that contains it
+    Project {
+        /// Directory of the new project
+        dir: PathBuf,
+
+        /// Set up the project with tools, build envs and tasks for a common stack
+        #[arg(long, value_parser = project_template_parser())]
+        template: Option<String>,
+    },
+}
+
 #[derive(Subcommand)]
 enum EnvCommand {
     Run {
@@ -131,6 +335,15 @@ fn main() -> ExitCode {
         return ExitCode::from(0);
     }
 
+    // Make sure background processes started by actions don't outlive cobble if it is interrupted
+    let set_handler_res = ctrlc::set_handler(|| {
+        kill_all_spawned_processes();
+        std::process::exit(130);
+    });
+    if let Err(e) = set_handler_res {
+        eprintln!("Unable to set interrupt handler: {}", e);
+    }
+
     let cwd = std::env::current_dir().expect("was run from a directory");
 
     let show_output_enum = match &args.task_output {
@@ -166,27 +379,101 @@ fn main() -> ExitCode {
         None => None
     };
 
+    let output_mode_enum = match &args.output_mode {
+        Some(s) => match parse_output_mode(s.as_str()) {
+            Ok(val) => Some(val),
+            Err(e) => {
+                eprintln!("For --output-mode: {}.", e);
+                return ExitCode::from(1);
+            }
+        }
+        None => None
+    };
+
     let result = match args.command {
         Some(cmd) => match cmd {
-            CoblCommand::List { tasks } => list_command(ListCommandInput {
+            CoblCommand::List {
+                tasks,
+                exclude,
+                all,
+                tree,
+                json,
+            } => list_command(ListCommandInput {
                 cwd: cwd,
                 tasks: tasks,
+                exclude,
+                all,
+                tree,
+                json,
             }),
-            CoblCommand::Run { tasks, force } => run_command(RunCommandInput {
+            CoblCommand::Run {
+                tasks,
+                exclude,
+                force,
+                no_deps,
+                only_deps,
+                from,
+                force_only,
+                changed_since,
+                changed_files,
+                shard,
+                shard_durations,
+                save_durations,
+                trace,
+            } => run_command(RunCommandInput {
                 cwd,
                 tasks,
+                exclude,
+                changed_since,
+                changed_files,
+                shard,
+                shard_durations,
+                save_durations,
                 vars: args.var,
+                secret_vars: args.secret_var,
+                var_files: args.var_file,
+                profile: args.profile,
                 force_run_tasks: force,
+                no_deps,
+                only_deps,
+                from,
+                force_only,
+                trace,
                 num_threads: args.num_threads,
                 show_stdout: show_stdout_enum.or(show_output_enum.clone()),
-                show_stderr: show_stderr_enum.or(show_output_enum)
+                show_stderr: show_stderr_enum.or(show_output_enum),
+                output_mode: output_mode_enum,
             }),
-            CoblCommand::Clean { tasks } => clean_command(CleanCommandInput {
+            CoblCommand::Clean { tasks, exclude } => clean_command(CleanCommandInput {
                 cwd,
                 tasks,
+                exclude,
                 num_threads: args.num_threads,
                 show_stdout: show_stdout_enum.or(show_output_enum.clone()),
-                show_stderr: show_stderr_enum.or(show_output_enum)
+                show_stderr: show_stderr_enum.or(show_output_enum),
+                output_mode: output_mode_enum,
+            }),
+            CoblCommand::Invalidate { tasks, exclude, rdeps } => {
+                invalidate_command(InvalidateCommandInput {
+                    cwd,
+                    tasks,
+                    exclude,
+                    rdeps,
+                })
+            }
+            CoblCommand::Status {
+                tasks,
+                exclude,
+                calc,
+            } => status_command(StatusCommandInput {
+                cwd,
+                tasks,
+                exclude,
+                vars: args.var,
+                secret_vars: args.secret_var,
+                var_files: args.var_file,
+                profile: args.profile,
+                calc,
             }),
             CoblCommand::Tool { tool_cmd } => match tool_cmd {
                 ToolCommand::Check { names } => check_tool_command(CheckToolInput {
@@ -194,7 +481,8 @@ fn main() -> ExitCode {
                     tools: names,
                     num_threads: args.num_threads,
                     show_stdout: show_stdout_enum.or(show_output_enum.clone()),
-                    show_stderr: show_stderr_enum.or(show_output_enum)
+                    show_stderr: show_stderr_enum.or(show_output_enum),
+                    output_mode: output_mode_enum,
                 }),
             },
             CoblCommand::Env { env_cmd } => match env_cmd {
@@ -207,14 +495,68 @@ fn main() -> ExitCode {
                     args: env_args,
                     num_threads: args.num_threads,
                     show_stdout: show_stdout_enum.or(show_output_enum.clone()),
-                    show_stderr: show_stderr_enum.or(show_output_enum)
+                    show_stderr: show_stderr_enum.or(show_output_enum),
+                    output_mode: output_mode_enum,
                 }),
             },
-            CoblCommand::Show { tasks } => show_task_command(ShowTaskInput {
+            CoblCommand::Show {
+                tasks,
+                exclude,
+                json,
+            } => show_task_command(ShowTaskInput {
                 cwd,
                 tasks,
+                exclude,
+                vars: args.var,
+                secret_vars: args.secret_var,
+                var_files: args.var_file,
+                profile: args.profile,
+                num_threads: args.num_threads,
+                json,
+            }),
+            CoblCommand::DumpWorkspace { json: _, calc } => {
+                dump_workspace_command(DumpWorkspaceCommandInput {
+                    cwd,
+                    vars: args.var,
+                    secret_vars: args.secret_var,
+                    var_files: args.var_file,
+                    profile: args.profile,
+                    calc,
+                })
+            }
+            CoblCommand::Log { task, run, list, timestamps } => log_command(LogCommandInput {
+                cwd,
+                task,
+                run_id: run,
+                list,
+                timestamps,
+            }),
+            CoblCommand::Init { init_cmd, template } => match init_cmd {
+                Some(InitCommand::Project { dir, template }) => {
+                    init_project_command(InitProjectCommandInput { cwd, dir, template })
+                }
+                None => init_command(InitCommandInput { cwd, template }),
+            },
+            CoblCommand::Lua { script, args: script_args } => lua_command(LuaCommandInput {
+                cwd,
+                script,
+                args: script_args,
+            }),
+            CoblCommand::Completions { shell } => {
+                completions_command(CompletionsCommandInput { shell })
+            }
+            CoblCommand::Complete { index, words } => complete_command(CompleteCommandInput {
+                cwd,
+                command: Cli::command(),
+                index,
+                words,
+            }),
+            CoblCommand::Vars => vars_command(VarsCommandInput {
+                cwd,
                 vars: args.var,
-                num_threads: args.num_threads
+                secret_vars: args.secret_var,
+                var_files: args.var_file,
+                profile: args.profile,
             }),
         },
         None => run_from_dir(cwd.as_path()),
@@ -0,0 +1,73 @@
+local tblext = require("tblext")
+local iter = require("iter")
+local json = require("json")
+local maybe = require("maybe")
+
+env {
+    name = "npm_env",
+    setup_task = {
+        actions = { { tool = "npm", "install" } },
+        deps = { files = { "package.json" } }
+    },
+    action = { tool = "npm", function (c) return c.tool.npm (tblext.extend({ "exec", "--" }, c.args)) end }
+}
+
+task {
+    name = "calc_build_inputs",
+    private = true,
+    env = "npm_env",
+    always_run = true,
+    actions = {
+        function (c)
+            local tsc_config_result = c.env.npm_env { "tsc", "--showConfig" }
+            local tsc_config = json.loads(tsc_config_result.stdout)
+            return { files = tsc_config["files"] }
+        end
+    }
+}
+
+task {
+    name = "calc_build_outputs",
+    private = true,
+    env = "npm_env",
+    always_run = true,
+    actions = {
+        function (c)
+            local tsc_config_result = c.env.npm_env { "tsc", "--showConfig" }
+            local tsc_config = json.loads(tsc_config_result.stdout)
+
+            local root_dir = maybe(tsc_config)["compilerOptions"]["rootDir"].value or "./"
+            local out_dir = maybe(tsc_config)["compilerOptions"]["outDir"].value or "./"
+
+            local out_files = iter(ipairs(tsc_config["files"]))
+                :map(function(i, f) return i, f:gsub("^"..root_dir, out_dir) end)
+                :map(function(i, f) return i, f:gsub(".tsx?$", ".js") end)
+                :to_table()
+            return out_files
+        end
+    }
+}
+
+task {
+    name = "build",
+    description = "Compile the package with tsc",
+    group = "build",
+    env = "npm_env",
+    actions = { { env = "npm_env", "tsc" } },
+    deps = {
+        files = { "tsconfig.json" },
+        calc = { "calc_build_inputs" }
+    },
+    artifacts = { calc = { "calc_build_outputs" } }
+}
+
+task {
+    name = "test",
+    description = "Run the package's test script",
+    group = "check",
+    actions = { { tool = "npm", "test" } },
+    deps = {
+        files = { "package.json" },
+        tasks = { "build" }
+    }
+}
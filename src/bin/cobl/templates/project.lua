@@ -0,0 +1,9 @@
+-- Define the tasks for this project here.  For example:
+--
+-- task {
+--     name = "hello",
+--     description = "Say hello",
+--     actions = {
+--         { "echo", "Hello from Cobble!" }
+--     }
+-- }
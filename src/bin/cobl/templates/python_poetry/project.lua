@@ -0,0 +1,98 @@
+local path = require("path")
+local maybe = require("maybe")
+local iter = require("iter")
+local toml = require("toml")
+
+env {
+    name = "poetry_env",
+    setup_task = {
+        actions = {
+            { tool = "poetry", function (c)
+                c.tool.poetry { "env", "use", c.vars["python.version"] }
+            end },
+            { tool = "poetry", "install" }
+        },
+        deps = {
+            files = { "pyproject.toml", "poetry.lock" },
+            vars = { "python.version" }
+        },
+    },
+    action = { tool = "poetry", "run" }
+}
+
+task {
+    name = "shell",
+    description = "Start a python shell in the poetry environment",
+    always_run = true,
+    interactive = true,
+    default = false,
+    actions = { { env = "poetry_env", "python" } }
+}
+
+task {
+    name = "test",
+    description = "Run the tests with pytest",
+    group = "check",
+    actions = { { env = "poetry_env", "python", "-m", "pytest" } },
+    deps = { calc = { "find_poetry_source_files" } }
+}
+
+task {
+    name = "lint",
+    description = "Lint the package with pylint",
+    group = "check",
+    actions = {
+        { env = "poetry_env", function (c)
+            local package_name = (toml.load("pyproject.toml")["tool"]["poetry"]["name"]:gsub("-", "_"))
+            c.env.poetry_env { "python", "-m", "pylint", package_name }
+        end }
+    },
+    deps = { calc = { "find_poetry_source_files" } }
+}
+
+task {
+    name = "format.check",
+    description = "Check formatting with black",
+    group = "check",
+    actions = { { env = "poetry_env", "python", "-m", "black", "--check", "." } },
+    deps = { calc = { "find_poetry_source_files" } }
+}
+
+task {
+    name = "format",
+    description = "Format the source files with black",
+    always_run = true,
+    default = false,
+    actions = { { env = "poetry_env", "python", "-m", "black", "." } }
+}
+
+task {
+    name = "find_poetry_source_files",
+    private = true,
+    deps = { files = { "pyproject.toml" } },
+    actions = {
+        function (c)
+            local pyproject_toml = toml.load("pyproject.toml")
+            local patterns = maybe(pyproject_toml)["tool"]["poetry"]["packages"]
+                :or_else(function ()
+                    return maybe(pyproject_toml)["tool"]["poetry"]["name"]
+                        :and_then(function (name) return { { include = name:gsub("-", "_") }, } end)
+                        :or_else(function () error("Expected name to exist in pyproject.toml") end)
+                        .value
+                end)
+                .value
+
+            local include_files = {}
+            for i, v in ipairs(patterns) do
+                if v.include then
+                    local pattern = path.is_dir(v.include) and (v.include .. "/**/*") or v.include
+                    iter(ipairs(path.glob(pattern)))
+                        :filter(function(_, f) return not f:match("%.pyc$") end)
+                        :filter(function(_, f) return not f:match("[/\\]__pycache__[/\\]") end)
+                        :for_each(function(_, f) include_files[f] = f end)
+                end
+            end
+            return { files = include_files }
+        end
+    }
+}
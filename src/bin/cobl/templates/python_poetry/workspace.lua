@@ -0,0 +1,20 @@
+local cmd = require("cmd")
+local version = require("version")
+
+var {
+    name = "python.version",
+    default = "3.12",
+    description = "The version of python used to create poetry virtual environments"
+}
+
+tool {
+    name = "poetry",
+    check = function (c)
+        local res = cmd { "poetry", "--version" }
+        assert(res.status == 0, "poetry command exited with status " .. res.status)
+
+        local poetry_version = res.stdout:match("Poetry %(version (%S+)%)")
+        assert(version(poetry_version) >= "1.8.0", "Poetry >= 1.8.0 required. Found ".. poetry_version)
+    end,
+    action = { tool = "cmd", "poetry" }
+}
@@ -0,0 +1,295 @@
+// Cobble Build Automation
+// Copyright (C) 2024 Jeremiah Darais
+//
+// This program is licensed under the GPLv3.0 license (https://github.com/jdarais/cobble/blob/main/COPYING)
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead};
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+
+use crate::query::{expand_project_tasks, find_reverse_dependencies};
+use crate::workspace::{TaskType, Workspace};
+
+/// Passing this value to `--changed-files` reads the list of changed files from stdin
+pub const CHANGED_FILES_STDIN: &str = "-";
+
+#[derive(Debug)]
+pub enum ChangedFilesError {
+    IOError(io::Error),
+    GitError { args: Vec<String>, message: String },
+}
+
+impl Error for ChangedFilesError {}
+
+impl fmt::Display for ChangedFilesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ChangedFilesError::*;
+        match self {
+            IOError(e) => write!(f, "Error reading changed files: {}", e),
+            GitError { args, message } => {
+                write!(f, "Error running 'git {}': {}", args.join(" "), message)
+            }
+        }
+    }
+}
+
+fn run_git(workspace_dir: &Path, args: &[&str]) -> Result<Vec<String>, ChangedFilesError> {
+    let git_error = |message: String| ChangedFilesError::GitError {
+        args: args.iter().map(|a| String::from(*a)).collect(),
+        message,
+    };
+
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(workspace_dir)
+        .output()
+        .map_err(|e| git_error(e.to_string()))?;
+
+    if !output.status.success() {
+        return Err(git_error(
+            String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// Get the files that have changed in the workspace since the given git revision, including
+/// uncommitted changes and untracked files.  Returned paths are relative to the workspace dir.
+pub fn find_git_changed_files(
+    workspace_dir: &Path,
+    rev: &str,
+) -> Result<Vec<PathBuf>, ChangedFilesError> {
+    // With --relative, git limits the diff to the current dir and gives paths relative to it
+    let mut changed_files =
+        run_git(workspace_dir, &["diff", "--name-only", "--relative", rev, "--"])?;
+    changed_files.extend(run_git(
+        workspace_dir,
+        &["ls-files", "--others", "--exclude-standard"],
+    )?);
+
+    Ok(changed_files.iter().map(PathBuf::from).collect())
+}
+
+/// Read a list of changed files given on the command line.  Each value can be a comma-separated
+/// list of paths, or "-" to read paths from stdin, one per line.  Paths are relative to `cwd`.
+pub fn read_changed_files_arg<'a, I>(
+    cwd: &Path,
+    workspace_dir: &Path,
+    values: I,
+) -> Result<Vec<PathBuf>, ChangedFilesError>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let mut paths: Vec<String> = Vec::new();
+    for value in values {
+        if value == CHANGED_FILES_STDIN {
+            for line in io::stdin().lock().lines() {
+                let line = line.map_err(ChangedFilesError::IOError)?;
+                if !line.trim().is_empty() {
+                    paths.push(String::from(line.trim()));
+                }
+            }
+        } else {
+            paths.extend(
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|p| !p.is_empty())
+                    .map(String::from),
+            );
+        }
+    }
+
+    let full_cwd = normalize_path(cwd);
+    let full_workspace_dir = normalize_path(workspace_dir);
+    Ok(paths
+        .iter()
+        .filter_map(|p| {
+            normalize_path(&full_cwd.join(p))
+                .strip_prefix(&full_workspace_dir)
+                .ok()
+                .map(PathBuf::from)
+        })
+        .collect())
+}
+
+/// Collect the changed files given by the `--changed-since` and `--changed-files` arguments.
+/// Returns `None` if neither argument was given.
+pub fn find_changed_files(
+    cwd: &Path,
+    workspace_dir: &Path,
+    changed_since: Option<&str>,
+    changed_files: &[String],
+) -> Result<Option<Vec<PathBuf>>, ChangedFilesError> {
+    if changed_since.is_none() && changed_files.is_empty() {
+        return Ok(None);
+    }
+
+    let mut result: Vec<PathBuf> = Vec::new();
+    if let Some(rev) = changed_since {
+        result.extend(find_git_changed_files(workspace_dir, rev)?);
+    }
+    result.extend(read_changed_files_arg(
+        cwd,
+        workspace_dir,
+        changed_files.iter().map(|s| s.as_str()),
+    )?);
+
+    Ok(Some(result))
+}
+
+/// Remove "." and ".." components from a path, without touching the filesystem
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for comp in path.components() {
+        match comp {
+            Component::CurDir => { /* skip */ }
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            _ => normalized.push(comp),
+        }
+    }
+    normalized
+}
+
+/// Find the tasks affected by changes to the given files, (paths relative to the workspace dir.)
+/// A task is affected if one of its file dependencies, artifacts, or project source files changed,
+/// or if it depends, directly or indirectly, on an affected task.
+pub fn find_tasks_affected_by_files(
+    workspace: &Workspace,
+    changed_files: &[PathBuf],
+) -> Vec<Arc<str>> {
+    let changed: HashSet<PathBuf> = changed_files.iter().map(|p| normalize_path(p)).collect();
+    let is_changed = |path: &str| changed.contains(&normalize_path(Path::new(path)));
+
+    let mut directly_affected: Vec<Arc<str>> = workspace
+        .tasks
+        .iter()
+        .filter(|(_, task)| !matches!(task.task_type, TaskType::Project))
+        .filter(|(_, task)| {
+            task.file_deps.values().any(|f| is_changed(&f.path))
+                || task.artifacts.files.iter().any(|f| is_changed(f))
+                || task.project_source_deps.iter().any(|f| is_changed(f))
+        })
+        .map(|(name, _)| name.clone())
+        .collect();
+    directly_affected.sort();
+
+    find_reverse_dependencies(workspace, directly_affected)
+}
+
+/// Narrow a task selection down to the tasks affected by changes to the given files.  Projects in
+/// the selection are replaced by the tasks they run.
+pub fn filter_tasks_affected_by_files(
+    workspace: &Workspace,
+    tasks: Vec<Arc<str>>,
+    changed_files: &[PathBuf],
+) -> Vec<Arc<str>> {
+    let affected: HashSet<Arc<str>> = find_tasks_affected_by_files(workspace, changed_files)
+        .into_iter()
+        .collect();
+
+    let mut result = expand_project_tasks(workspace, tasks);
+    result.retain(|t| affected.contains(t));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::workspace::{FileDependency, Task};
+
+    use super::*;
+
+    #[test]
+    fn test_find_tasks_affected_by_files() {
+        let mut tasks: HashMap<Arc<str>, Arc<Task>> = HashMap::new();
+        tasks.insert(Arc::<str>::from("/a/build"), Arc::new(Task {
+            name: Arc::<str>::from("/a/build"),
+            file_deps: HashMap::from([(Arc::<str>::from("main.c"), FileDependency {
+                path: Arc::<str>::from("a/main.c"),
+                provided_by_task: None,
+            })]),
+            ..Default::default()
+        }));
+        tasks.insert(Arc::<str>::from("/a/test"), Arc::new(Task {
+            name: Arc::<str>::from("/a/test"),
+            task_deps: HashMap::from([(Arc::<str>::from("build"), Arc::<str>::from("/a/build"))]),
+            ..Default::default()
+        }));
+        tasks.insert(Arc::<str>::from("/b/lint"), Arc::new(Task {
+            name: Arc::<str>::from("/b/lint"),
+            project_source_deps: vec![Arc::<str>::from("./b/project.lua")],
+            ..Default::default()
+        }));
+
+        let workspace = Workspace {
+            tasks,
+            ..Default::default()
+        };
+
+        let affected = find_tasks_affected_by_files(&workspace, &[PathBuf::from("a/main.c")]);
+        assert_eq!(affected, vec![Arc::<str>::from("/a/build"), Arc::<str>::from("/a/test")]);
+
+        let affected = find_tasks_affected_by_files(&workspace, &[PathBuf::from("b/project.lua")]);
+        assert_eq!(affected, vec![Arc::<str>::from("/b/lint")]);
+
+        let affected = find_tasks_affected_by_files(&workspace, &[PathBuf::from("README.md")]);
+        assert!(affected.is_empty());
+    }
+
+    #[test]
+    fn test_filter_tasks_affected_by_calculated_file_deps() {
+        let tmpdir = mktemp::Temp::new_dir().unwrap();
+        std::fs::write(
+            tmpdir.as_path().join("project.lua"),
+            r#"
+            task {
+                name = "find_sources",
+                actions = { function (c) return { files = { "gen.c" } } end }
+            }
+            task {
+                name = "build",
+                deps = { calc = { "find_sources" } },
+                actions = { function (c) end }
+            }
+            "#,
+        )
+        .unwrap();
+
+        let config = Arc::new(
+            crate::config::parse_workspace_config("", tmpdir.as_path().join("cobble.toml").as_path()).unwrap(),
+        );
+        let projects = crate::load::load_projects(tmpdir.as_path(), std::iter::once(".")).unwrap();
+        let mut workspace = crate::workspace::create_workspace(projects.values());
+        let mut executor =
+            crate::execute::execute::TaskExecutor::new(config, tmpdir.as_path().join(".cobble.db").as_path()).unwrap();
+
+        let selected_tasks = vec![Arc::<str>::from("/build")];
+        let changed_files = vec![PathBuf::from("gen.c")];
+
+        // The file dependency isn't known until the calc dependency is resolved
+        let affected = filter_tasks_affected_by_files(&workspace, selected_tasks.clone(), &changed_files);
+        assert!(affected.is_empty());
+
+        crate::dependency::resolve_calculated_dependencies_in_subtrees(
+            selected_tasks.iter(),
+            &mut workspace,
+            &mut executor,
+        )
+        .unwrap();
+        let affected = filter_tasks_affected_by_files(&workspace, selected_tasks, &changed_files);
+        assert_eq!(affected, vec![Arc::<str>::from("/build")]);
+    }
+}
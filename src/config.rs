@@ -3,7 +3,7 @@
 //
 // This program is licensed under the GPLv3.0 license (https://github.com/jdarais/cobble/blob/main/COPYING)
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fmt::Display;
 use std::fs::File;
@@ -11,31 +11,118 @@ use std::io::{self, Read};
 use std::path::{Path, PathBuf};
 
 use crate::project_def::types::TaskVar;
-use crate::vars::{set_var, VarLookupError};
+use crate::vars::{get_var, set_var, VarLookupError};
 
 pub const WORKSPACE_CONFIG_FILE_NAME: &str = "cobble.toml";
+pub const LOCAL_WORKSPACE_CONFIG_FILE_NAME: &str = "cobble.local.toml";
+pub const USER_CONFIG_FILE_NAME: &str = "config.toml";
+
+/// Settings that can be set in the user config file.  Only settings that don't affect the results
+/// of a build are allowed, so that a build gives the same results for everyone.
+pub const USER_CONFIG_KEYS: [&str; 6] = [
+    "num_threads",
+    "output",
+    "stdout",
+    "stderr",
+    "output_mode",
+    "log_retention",
+];
 pub const PROJECT_FILE_NAME: &str = "project.lua";
 
+pub const ENV_NUM_THREADS: &str = "COBBLE_NUM_THREADS";
+pub const ENV_OUTPUT: &str = "COBBLE_OUTPUT";
+pub const ENV_STDOUT: &str = "COBBLE_STDOUT";
+pub const ENV_STDERR: &str = "COBBLE_STDERR";
+pub const ENV_OUTPUT_MODE: &str = "COBBLE_OUTPUT_MODE";
+pub const ENV_PTY: &str = "COBBLE_PTY";
+pub const ENV_LOG_RETENTION: &str = "COBBLE_LOG_RETENTION";
+pub const ENV_PROFILE: &str = "COBBLE_PROFILE";
+pub const ENV_VAR_PREFIX: &str = "COBBLE_VAR_";
+pub const ENV_SECRET_VAR_PREFIX: &str = "COBBLE_SECRET_VAR_";
+
 pub const DEFAULT_NUM_THREADS: u8 = 5;
+pub const DEFAULT_LOG_RETENTION: usize = 10;
 
 #[derive(Debug)]
 pub struct WorkspaceConfig {
     pub workspace_dir: PathBuf,
     pub root_projects: Vec<String>,
     pub vars: HashMap<String, TaskVar>,
+    pub secret_vars: HashSet<String>,
+    /// Where vars were set, for vars that weren't set in the workspace config file
+    pub var_sources: HashMap<String, VarSource>,
+    /// Vars for each profile defined in the workspace config file
+    pub profiles: HashMap<String, HashMap<String, TaskVar>>,
+    /// The selected profile
+    pub profile: Option<String>,
     pub force_run_tasks: bool,
     pub num_threads: u8,
     pub show_stdout: TaskOutputCondition,
-    pub show_stderr: TaskOutputCondition
+    pub show_stderr: TaskOutputCondition,
+    pub output_mode: OutputMode,
+    pub env_vars: HashMap<String, String>,
+    pub env_passthrough: Option<Vec<String>>,
+    pub pty: bool,
+    pub log_retention: usize,
+}
+
+impl WorkspaceConfig {
+    /// Get where the value of a var came from, or None if the var isn't set
+    pub fn var_source(&self, var_name: &str) -> Option<VarSource> {
+        if let Some(source) = self.var_sources.get(var_name) {
+            return Some(source.clone());
+        }
+
+        get_var(var_name, &self.vars)
+            .ok()
+            .map(|_| VarSource::WorkspaceConfig)
+    }
+}
+
+/// Where the value of a var was set.  Sources are listed here in order of precedence, lowest first.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VarSource {
+    /// The default value given in the var's declaration
+    Default,
+    /// The vars table in cobble.toml
+    WorkspaceConfig,
+    /// The vars table of the selected profile in cobble.toml
+    Profile(String),
+    /// The vars table, or the vars table of the selected profile, in cobble.local.toml
+    LocalConfig,
+    /// A file given with --var-file
+    VarFile(PathBuf),
+    /// A COBBLE_VAR_* or COBBLE_SECRET_VAR_* environment variable
+    Environment(String),
+    /// A --var or --secret-var command line argument
+    CommandLine,
+}
+
+impl Display for VarSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VarSource::Default => f.write_str("default"),
+            VarSource::WorkspaceConfig => f.write_str(WORKSPACE_CONFIG_FILE_NAME),
+            VarSource::Profile(name) => write!(f, "profile {}", name),
+            VarSource::LocalConfig => f.write_str(LOCAL_WORKSPACE_CONFIG_FILE_NAME),
+            VarSource::VarFile(path) => write!(f, "{}", path.display()),
+            VarSource::Environment(env_var) => write!(f, "environment variable {}", env_var),
+            VarSource::CommandLine => f.write_str("command line"),
+        }
+    }
 }
 
 #[derive(Default)]
 pub struct WorkspaceConfigArgs {
     pub vars: Vec<String>,
+    pub secret_vars: Vec<String>,
+    pub var_files: Vec<PathBuf>,
+    pub profile: Option<String>,
     pub force_run_tasks: Option<bool>,
     pub num_threads: Option<u8>,
     pub show_stdout: Option<TaskOutputCondition>,
-    pub show_stderr: Option<TaskOutputCondition>
+    pub show_stderr: Option<TaskOutputCondition>,
+    pub output_mode: Option<OutputMode>,
 }
 
 #[derive(Debug)]
@@ -91,14 +178,66 @@ pub fn parse_output_condition(value: &str) -> Result<TaskOutputCondition, String
     }
 }
 
+/// How output from concurrently running jobs is displayed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputMode {
+    /// Show the output of one job at a time, buffering the output of other jobs until it completes
+    Grouped,
+    /// Show each line of output from every job as soon as it is available, prefixed with the job name
+    Prefixed,
+    /// Show a live dashboard of all jobs, falling back to grouped output when not run in a terminal
+    Tui,
+}
+
+pub fn parse_output_mode(value: &str) -> Result<OutputMode, String> {
+    let value_lower = value.to_lowercase();
+    match value_lower.as_str() {
+        "grouped" => Ok(OutputMode::Grouped),
+        "prefixed" => Ok(OutputMode::Prefixed),
+        "tui" => Ok(OutputMode::Tui),
+        invalid_val => Err(format!("Invalid value given for output mode: {}.  Expected one of [grouped, prefixed, tui].", invalid_val))
+    }
+}
+
 pub fn parse_workspace_config(
     config_str: &str,
     config_path: &Path,
+) -> Result<WorkspaceConfig, WorkspaceConfigError> {
+    parse_workspace_config_with_user_config(config_str, config_path, toml::Table::new())
+}
+
+/// Parse the workspace config, using settings from the user config for any settings that aren't
+/// set in the workspace config
+pub fn parse_workspace_config_with_user_config(
+    config_str: &str,
+    config_path: &Path,
+    user_config: toml::Table,
+) -> Result<WorkspaceConfig, WorkspaceConfigError> {
+    let env: HashMap<String, String> = std::env::vars().collect();
+    parse_workspace_config_with_env(config_str, config_path, user_config, &env)
+}
+
+/// Parse the workspace config, reading secret vars from the environment variables in `env`
+fn parse_workspace_config_with_env(
+    config_str: &str,
+    config_path: &Path,
+    user_config: toml::Table,
+    env: &HashMap<String, String>,
 ) -> Result<WorkspaceConfig, WorkspaceConfigError> {
     let mut config: toml::Table = config_str
         .parse()
         .map_err(|e| WorkspaceConfigError::ParseError(format!("Error parsing config: {}", e)))?;
 
+    // If the workspace sets 'output', it should take precedence over 'stdout' and 'stderr' in the
+    // user config, even though they are more specific
+    let workspace_sets_output = config.contains_key("output");
+    for (key, value) in user_config {
+        if workspace_sets_output && (key == "stdout" || key == "stderr") {
+            continue;
+        }
+        config.entry(key).or_insert(value);
+    }
+
     // Root Projects
     let root_projects_opt: Option<toml::Value> = config.remove("root_projects");
     let root_projects: Vec<String> = match root_projects_opt {
@@ -146,23 +285,73 @@ pub fn parse_workspace_config(
         None => output
     };
 
-    // Vars
-    let mut vars: HashMap<String, TaskVar> = HashMap::new();
-    let vars_val: toml::Value = config
-        .remove("vars")
-        .unwrap_or_else(|| toml::Value::Table(toml::Table::new()));
-    let vars_table = match vars_val {
-        toml::Value::Table(t) => t,
-        _ => {
-            return Err(WorkspaceConfigError::ValueError(String::from(
-                "vars config variable must be a table",
-            )));
+    let output_mode_opt: Option<toml::Value> = config.remove("output_mode");
+    let output_mode = match output_mode_opt {
+        Some(output_mode_val) => {
+            let output_mode_str: String = output_mode_val.try_into().map_err(|e| WorkspaceConfigError::ValueError(format!("at 'output_mode': {}", e)))?;
+            parse_output_mode(output_mode_str.as_str()).map_err(|e| WorkspaceConfigError::ValueError(format!("at 'output_mode': {}", e)))?
         }
+        None => OutputMode::Grouped
+    };
+
+    // Pseudo-terminal
+    let pty: bool = match config.remove("pty") {
+        Some(val) => val
+            .try_into()
+            .map_err(|e| WorkspaceConfigError::ValueError(format!("at 'pty': {}", e)))?,
+        None => false,
+    };
+
+    // Logs
+    let log_retention: usize = match config.remove("log_retention") {
+        Some(val) => val
+            .try_into()
+            .map_err(|e| WorkspaceConfigError::ValueError(format!("at 'log_retention': {}", e)))?,
+        None => DEFAULT_LOG_RETENTION,
+    };
+
+    // Vars
+    let mut vars: HashMap<String, TaskVar> = match config.remove("vars") {
+        Some(val) => parse_vars_table(val, "vars")?,
+        None => HashMap::new(),
+    };
+
+    // Profiles
+    let profiles = match config.remove("profiles") {
+        Some(val) => parse_profiles_table(val)?,
+        None => HashMap::new(),
     };
-    for (k, v) in vars_table {
-        vars.insert(k, v.into());
+
+    // Secret vars
+    let mut secret_vars: HashSet<String> = HashSet::new();
+    if let Some(secret_vars_val) = config.remove("secret_vars") {
+        match secret_vars_val {
+            toml::Value::Table(t) => {
+                add_secret_vars_from_table("", t, env, &mut vars, &mut secret_vars)?;
+            }
+            _ => {
+                return Err(WorkspaceConfigError::ValueError(String::from(
+                    "secret_vars config variable must be a table",
+                )));
+            }
+        }
     }
 
+    // Environment variables
+    let env_vars: HashMap<String, String> = match config.remove("env_vars") {
+        Some(val) => val
+            .try_into()
+            .map_err(|e| WorkspaceConfigError::ValueError(format!("at 'env_vars': {}", e)))?,
+        None => HashMap::new(),
+    };
+
+    let env_passthrough: Option<Vec<String>> = match config.remove("env_passthrough") {
+        Some(val) => Some(val.try_into().map_err(|e| {
+            WorkspaceConfigError::ValueError(format!("at 'env_passthrough': {}", e))
+        })?),
+        None => None,
+    };
+
     // Raise an error if there are unrecognized keys in the config table
     if let Some((key, _)) = config.iter().next() {
         return Err(WorkspaceConfigError::ValueError(format!(
@@ -175,21 +364,124 @@ pub fn parse_workspace_config(
         workspace_dir: PathBuf::from(config_path.parent().unwrap_or_else(|| Path::new("."))),
         root_projects,
         vars,
+        secret_vars,
+        var_sources: HashMap::new(),
+        profiles,
+        profile: None,
         force_run_tasks: false,
         num_threads,
         show_stdout: stdout,
-        show_stderr: stderr
+        show_stderr: stderr,
+        output_mode,
+        env_vars,
+        env_passthrough,
+        pty,
+        log_retention,
     })
 }
 
-pub fn parse_workspace_config_file(path: &Path) -> Result<WorkspaceConfig, WorkspaceConfigError> {
+fn parse_vars_table(
+    val: toml::Value,
+    key: &str,
+) -> Result<HashMap<String, TaskVar>, WorkspaceConfigError> {
+    match val {
+        toml::Value::Table(t) => Ok(t.into_iter().map(|(k, v)| (k, v.into())).collect()),
+        _ => Err(WorkspaceConfigError::ValueError(format!(
+            "at '{}': expected a table",
+            key
+        ))),
+    }
+}
+
+/// Parse the `profiles` config table, where each profile is a table with a `vars` table
+fn parse_profiles_table(
+    val: toml::Value,
+) -> Result<HashMap<String, HashMap<String, TaskVar>>, WorkspaceConfigError> {
+    let profiles_table = match val {
+        toml::Value::Table(t) => t,
+        _ => {
+            return Err(WorkspaceConfigError::ValueError(String::from(
+                "at 'profiles': expected a table",
+            )));
+        }
+    };
+
+    let mut profiles: HashMap<String, HashMap<String, TaskVar>> = HashMap::new();
+    for (profile_name, profile_val) in profiles_table {
+        let mut profile_table = match profile_val {
+            toml::Value::Table(t) => t,
+            _ => {
+                return Err(WorkspaceConfigError::ValueError(format!(
+                    "at 'profiles.{}': expected a table",
+                    profile_name
+                )));
+            }
+        };
+
+        let profile_vars = match profile_table.remove("vars") {
+            Some(val) => parse_vars_table(val, format!("profiles.{}.vars", profile_name).as_str())?,
+            None => HashMap::new(),
+        };
+
+        if let Some((key, _)) = profile_table.iter().next() {
+            return Err(WorkspaceConfigError::ValueError(format!(
+                "Unrecognized field 'profiles.{}.{}'",
+                profile_name, key
+            )));
+        }
+
+        profiles.insert(profile_name, profile_vars);
+    }
+
+    Ok(profiles)
+}
+
+/// Add secret vars from the `secret_vars` config table.  Each entry is either the name of an
+/// environment variable to read the secret value from, or `true` to mark a var that is set
+/// elsewhere as secret.
+fn add_secret_vars_from_table(
+    prefix: &str,
+    table: toml::Table,
+    env: &HashMap<String, String>,
+    vars: &mut HashMap<String, TaskVar>,
+    secret_vars: &mut HashSet<String>,
+) -> Result<(), WorkspaceConfigError> {
+    for (key, val) in table {
+        let var_name = format!("{}{}", prefix, key);
+        match val {
+            toml::Value::Table(t) => {
+                add_secret_vars_from_table(format!("{}.", var_name).as_str(), t, env, vars, secret_vars)?;
+            }
+            toml::Value::String(env_var_name) => {
+                if let Some(value) = env.get(env_var_name.as_str()) {
+                    set_var(var_name.as_str(), TaskVar::String(value.clone()), vars)
+                        .map_err(WorkspaceConfigError::SetVarError)?;
+                }
+                secret_vars.insert(var_name);
+            }
+            toml::Value::Boolean(true) => {
+                secret_vars.insert(var_name);
+            }
+            _ => {
+                return Err(WorkspaceConfigError::ValueError(format!(
+                    "at 'secret_vars.{}': expected the name of an environment variable, or true",
+                    var_name
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn read_config_file(path: &Path) -> Result<String, WorkspaceConfigError> {
     let mut config_file = File::open(path).map_err(|e| WorkspaceConfigError::FileError {
         path: PathBuf::from(path),
         error: e,
     })?;
 
-    let mut config_toml_str = String::new();
-    let file_read_res = config_file.read_to_string(&mut config_toml_str);
+    let mut config_str = String::new();
+    let file_read_res = config_file.read_to_string(&mut config_str);
     if let Err(e) = file_read_res {
         return Err(WorkspaceConfigError::FileError {
             path: PathBuf::from(path),
@@ -197,7 +489,143 @@ pub fn parse_workspace_config_file(path: &Path) -> Result<WorkspaceConfig, Works
         });
     }
 
-    parse_workspace_config(config_toml_str.as_str(), path)
+    Ok(config_str)
+}
+
+/// Parse a workspace config file, with defaults taken from the user config file at
+/// `user_config_path`, if it is given and exists
+pub fn parse_workspace_config_file(
+    path: &Path,
+    user_config_path: Option<&Path>,
+) -> Result<WorkspaceConfig, WorkspaceConfigError> {
+    let config_toml_str = read_config_file(path)?;
+
+    let user_config = match user_config_path {
+        Some(user_config_path) if user_config_path.exists() => parse_user_config(
+            read_config_file(user_config_path)?.as_str(),
+            user_config_path,
+        )?,
+        _ => toml::Table::new(),
+    };
+
+    parse_workspace_config_with_user_config(config_toml_str.as_str(), path, user_config)
+}
+
+/// The path to the user config file, (~/.config/cobble/config.toml, or under $XDG_CONFIG_HOME if
+/// it is set)
+pub fn user_config_path() -> Option<PathBuf> {
+    let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => {
+            let home_dir = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+            PathBuf::from(home_dir).join(".config")
+        }
+    };
+
+    Some(config_dir.join("cobble").join(USER_CONFIG_FILE_NAME))
+}
+
+/// Parse the user config file, making sure that it only contains settings that are allowed in it
+pub fn parse_user_config(
+    config_str: &str,
+    path: &Path,
+) -> Result<toml::Table, WorkspaceConfigError> {
+    let config: toml::Table = config_str.parse().map_err(|e| {
+        WorkspaceConfigError::ParseError(format!("Error parsing {}: {}", path.display(), e))
+    })?;
+
+    for key in config.keys() {
+        if !USER_CONFIG_KEYS.contains(&key.as_str()) {
+            return Err(WorkspaceConfigError::ValueError(format!(
+                "Field '{}' is not allowed in {}.  Expected one of [{}]",
+                key,
+                path.display(),
+                USER_CONFIG_KEYS.join(", ")
+            )));
+        }
+    }
+
+    Ok(config)
+}
+
+/// Settings from cobble.local.toml, which holds overrides that aren't checked in to source control
+#[derive(Debug, Default)]
+pub struct LocalWorkspaceConfig {
+    pub vars: HashMap<String, TaskVar>,
+    pub profiles: HashMap<String, HashMap<String, TaskVar>>,
+}
+
+pub fn parse_local_workspace_config(
+    config_str: &str,
+) -> Result<LocalWorkspaceConfig, WorkspaceConfigError> {
+    let mut config: toml::Table = config_str.parse().map_err(|e| {
+        WorkspaceConfigError::ParseError(format!(
+            "Error parsing {}: {}",
+            LOCAL_WORKSPACE_CONFIG_FILE_NAME, e
+        ))
+    })?;
+
+    let vars = match config.remove("vars") {
+        Some(val) => parse_vars_table(val, "vars")?,
+        None => HashMap::new(),
+    };
+
+    let profiles = match config.remove("profiles") {
+        Some(val) => parse_profiles_table(val)?,
+        None => HashMap::new(),
+    };
+
+    if let Some((key, _)) = config.iter().next() {
+        return Err(WorkspaceConfigError::ValueError(format!(
+            "Unrecognized field '{}' in {}.  Only 'vars' and 'profiles' can be set in {}",
+            key, LOCAL_WORKSPACE_CONFIG_FILE_NAME, LOCAL_WORKSPACE_CONFIG_FILE_NAME
+        )));
+    }
+
+    Ok(LocalWorkspaceConfig { vars, profiles })
+}
+
+/// Parse a file given with --var-file.  The file is a table of vars in either JSON or TOML
+/// format, depending on the file extension.
+pub fn parse_var_file(path: &Path) -> Result<HashMap<String, TaskVar>, WorkspaceConfigError> {
+    let var_file_str = read_config_file(path)?;
+
+    let is_json = path
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case("json"))
+        .unwrap_or(false);
+
+    if is_json {
+        let value: serde_json::Value = serde_json::from_str(var_file_str.as_str()).map_err(|e| {
+            WorkspaceConfigError::ParseError(format!("Error parsing {}: {}", path.display(), e))
+        })?;
+        match value {
+            serde_json::Value::Object(obj) => {
+                let mut vars: HashMap<String, TaskVar> = HashMap::with_capacity(obj.len());
+                for (k, v) in obj {
+                    let var_value = TaskVar::try_from(v).map_err(|e| {
+                        WorkspaceConfigError::ValueError(format!(
+                            "in {} at '{}': {}",
+                            path.display(),
+                            k,
+                            e
+                        ))
+                    })?;
+                    vars.insert(k, var_value);
+                }
+                Ok(vars)
+            }
+            _ => Err(WorkspaceConfigError::ValueError(format!(
+                "{} must contain a JSON object",
+                path.display()
+            ))),
+        }
+    } else {
+        let table: toml::Table = var_file_str.parse().map_err(|e| {
+            WorkspaceConfigError::ParseError(format!("Error parsing {}: {}", path.display(), e))
+        })?;
+        Ok(table.into_iter().map(|(k, v)| (k, v.into())).collect())
+    }
 }
 
 pub fn find_nearest_workspace_config_file_from(path: &Path) -> Result<PathBuf, io::Error> {
@@ -245,10 +673,33 @@ pub fn find_nearest_project_dir(path: &Path, workspace_dir: &Path) -> Result<Pat
 pub fn get_workspace_config(
     path: &Path,
     args: &WorkspaceConfigArgs,
+) -> Result<WorkspaceConfig, WorkspaceConfigError> {
+    let cobble_env: HashMap<String, String> = std::env::vars()
+        .filter(|(k, _)| k.starts_with("COBBLE_"))
+        .collect();
+    get_workspace_config_with_env(path, args, &cobble_env, user_config_path().as_deref())
+}
+
+/// Get the workspace config, with settings from files overridden by COBBLE_* environment variables
+/// in `env`, which are in turn overridden by command line arguments
+fn get_workspace_config_with_env(
+    path: &Path,
+    args: &WorkspaceConfigArgs,
+    env: &HashMap<String, String>,
+    user_config_path: Option<&Path>,
 ) -> Result<WorkspaceConfig, WorkspaceConfigError> {
     let config_path = find_nearest_workspace_config_file_from(path)
         .map_err(|e| WorkspaceConfigError::IOError(e))?;
-    let mut config = parse_workspace_config_file(config_path.as_path())?;
+    let mut config = parse_workspace_config_file(config_path.as_path(), user_config_path)?;
+
+    let local_config_path = config.workspace_dir.join(LOCAL_WORKSPACE_CONFIG_FILE_NAME);
+    let local_config = if local_config_path.exists() {
+        parse_local_workspace_config(read_config_file(local_config_path.as_path())?.as_str())?
+    } else {
+        LocalWorkspaceConfig::default()
+    };
+
+    apply_env_settings(env, &mut config)?;
 
     if let Some(force_run_tasks) = args.force_run_tasks {
         config.force_run_tasks = force_run_tasks;
@@ -266,13 +717,189 @@ pub fn get_workspace_config(
         config.show_stderr = show_stderr.clone();
     }
 
-    add_cli_vars_to_workspace_config(args.vars.iter().map(String::as_str), &mut config)?;
+    if let Some(output_mode) = args.output_mode {
+        config.output_mode = output_mode;
+    }
+
+    // Vars are merged in order of precedence, lowest first
+    let profile = args.profile.as_ref().or_else(|| env.get(ENV_PROFILE));
+    if let Some(profile) = profile {
+        let profile_vars = config.profiles.get(profile).cloned();
+        let local_profile_vars = local_config.profiles.get(profile).cloned();
+        if profile_vars.is_none() && local_profile_vars.is_none() {
+            let mut profile_names: Vec<&str> = config
+                .profiles
+                .keys()
+                .chain(local_config.profiles.keys())
+                .map(String::as_str)
+                .collect();
+            profile_names.sort();
+            profile_names.dedup();
+            return Err(WorkspaceConfigError::ValueError(format!(
+                "Profile '{}' not found.  Available profiles: [{}]",
+                profile,
+                profile_names.join(", ")
+            )));
+        }
+
+        if let Some(vars) = profile_vars {
+            merge_vars(vars, VarSource::Profile(profile.clone()), &mut config)?;
+        }
+        merge_vars(local_config.vars, VarSource::LocalConfig, &mut config)?;
+        if let Some(vars) = local_profile_vars {
+            merge_vars(vars, VarSource::LocalConfig, &mut config)?;
+        }
+        config.profile = Some(profile.clone());
+    } else {
+        merge_vars(local_config.vars, VarSource::LocalConfig, &mut config)?;
+    }
+
+    for var_file in args.var_files.iter() {
+        let vars = parse_var_file(path.join(var_file).as_path())?;
+        merge_vars(vars, VarSource::VarFile(var_file.clone()), &mut config)?;
+    }
+
+    add_env_vars_to_workspace_config(env, &mut config)?;
+
+    add_cli_vars_to_workspace_config(args.vars.iter().map(String::as_str), false, &mut config)?;
+    add_cli_vars_to_workspace_config(args.secret_vars.iter().map(String::as_str), true, &mut config)?;
 
     Ok(config)
 }
 
+fn parse_env_setting<T, F>(
+    env: &HashMap<String, String>,
+    name: &str,
+    parse: F,
+) -> Result<Option<T>, WorkspaceConfigError>
+where
+    F: FnOnce(&str) -> Result<T, String>,
+{
+    match env.get(name) {
+        Some(value) => parse(value.as_str()).map(Some).map_err(|e| {
+            WorkspaceConfigError::ValueError(format!("in environment variable {}: {}", name, e))
+        }),
+        None => Ok(None),
+    }
+}
+
+/// Apply workspace settings given in COBBLE_* environment variables
+fn apply_env_settings(
+    env: &HashMap<String, String>,
+    config: &mut WorkspaceConfig,
+) -> Result<(), WorkspaceConfigError> {
+    if let Some(num_threads) = parse_env_setting(env, ENV_NUM_THREADS, |v| {
+        v.parse::<u8>().map_err(|e| e.to_string())
+    })? {
+        config.num_threads = num_threads;
+    }
+
+    if let Some(output) = parse_env_setting(env, ENV_OUTPUT, parse_output_condition)? {
+        config.show_stdout = output.clone();
+        config.show_stderr = output;
+    }
+
+    if let Some(stdout) = parse_env_setting(env, ENV_STDOUT, parse_output_condition)? {
+        config.show_stdout = stdout;
+    }
+
+    if let Some(stderr) = parse_env_setting(env, ENV_STDERR, parse_output_condition)? {
+        config.show_stderr = stderr;
+    }
+
+    if let Some(output_mode) = parse_env_setting(env, ENV_OUTPUT_MODE, parse_output_mode)? {
+        config.output_mode = output_mode;
+    }
+
+    if let Some(pty) = parse_env_setting(env, ENV_PTY, |v| match v.to_lowercase().as_str() {
+        "true" | "1" => Ok(true),
+        "false" | "0" => Ok(false),
+        _ => Err(format!("Expected true or false, but got '{}'", v)),
+    })? {
+        config.pty = pty;
+    }
+
+    if let Some(log_retention) = parse_env_setting(env, ENV_LOG_RETENTION, |v| {
+        v.parse::<usize>().map_err(|e| e.to_string())
+    })? {
+        config.log_retention = log_retention;
+    }
+
+    Ok(())
+}
+
+/// Set vars from COBBLE_VAR_<name> and COBBLE_SECRET_VAR_<name> environment variables.  Since
+/// environment variable names can't contain '.', a double underscore in the name is used to
+/// separate the parts of a var name.
+fn add_env_vars_to_workspace_config(
+    env: &HashMap<String, String>,
+    config: &mut WorkspaceConfig,
+) -> Result<(), WorkspaceConfigError> {
+    let mut env_var_names: Vec<&String> = env.keys().collect();
+    env_var_names.sort();
+
+    for env_var_name in env_var_names {
+        let (var_name, secret) = if let Some(name) = env_var_name.strip_prefix(ENV_SECRET_VAR_PREFIX) {
+            (name.replace("__", "."), true)
+        } else if let Some(name) = env_var_name.strip_prefix(ENV_VAR_PREFIX) {
+            (name.replace("__", "."), false)
+        } else {
+            continue;
+        };
+
+        set_var(
+            var_name.as_str(),
+            TaskVar::String(env[env_var_name].clone()),
+            &mut config.vars,
+        )
+        .map_err(WorkspaceConfigError::SetVarError)?;
+
+        config
+            .var_sources
+            .insert(var_name.clone(), VarSource::Environment(env_var_name.clone()));
+
+        if secret {
+            config.secret_vars.insert(var_name);
+        }
+    }
+
+    Ok(())
+}
+
+/// Set each value in a table of vars, so that a var set in the table only replaces that var, and
+/// not the whole table it is in
+fn merge_vars(
+    vars: HashMap<String, TaskVar>,
+    source: VarSource,
+    config: &mut WorkspaceConfig,
+) -> Result<(), WorkspaceConfigError> {
+    let mut flattened_vars: Vec<(String, TaskVar)> = Vec::new();
+    flatten_vars("", vars, &mut flattened_vars);
+
+    for (var_name, value) in flattened_vars {
+        set_var(var_name.as_str(), value, &mut config.vars)
+            .map_err(WorkspaceConfigError::SetVarError)?;
+        config.var_sources.insert(var_name, source.clone());
+    }
+
+    Ok(())
+}
+
+fn flatten_vars(prefix: &str, vars: HashMap<String, TaskVar>, out: &mut Vec<(String, TaskVar)>) {
+    for (key, value) in vars {
+        let var_name = format!("{}{}", prefix, key);
+        match value {
+            TaskVar::Table(t) if !t.is_empty() => {
+                flatten_vars(format!("{}.", var_name).as_str(), t, out);
+            }
+            _ => out.push((var_name, value)),
+        }
+    }
+}
+
 fn add_cli_vars_to_workspace_config<'a, I>(
     vars: I,
+    secret: bool,
     config: &mut WorkspaceConfig,
 ) -> Result<(), WorkspaceConfigError>
 where
@@ -283,7 +910,7 @@ where
             Some(i) => i,
             None => {
                 return Err(WorkspaceConfigError::ValueError(
-                    format!("Unable to parse variable argument '{}'.  Specify variable arguments in the form '--var <the.var.name>=<value>'.", var)
+                    format!("Unable to parse variable argument '{}'.  Specify variable arguments in the form '{} <the.var.name>=<value>'.", var, if secret { "--secret-var" } else { "--var" })
                 ));
             }
         };
@@ -297,6 +924,14 @@ where
             &mut config.vars,
         )
         .map_err(|e| WorkspaceConfigError::SetVarError(e))?;
+
+        config
+            .var_sources
+            .insert(var_name.to_owned(), VarSource::CommandLine);
+
+        if secret {
+            config.secret_vars.insert(var_name.to_owned());
+        }
     }
 
     Ok(())
@@ -305,6 +940,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::vars::get_var;
 
     #[test]
     fn test_parse_workspace_config() {
@@ -316,5 +952,224 @@ mod tests {
             parse_workspace_config(config_toml, Path::new("/home/test/proj/cobble.toml")).unwrap();
         assert_eq!(config.workspace_dir, PathBuf::from("/home/test/proj"));
         assert_eq!(config.root_projects, vec!["proj1", "proj2", "proj3"]);
+        assert!(config.env_passthrough.is_none());
+    }
+
+    #[test]
+    fn test_parse_workspace_config_env_vars() {
+        let config_toml = r#"
+            env_passthrough = ["PATH", "HOME"]
+
+            [env_vars]
+            LANG = "C"
+            TZ = "UTC"
+        "#;
+
+        let config =
+            parse_workspace_config(config_toml, Path::new("/home/test/proj/cobble.toml")).unwrap();
+        assert_eq!(config.env_vars.get("LANG").map(String::as_str), Some("C"));
+        assert_eq!(config.env_vars.get("TZ").map(String::as_str), Some("UTC"));
+        assert_eq!(
+            config.env_passthrough,
+            Some(vec![String::from("PATH"), String::from("HOME")])
+        );
+    }
+
+    #[test]
+    fn test_parse_workspace_config_secret_vars() {
+        let env = HashMap::from([(String::from("TEST_SECRET_TOKEN"), String::from("hunter2"))]);
+        let config_toml = r#"
+            [vars]
+            db.password = "abc"
+
+            [secret_vars]
+            db.password = true
+            api.token = "TEST_SECRET_TOKEN"
+        "#;
+
+        let config = parse_workspace_config_with_env(
+            config_toml,
+            Path::new("/home/test/proj/cobble.toml"),
+            toml::Table::new(),
+            &env,
+        )
+        .unwrap();
+        assert!(config.secret_vars.contains("db.password"));
+        assert!(config.secret_vars.contains("api.token"));
+        assert_eq!(
+            get_var("api.token", &config.vars).unwrap(),
+            &TaskVar::String(String::from("hunter2"))
+        );
+    }
+
+    #[test]
+    fn test_get_workspace_config_var_precedence() {
+        let tmpdir = mktemp::Temp::new_dir().unwrap();
+        std::fs::write(
+            tmpdir.join(WORKSPACE_CONFIG_FILE_NAME),
+            r#"
+            [vars]
+            mode = "debug"
+            db.host = "localhost"
+            db.port = "5432"
+            jobs = "1"
+
+            [profiles.ci.vars]
+            mode = "release"
+            db.host = "db.ci"
+        "#,
+        )
+        .unwrap();
+        std::fs::write(
+            tmpdir.join(LOCAL_WORKSPACE_CONFIG_FILE_NAME),
+            r#"
+            [profiles.ci.vars]
+            db.port = "6543"
+        "#,
+        )
+        .unwrap();
+        std::fs::write(tmpdir.join("vars.json"), r#"{ "jobs": 4 }"#).unwrap();
+
+        let args = WorkspaceConfigArgs {
+            vars: vec![String::from("mode=fast")],
+            var_files: vec![PathBuf::from("vars.json")],
+            profile: Some(String::from("ci")),
+            ..Default::default()
+        };
+        let config = get_workspace_config(tmpdir.as_path(), &args).unwrap();
+
+        let var = |name: &str| get_var(name, &config.vars).unwrap().clone();
+        assert_eq!(var("mode"), TaskVar::String(String::from("fast")));
+        assert_eq!(var("db.host"), TaskVar::String(String::from("db.ci")));
+        assert_eq!(var("db.port"), TaskVar::String(String::from("6543")));
+        assert_eq!(var("jobs"), TaskVar::String(String::from("4")));
+
+        assert_eq!(config.var_source("mode"), Some(VarSource::CommandLine));
+        assert_eq!(config.var_source("db.host"), Some(VarSource::Profile(String::from("ci"))));
+        assert_eq!(config.var_source("db.port"), Some(VarSource::LocalConfig));
+        assert_eq!(config.var_source("jobs"), Some(VarSource::VarFile(PathBuf::from("vars.json"))));
+
+        let missing_profile_args = WorkspaceConfigArgs {
+            profile: Some(String::from("release")),
+            ..Default::default()
+        };
+        assert!(get_workspace_config(tmpdir.as_path(), &missing_profile_args).is_err());
+    }
+
+    #[test]
+    fn test_get_workspace_config_env_overrides() {
+        let tmpdir = mktemp::Temp::new_dir().unwrap();
+        std::fs::write(
+            tmpdir.join(WORKSPACE_CONFIG_FILE_NAME),
+            r#"
+            num_threads = 2
+            output = "never"
+
+            [vars]
+            build.mode = "debug"
+            build.target = "x86_64"
+
+            [profiles.ci.vars]
+            build.target = "aarch64"
+        "#,
+        )
+        .unwrap();
+
+        let env: HashMap<String, String> = vec![
+            (ENV_NUM_THREADS, "8"),
+            (ENV_OUTPUT, "always"),
+            (ENV_STDERR, "on_fail"),
+            (ENV_PROFILE, "ci"),
+            ("COBBLE_VAR_build__mode", "release"),
+            ("COBBLE_SECRET_VAR_token", "hunter2"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (String::from(k), String::from(v)))
+        .collect();
+
+        let args = WorkspaceConfigArgs {
+            num_threads: Some(4),
+            ..Default::default()
+        };
+        let config = get_workspace_config_with_env(tmpdir.as_path(), &args, &env, None).unwrap();
+
+        // Command line arguments take precedence over environment variables
+        assert_eq!(config.num_threads, 4);
+        assert!(matches!(config.show_stdout, TaskOutputCondition::Always));
+        assert!(matches!(config.show_stderr, TaskOutputCondition::OnFail));
+        assert_eq!(config.profile.as_deref(), Some("ci"));
+
+        let var = |name: &str| get_var(name, &config.vars).unwrap().clone();
+        assert_eq!(var("build.mode"), TaskVar::String(String::from("release")));
+        assert_eq!(var("build.target"), TaskVar::String(String::from("aarch64")));
+        assert_eq!(var("token"), TaskVar::String(String::from("hunter2")));
+        assert!(config.secret_vars.contains("token"));
+        assert_eq!(
+            config.var_source("build.mode"),
+            Some(VarSource::Environment(String::from("COBBLE_VAR_build__mode")))
+        );
+
+        let bad_env: HashMap<String, String> =
+            vec![(String::from(ENV_NUM_THREADS), String::from("lots"))]
+                .into_iter()
+                .collect();
+        assert!(get_workspace_config_with_env(tmpdir.as_path(), &Default::default(), &bad_env, None).is_err());
+    }
+
+    #[test]
+    fn test_parse_workspace_config_with_user_config() {
+        let user_config_path = Path::new("/home/test/.config/cobble/config.toml");
+        let user_config = parse_user_config(
+            r#"
+            num_threads = 12
+            output_mode = "tui"
+            stdout = "always"
+        "#,
+            user_config_path,
+        )
+        .unwrap();
+
+        let config = parse_workspace_config_with_user_config(
+            r#"
+            num_threads = 4
+            output = "never"
+        "#,
+            Path::new("/home/test/proj/cobble.toml"),
+            user_config,
+        )
+        .unwrap();
+
+        // Settings in the workspace config take precedence over the user config
+        assert_eq!(config.num_threads, 4);
+        assert!(matches!(config.show_stdout, TaskOutputCondition::Never));
+        assert_eq!(config.output_mode, OutputMode::Tui);
+
+        assert!(parse_user_config("[vars]\nfoo = \"bar\"", user_config_path).is_err());
+        assert!(parse_user_config("root_projects = [\".\"]", user_config_path).is_err());
+    }
+
+    #[test]
+    fn test_get_workspace_config_user_config_file() {
+        let tmpdir = mktemp::Temp::new_dir().unwrap();
+        std::fs::write(tmpdir.join(WORKSPACE_CONFIG_FILE_NAME), "num_threads = 2\n").unwrap();
+        let user_config_path = tmpdir.join(USER_CONFIG_FILE_NAME);
+        std::fs::write(&user_config_path, "num_threads = 12\noutput_mode = \"tui\"\n").unwrap();
+
+        let config = get_workspace_config_with_env(
+            tmpdir.as_path(),
+            &Default::default(),
+            &HashMap::new(),
+            Some(user_config_path.as_path()),
+        )
+        .unwrap();
+        assert_eq!(config.num_threads, 2);
+        assert_eq!(config.output_mode, OutputMode::Tui);
+
+        // Without a user config path, only the workspace config is used
+        let config =
+            get_workspace_config_with_env(tmpdir.as_path(), &Default::default(), &HashMap::new(), None)
+                .unwrap();
+        assert_eq!(config.num_threads, 2);
+        assert_eq!(config.output_mode, OutputMode::Grouped);
     }
 }
@@ -18,6 +18,17 @@ pub struct TaskInput {
     pub file_hashes: HashMap<String, String>,
     pub task_outputs: HashMap<String, serde_json::Value>,
     pub vars: HashMap<String, TaskVar>,
+    #[serde(default)]
+    pub env_vars: HashMap<String, String>,
+    /// Values of secret vars.  These are only kept in memory, and are never written to the database.
+    #[serde(skip)]
+    pub secret_vars: HashMap<String, TaskVar>,
+    /// Salt used to hash the values of secret vars
+    #[serde(default)]
+    pub secret_var_salt: String,
+    /// Salted hashes of the values of secret vars, used to detect when a secret var changes
+    #[serde(default)]
+    pub secret_var_hashes: HashMap<String, String>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -30,6 +41,9 @@ pub struct TaskOutput {
 pub struct TaskRecord {
     pub input: TaskInput,
     pub output: TaskOutput,
+    /// How long the task's actions took to run, the last time the task was run
+    #[serde(default)]
+    pub duration_ms: Option<u64>,
 }
 
 fn get_task_key(task_name: &str) -> String {
@@ -322,7 +322,12 @@ pub fn create_task_action_context<'lua>(
             extra_tools: task.tools.clone(),
             extra_envs: task.build_envs.clone(),
             files,
-            vars: task_input.vars.clone(),
+            vars: task_input
+                .vars
+                .iter()
+                .chain(task_input.secret_vars.iter())
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
             task_outputs: task_input.task_outputs.clone(),
             project_dir,
             args,
@@ -7,20 +7,31 @@ use std::cmp::max;
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::error::Error;
 use std::fmt;
-use std::io;
+use std::io::{self, IsTerminal};
 use std::path::Path;
-use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
 use std::sync::{Arc, Condvar, Mutex, RwLock};
 use std::thread::{self, JoinHandle};
-
-use crate::config::{TaskOutputCondition, WorkspaceConfig};
-use crate::db::{new_db_env, DeleteError, GetError, PutError};
-use crate::execute::job_io::ConcurrentIO;
+use std::time::{Duration, Instant};
+
+use crate::config::{OutputMode, TaskOutputCondition, WorkspaceConfig};
+use crate::db::{delete_task_record, get_task_record, new_db_env, DeleteError, GetError, PutError};
+use crate::execute::job_io::{ConcurrentIO, JobIO};
+use crate::execute::job_log::{new_run_id, JobLogger};
+use crate::execute::prefixed_io::PrefixedIO;
+use crate::execute::secrets::{OutputStream, SecretMasker};
+use crate::execute::tui_io::TuiIO;
+use crate::execute::status::{compute_task_statuses, TaskStatus};
 use crate::execute::worker::{run_task_executor_worker, TaskExecutorWorkerArgs};
 use crate::project_def::ExternalTool;
-use crate::vars::VarLookupError;
+use crate::trace::Tracer;
+use crate::project_def::types::TaskVar;
+use crate::vars::{get_var, VarLookupError};
 use crate::workspace::{BuildEnv, Task, TaskType, Workspace};
 
+/// How often to let the job output display update while waiting for messages from jobs
+const JOB_IO_TICK_INTERVAL: Duration = Duration::from_millis(100);
+
 pub enum ExecutorJob {
     Task(TaskJob),
     Clean(CleanJob),
@@ -34,6 +45,8 @@ pub struct TaskJob {
     pub task_name: Arc<str>,
     pub task: Arc<Task>,
     pub workspace: Arc<Workspace>,
+    /// Run the task even if it is up-to-date
+    pub force_run: bool,
 }
 
 #[derive(Debug)]
@@ -64,6 +77,8 @@ pub enum TaskJobMessage {
     Started {
         task: Arc<str>,
         stdin_ready: Arc<(Mutex<bool>, Condvar)>,
+        /// Whether the job waits for stdin_ready before reading from stdin
+        interactive: bool,
         show_stdout: TaskOutputCondition,
         show_stderr: TaskOutputCondition        
     },
@@ -105,6 +120,8 @@ pub enum TaskExecutionError {
     ActionFailed(String),
     SerializeError(serde_json::Error),
     GraphError(String),
+    /// An error whose message contained the value of a secret var, with the value masked out
+    MaskedError(String),
 }
 
 impl Error for TaskExecutionError {}
@@ -135,6 +152,7 @@ impl fmt::Display for TaskExecutionError {
             ActionFailed(s) => write!(f, "Action failed: {}", s),
             SerializeError(e) => write!(f, "(De)serialization of value failed: {}", e),
             GraphError(s) => write!(f, "{}", s),
+            MaskedError(s) => write!(f, "{}", s),
         }
     }
 }
@@ -151,7 +169,7 @@ fn get_env_action_job_id(env_name: &Arc<str>) -> Arc<str> {
     Arc::<str>::from(job_name)
 }
 
-fn get_task_job_dependencies(task: &Task, workspace: &Workspace) -> Result<Vec<Arc<str>>, TaskExecutionError> {
+pub fn get_task_job_dependencies(task: &Task, workspace: &Workspace) -> Result<Vec<Arc<str>>, TaskExecutionError> {
     let mut deps_set: HashSet<Arc<str>> = HashSet::with_capacity(task.task_deps.len());
     
     for task_dep in task.task_deps.values() {
@@ -176,15 +194,33 @@ fn get_task_job_dependencies(task: &Task, workspace: &Workspace) -> Result<Vec<A
     Ok(deps_set.into_iter().collect())
 }
 
-fn add_task_jobs(
+/// Which parts of the dependency graph of the selected tasks get run
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DependencyMode {
+    /// Run the selected tasks and all of their dependencies
+    #[default]
+    All,
+    /// Run only the selected tasks, using the recorded outputs of their dependencies
+    NoDeps,
+    /// Run only the dependencies of the selected tasks
+    OnlyDeps,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ExecuteOptions {
+    pub dependency_mode: DependencyMode,
+    /// Tasks to run even if they are up-to-date.  Tasks that depend on them are also run.
+    pub force_run_from: Vec<Arc<str>>,
+    /// Tasks to run even if they are up-to-date.  Other tasks are only run if they are out of date.
+    pub force_run_tasks: Vec<Arc<str>>,
+}
+
+/// Add a job for the task alone, without its dependencies
+fn add_task_job(
     task_name: &Arc<str>,
     workspace: &Arc<Workspace>,
     jobs: &mut HashMap<Arc<str>, ExecutorJob>,
-) -> Result<(), TaskExecutionError> {
-    if jobs.contains_key(task_name) {
-        return Ok(());
-    }
-
+) -> Result<Arc<Task>, TaskExecutionError> {
     let task = workspace
         .tasks
         .get(task_name)
@@ -200,17 +236,72 @@ fn add_task_jobs(
         task_name: task_name.to_owned(),
         task: task.clone(),
         workspace: workspace.clone(),
+        force_run: false,
     });
 
     jobs.insert(task_name.to_owned(), job);
 
-    for dep in get_task_job_dependencies(&*task, workspace.as_ref())? {
+    Ok(task.clone())
+}
+
+fn add_task_jobs(
+    task_name: &Arc<str>,
+    workspace: &Arc<Workspace>,
+    jobs: &mut HashMap<Arc<str>, ExecutorJob>,
+) -> Result<(), TaskExecutionError> {
+    if jobs.contains_key(task_name) {
+        return Ok(());
+    }
+
+    let task = add_task_job(task_name, workspace, jobs)?;
+
+    for dep in get_task_job_dependencies(&task, workspace.as_ref())? {
         add_task_jobs(&dep, workspace, jobs)?;
     }
 
     Ok(())
 }
 
+/// Mark the given tasks, and all jobs in the graph that depend on them, to be run even if they
+/// are up-to-date
+fn force_run_downstream_jobs(
+    jobs: &mut HashMap<Arc<str>, ExecutorJob>,
+    force_run_from: &[Arc<str>],
+    workspace: &Workspace,
+) -> Result<(), TaskExecutionError> {
+    let rev_dep_edges = compute_reverse_dependency_edges(&compute_dependency_edges(jobs, workspace)?);
+
+    let mut forced: HashSet<Arc<str>> = HashSet::new();
+    let mut stack: Vec<Arc<str>> = force_run_from.to_vec();
+    while let Some(job_id) = stack.pop() {
+        if !forced.insert(job_id.clone()) {
+            continue;
+        }
+
+        if let Some(dependents) = rev_dep_edges.get(&job_id) {
+            stack.extend(dependents.iter().cloned());
+        }
+    }
+
+    for job_id in forced {
+        if let Some(ExecutorJob::Task(task_job)) = jobs.get_mut(&job_id) {
+            task_job.force_run = true;
+        }
+    }
+
+    Ok(())
+}
+
+/// Mark the jobs for the given tasks to be run even if they are up-to-date.  Jobs that depend on
+/// them are left alone.
+fn force_run_jobs(jobs: &mut HashMap<Arc<str>, ExecutorJob>, force_run_tasks: &[Arc<str>]) {
+    for task_name in force_run_tasks {
+        if let Some(ExecutorJob::Task(task_job)) = jobs.get_mut(task_name) {
+            task_job.force_run = true;
+        }
+    }
+}
+
 pub fn get_clean_task_name(task_name: &str) -> Arc<str> {
     let mut clean_task_name = String::from("clean:");
     clean_task_name.push_str(task_name);
@@ -475,6 +566,60 @@ fn has_missing_dependencies(
     None
 }
 
+/// Find a job that depends on a variable that isn't set, so that it is reported before any jobs run
+fn find_missing_var_dependency(
+    nodes: &HashMap<Arc<str>, ExecutorJob>,
+    vars: &HashMap<String, TaskVar>,
+) -> Option<(Arc<str>, VarLookupError)> {
+    let mut job_ids: Vec<&Arc<str>> = nodes.keys().collect();
+    job_ids.sort();
+
+    for job_id in job_ids {
+        let task = match &nodes[job_id] {
+            ExecutorJob::Task(task_job) => &task_job.task,
+            ExecutorJob::Clean(clean_job) => &clean_job.task,
+            ExecutorJob::ToolCheck(_) | ExecutorJob::EnvAction(_) => continue,
+        };
+
+        let mut var_names: Vec<&Arc<str>> = task.var_deps.values().collect();
+        var_names.sort();
+        for var_name in var_names {
+            if let Err(e) = get_var(var_name, vars) {
+                return Some((job_id.clone(), e));
+            }
+        }
+    }
+
+    None
+}
+
+fn create_job_io(output_mode: OutputMode, nodes: &HashMap<Arc<str>, ExecutorJob>) -> Box<dyn JobIO> {
+    match output_mode {
+        OutputMode::Grouped => Box::new(ConcurrentIO::new()),
+        OutputMode::Prefixed => Box::new(PrefixedIO::new(nodes.keys())),
+        OutputMode::Tui => {
+            // The dashboard takes over the terminal, so it can't be used if a job needs to
+            // interact with the user, or if there is no terminal to draw on
+            let has_interactive_jobs = nodes.values().any(|job| match job {
+                ExecutorJob::Task(task_job) => task_job.task.is_interactive,
+                ExecutorJob::EnvAction(_) => true,
+                ExecutorJob::Clean(_) | ExecutorJob::ToolCheck(_) => false,
+            });
+            if has_interactive_jobs || !io::stdout().is_terminal() {
+                return Box::new(ConcurrentIO::new());
+            }
+
+            match TuiIO::new(nodes.keys()) {
+                Ok(tui_io) => Box::new(tui_io),
+                Err(e) => {
+                    eprintln!("WARNING: Unable to start the dashboard, falling back to grouped output: {}", e);
+                    Box::new(ConcurrentIO::new())
+                }
+            }
+        }
+    }
+}
+
 pub struct TaskExecutorCache {
     pub project_source_hashes: RwLock<HashMap<Arc<str>, String>>,
     pub file_hashes: RwLock<HashMap<Arc<str>, String>>,
@@ -494,6 +639,10 @@ pub struct TaskExecutor {
     job_queue: Arc<(Mutex<Option<VecDeque<ExecutorJob>>>, Condvar)>,
     message_channel: (Sender<TaskJobMessage>, Receiver<TaskJobMessage>),
     cache: Arc<TaskExecutorCache>,
+    run_id: Arc<str>,
+    next_worker_id: u64,
+    tracer: Option<Arc<Tracer>>,
+    job_output_to_stderr: bool,
 }
 
 impl TaskExecutor {
@@ -512,13 +661,74 @@ impl TaskExecutor {
                 file_hashes: RwLock::new(HashMap::new()),
                 task_outputs: RwLock::new(HashMap::new()),
             }),
+            run_id: new_run_id(),
+            next_worker_id: 1,
+            tracer: None,
+            job_output_to_stderr: false,
         })
     }
 
+    /// Identifies the log files written for jobs executed by this executor
+    pub fn run_id(&self) -> &Arc<str> {
+        &self.run_id
+    }
+
+    /// Record a trace span for each job that is executed.  Must be set before any jobs are run.
+    pub fn set_tracer(&mut self, tracer: Arc<Tracer>) {
+        assert!(
+            self.worker_threads.is_empty(),
+            "The tracer must be set before worker threads are started"
+        );
+        self.tracer = Some(tracer);
+    }
+
+    /// Print job status and output to stderr, for commands that write machine-readable output to
+    /// stdout
+    pub fn set_job_output_to_stderr(&mut self) {
+        self.job_output_to_stderr = true;
+    }
+
     pub fn cache(&self) -> Arc<TaskExecutorCache> {
         self.cache.clone()
     }
 
+    /// Look up how long each task took the last time it was run.  Tasks that haven't been run, (or
+    /// were last run by a version of cobble that didn't record durations,) are left out.
+    pub fn task_durations<'a, T>(&self, tasks: T) -> HashMap<Arc<str>, Duration>
+    where
+        T: Iterator<Item = &'a Arc<str>>,
+    {
+        tasks
+            .filter_map(|task_name| {
+                let record = get_task_record(&self.db_env, self.db, task_name).ok()?;
+                let duration = Duration::from_millis(record.duration_ms?);
+                Some((task_name.clone(), duration))
+            })
+            .collect()
+    }
+
+    /// Check whether each task in the dependency graphs of the given tasks is up to date, without
+    /// running anything
+    pub fn task_statuses<'a, T>(
+        &self,
+        workspace: &Workspace,
+        tasks: T,
+        artifacts_calculated: bool,
+    ) -> Result<HashMap<Arc<str>, TaskStatus>, TaskExecutionError>
+    where
+        T: Iterator<Item = &'a Arc<str>>,
+    {
+        compute_task_statuses(
+            &self.workspace_config,
+            workspace,
+            tasks,
+            artifacts_calculated,
+            &self.db_env,
+            &self.db,
+            &self.cache,
+        )
+    }
+
     pub fn ensure_worker_threads(&mut self) {
         self.worker_threads = self
             .worker_threads
@@ -536,7 +746,10 @@ impl TaskExecutor {
                     task_queue: self.job_queue.clone(),
                     task_result_sender: self.message_channel.0.clone(),
                     cache: self.cache.clone(),
+                    worker_id: self.next_worker_id,
+                    tracer: self.tracer.clone(),
                 };
+                self.next_worker_id += 1;
 
                 let worker_thread = thread::spawn(move || run_task_executor_worker(worker_args));
 
@@ -570,6 +783,18 @@ impl TaskExecutor {
         workspace: &Workspace,
         tasks: T,
     ) -> Result<(), TaskExecutionError>
+    where
+        T: Iterator<Item = &'a Arc<str>>,
+    {
+        self.execute_tasks_with_options(workspace, tasks, &ExecuteOptions::default())
+    }
+
+    pub fn execute_tasks_with_options<'a, T>(
+        &mut self,
+        workspace: &Workspace,
+        tasks: T,
+        options: &ExecuteOptions,
+    ) -> Result<(), TaskExecutionError>
     where
         T: Iterator<Item = &'a Arc<str>>,
     {
@@ -579,10 +804,87 @@ impl TaskExecutor {
         let mut jobs: HashMap<Arc<str>, ExecutorJob> = HashMap::new();
 
         for task in tasks {
-            add_task_jobs(task, &frozen_workspace, &mut jobs)?;
+            match options.dependency_mode {
+                DependencyMode::All => add_task_jobs(task, &frozen_workspace, &mut jobs)?,
+                DependencyMode::NoDeps => {
+                    add_task_job(task, &frozen_workspace, &mut jobs)?;
+                }
+                DependencyMode::OnlyDeps => {
+                    let task_def = frozen_workspace
+                        .tasks
+                        .get(task)
+                        .ok_or_else(|| TaskExecutionError::TaskLookupError(task.clone()))?;
+                    for dep in get_task_job_dependencies(task_def, &frozen_workspace)? {
+                        add_task_jobs(&dep, &frozen_workspace, &mut jobs)?;
+                    }
+                }
+            }
         }
 
-        self.execute_graph(jobs, &frozen_workspace)
+        let use_recorded_deps = options.dependency_mode == DependencyMode::NoDeps;
+        if use_recorded_deps {
+            if let Some((job_id, dep)) = self.find_dependency_without_record(&jobs) {
+                return Err(TaskExecutionError::GraphError(format!(
+                    "{} depends on {}, which has not been run yet, so there is no recorded output to use in its place",
+                    job_id, dep
+                )));
+            }
+        }
+
+        if !options.force_run_from.is_empty() {
+            force_run_downstream_jobs(&mut jobs, &options.force_run_from, &frozen_workspace)?;
+        }
+
+        force_run_jobs(&mut jobs, &options.force_run_tasks);
+
+        self.execute_graph_with_recorded_deps(jobs, &frozen_workspace, use_recorded_deps)
+    }
+
+    /// Delete the recorded results of the given tasks, so that they are run the next time they
+    /// are selected
+    pub fn invalidate_tasks<'a, T>(&self, tasks: T) -> Result<(), TaskExecutionError>
+    where
+        T: Iterator<Item = &'a Arc<str>>,
+    {
+        for task_name in tasks {
+            delete_task_record(&self.db_env, self.db, task_name)
+                .map_err(TaskExecutionError::DBDeleteError)?;
+            self.cache.task_outputs.write().unwrap().remove(task_name);
+        }
+
+        Ok(())
+    }
+
+    /// Find a task dependency that is not part of the job graph, and has no recorded output to use
+    /// in place of running it
+    fn find_dependency_without_record(
+        &self,
+        jobs: &HashMap<Arc<str>, ExecutorJob>,
+    ) -> Option<(Arc<str>, Arc<str>)> {
+        let mut job_ids: Vec<&Arc<str>> = jobs.keys().collect();
+        job_ids.sort();
+
+        for job_id in job_ids {
+            let task = match &jobs[job_id] {
+                ExecutorJob::Task(task_job) => &task_job.task,
+                _ => continue,
+            };
+
+            let mut deps: Vec<&Arc<str>> =
+                task.task_deps.values().chain(task.build_envs.values()).collect();
+            deps.sort();
+
+            for dep in deps {
+                let has_output = jobs.contains_key(dep)
+                    || self.cache.task_outputs.read().unwrap().contains_key(dep)
+                    || get_task_record(&self.db_env, self.db, dep).is_ok();
+                if !has_output {
+                    return Some((job_id.clone(), dep.clone()));
+                }
+            }
+        }
+
+        None
     }
 
     pub fn clean_tasks<'a, T>(
@@ -625,7 +927,24 @@ impl TaskExecutor {
         nodes: HashMap<Arc<str>, ExecutorJob>,
         workspace: &Arc<Workspace>
     ) -> Result<(), TaskExecutionError> {
-        let dep_edges = &compute_dependency_edges(&nodes, workspace.as_ref())?;
+        self.execute_graph_with_recorded_deps(nodes, workspace, false)
+    }
+
+    /// Execute the job graph.  If `use_recorded_deps` is true, dependencies that are not part of
+    /// the graph are skipped, and the outputs recorded the last time they ran are used instead.
+    fn execute_graph_with_recorded_deps(
+        &mut self,
+        nodes: HashMap<Arc<str>, ExecutorJob>,
+        workspace: &Arc<Workspace>,
+        use_recorded_deps: bool,
+    ) -> Result<(), TaskExecutionError> {
+        let mut dep_edges = compute_dependency_edges(&nodes, workspace.as_ref())?;
+        if use_recorded_deps {
+            for job_deps in dep_edges.values_mut() {
+                job_deps.retain(|dep| nodes.contains_key(dep));
+            }
+        }
+        let dep_edges = &dep_edges;
 
         if let Some(cyclic_node) = has_cycle(dep_edges) {
             return Err(TaskExecutionError::GraphError(format!(
@@ -639,12 +958,27 @@ impl TaskExecutor {
                 job_id, missing_dep
             )));
         }
+        if let Some((job_id, e)) = find_missing_var_dependency(&nodes, &self.workspace_config.vars) {
+            return Err(TaskExecutionError::GraphError(format!(
+                "{} depends on a variable that is not set: {}",
+                job_id, e
+            )));
+        }
 
         let rev_dep_edges = compute_reverse_dependency_edges(dep_edges);
 
         let mut in_progress_jobs: HashSet<Arc<str>> = HashSet::new();
         let mut completed_jobs: HashSet<Arc<str>> = HashSet::new();
-        let mut concurrent_io = ConcurrentIO::new();
+        let mut job_io: Box<dyn JobIO> = match self.job_output_to_stderr {
+            true => Box::new(ConcurrentIO::new_on_stderr()),
+            false => create_job_io(self.workspace_config.output_mode, &nodes),
+        };
+        let mut secret_masker = SecretMasker::from_workspace_config(&self.workspace_config);
+        let mut job_logger = JobLogger::new(
+            self.workspace_config.workspace_dir.as_path(),
+            self.run_id.clone(),
+            self.workspace_config.log_retention,
+        );
 
         let mut remaining_jobs = nodes;
 
@@ -669,28 +1003,70 @@ impl TaskExecutor {
             self.push_task_job(task_name, job, &mut in_progress_jobs)?;
         }
 
+        let mut last_tick = Instant::now();
         while completed_jobs.len() < total_jobs {
-            let message = self.message_channel.1.recv().map_err(|_| {
-                TaskExecutionError::ExecutorError(String::from(
-                    "Executor message channel closed before all tasks completed",
-                ))
-            })?;
+            let tick_timeout = JOB_IO_TICK_INTERVAL.saturating_sub(last_tick.elapsed());
+            let message_opt = match self.message_channel.1.recv_timeout(tick_timeout) {
+                Ok(message) => Some(message),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(TaskExecutionError::ExecutorError(String::from(
+                        "Executor message channel closed before all tasks completed",
+                    )));
+                }
+            };
+
+            // Tick on a schedule rather than only when no messages arrive, so that jobs with a
+            // steady stream of output can't keep the job IO from handling input
+            if last_tick.elapsed() >= JOB_IO_TICK_INTERVAL {
+                job_io.tick();
+                last_tick = Instant::now();
+            }
+
+            let message = match message_opt {
+                Some(message) => message,
+                None => continue,
+            };
 
             match message {
-                TaskJobMessage::Started { task, stdin_ready, show_stdout, show_stderr } => {
-                    concurrent_io.job_started(&task, stdin_ready, show_stdout, show_stderr);
+                TaskJobMessage::Started { task, stdin_ready, interactive, show_stdout, show_stderr } => {
+                    job_logger.job_started(&task);
+                    job_io.job_started(&task, stdin_ready, interactive, show_stdout, show_stderr);
                 }
                 TaskJobMessage::Stdout { task, s } => {
-                    concurrent_io.print_stdout(&task, s);
+                    let s = secret_masker.push(&task, OutputStream::Stdout, s);
+                    job_logger.job_stdout(&task, s.as_str());
+                    job_io.print_stdout(&task, s);
                 }
                 TaskJobMessage::Stderr { task, s } => {
-                    concurrent_io.print_stderr(&task, s);
+                    let s = secret_masker.push(&task, OutputStream::Stderr, s);
+                    job_logger.job_stderr(&task, s.as_str());
+                    job_io.print_stderr(&task, s);
                 }
                 TaskJobMessage::Complete { task, result } => {
                     completed_jobs.insert(task.clone());
                     in_progress_jobs.remove(task.as_ref());
 
-                    concurrent_io.job_completed(&task, &result);
+                    // Output that was held back because it looked like the start of a secret
+                    // can be shown now that the job won't output any more
+                    let stdout_remainder = secret_masker.flush(&task, OutputStream::Stdout);
+                    if !stdout_remainder.is_empty() {
+                        job_logger.job_stdout(&task, stdout_remainder.as_str());
+                        job_io.print_stdout(&task, stdout_remainder);
+                    }
+                    let stderr_remainder = secret_masker.flush(&task, OutputStream::Stderr);
+                    if !stderr_remainder.is_empty() {
+                        job_logger.job_stderr(&task, stderr_remainder.as_str());
+                        job_io.print_stderr(&task, stderr_remainder);
+                    }
+
+                    let result = match result {
+                        TaskResult::Error(e) => TaskResult::Error(secret_masker.mask_error(e)),
+                        result => result,
+                    };
+
+                    job_logger.job_completed(&task, &result);
+                    job_io.job_completed(&task, &result);
 
                     if let TaskResult::Error(e) = result {
                         return Err(e);
@@ -755,3 +1131,116 @@ impl Drop for TaskExecutor {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{put_task_record, TaskInput, TaskOutput, TaskRecord};
+    use crate::query::find_reverse_dependencies;
+
+    /// A workspace where /b depends on /a, /c depends on /b, and /d depends on /a
+    fn test_workspace() -> Arc<Workspace> {
+        let task = |deps: Vec<&str>| -> Arc<Task> {
+            Arc::new(Task {
+                task_deps: deps.into_iter().map(|d| (Arc::<str>::from(d), Arc::<str>::from(d))).collect(),
+                ..Default::default()
+            })
+        };
+
+        Arc::new(Workspace {
+            tasks: HashMap::from([
+                (Arc::<str>::from("/a"), task(vec![])),
+                (Arc::<str>::from("/b"), task(vec!["/a"])),
+                (Arc::<str>::from("/c"), task(vec!["/b"])),
+                (Arc::<str>::from("/d"), task(vec!["/a"])),
+            ]),
+            ..Default::default()
+        })
+    }
+
+    fn forced_jobs(jobs: &HashMap<Arc<str>, ExecutorJob>) -> Vec<&str> {
+        let mut forced: Vec<&str> = jobs
+            .iter()
+            .filter(|(_, job)| matches!(job, ExecutorJob::Task(t) if t.force_run))
+            .map(|(id, _)| id.as_ref())
+            .collect();
+        forced.sort();
+        forced
+    }
+
+    #[test]
+    fn test_force_run_downstream_jobs() {
+        let workspace = test_workspace();
+
+        let mut jobs: HashMap<Arc<str>, ExecutorJob> = HashMap::new();
+        add_task_jobs(&Arc::<str>::from("/c"), &workspace, &mut jobs).unwrap();
+        add_task_jobs(&Arc::<str>::from("/d"), &workspace, &mut jobs).unwrap();
+
+        force_run_downstream_jobs(&mut jobs, &[Arc::<str>::from("/b")], &workspace).unwrap();
+
+        assert_eq!(forced_jobs(&jobs), vec!["/b", "/c"]);
+    }
+
+    #[test]
+    fn test_force_run_jobs() {
+        let workspace = test_workspace();
+
+        let mut jobs: HashMap<Arc<str>, ExecutorJob> = HashMap::new();
+        add_task_jobs(&Arc::<str>::from("/c"), &workspace, &mut jobs).unwrap();
+        add_task_jobs(&Arc::<str>::from("/d"), &workspace, &mut jobs).unwrap();
+
+        // Tasks that aren't in the job graph are ignored
+        force_run_jobs(&mut jobs, &[Arc::<str>::from("/b"), Arc::<str>::from("/x")]);
+
+        assert_eq!(forced_jobs(&jobs), vec!["/b"]);
+    }
+
+    #[test]
+    fn test_invalidate_tasks() {
+        let tmpdir = mktemp::Temp::new_dir().unwrap();
+        let config = Arc::new(
+            crate::config::parse_workspace_config("", tmpdir.join("cobble.toml").as_path()).unwrap(),
+        );
+        let executor = TaskExecutor::new(config, tmpdir.join(".cobble.db").as_path()).unwrap();
+        let workspace = test_workspace();
+
+        let record = TaskRecord {
+            input: TaskInput {
+                project_source_hashes: HashMap::new(),
+                file_hashes: HashMap::new(),
+                task_outputs: HashMap::new(),
+                vars: HashMap::new(),
+                env_vars: HashMap::new(),
+                secret_vars: HashMap::new(),
+                secret_var_salt: String::new(),
+                secret_var_hashes: HashMap::new(),
+            },
+            output: TaskOutput {
+                file_hashes: HashMap::new(),
+                task_output: serde_json::Value::Null,
+            },
+            duration_ms: None,
+        };
+        let put_records = || {
+            for task_name in ["/a", "/b", "/c", "/d"] {
+                put_task_record(&executor.db_env, executor.db, task_name, &record).unwrap();
+            }
+        };
+        let remaining_records = || -> Vec<&str> {
+            ["/a", "/b", "/c", "/d"]
+                .into_iter()
+                .filter(|t| get_task_record(&executor.db_env, executor.db, t).is_ok())
+                .collect()
+        };
+
+        put_records();
+        executor.invalidate_tasks([Arc::<str>::from("/b")].iter()).unwrap();
+        assert_eq!(remaining_records(), vec!["/a", "/c", "/d"]);
+
+        // With --rdeps, the tasks that depend on the invalidated tasks are invalidated too
+        put_records();
+        let rdeps = find_reverse_dependencies(&workspace, vec![Arc::<str>::from("/b")]);
+        executor.invalidate_tasks(rdeps.iter()).unwrap();
+        assert_eq!(remaining_records(), vec!["/a", "/d"]);
+    }
+}
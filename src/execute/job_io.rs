@@ -10,6 +10,25 @@ use std::{
 
 use crate::{config::TaskOutputCondition, execute::execute::TaskResult};
 
+/// Displays the output of running jobs
+pub trait JobIO {
+    /// `interactive` is true if the job waits for `stdin_ready` before reading from stdin
+    fn job_started(
+        &mut self,
+        job_id: &Arc<str>,
+        stdin_ready: Arc<(Mutex<bool>, Condvar)>,
+        interactive: bool,
+        show_stdout: TaskOutputCondition,
+        show_stderr: TaskOutputCondition,
+    );
+    fn print_stdout(&mut self, job_id: &Arc<str>, text: String);
+    fn print_stderr(&mut self, job_id: &Arc<str>, text: String);
+    fn job_completed(&mut self, job_id: &Arc<str>, task_result: &TaskResult);
+
+    /// Called periodically while waiting for output from jobs
+    fn tick(&mut self) {}
+}
+
 enum Output {
     Status(String),
     Stdout(String),
@@ -33,9 +52,19 @@ struct TrackedJob {
     failed: bool
 }
 
+fn print_out(to_stderr: bool, text: &str) {
+    if to_stderr {
+        eprint!("{}", text);
+    } else {
+        print!("{}", text);
+    }
+}
+
 pub struct ConcurrentIO {
     jobs: HashMap<Arc<str>, TrackedJob>,
     active_job: Option<Arc<str>>,
+    /// Print status lines and job stdout to stderr, to keep stdout free for other output
+    stdout_to_stderr: bool,
 }
 
 impl ConcurrentIO {
@@ -43,77 +72,20 @@ impl ConcurrentIO {
         ConcurrentIO {
             jobs: HashMap::new(),
             active_job: None,
+            stdout_to_stderr: false,
         }
     }
 
-    pub fn job_started(
-        &mut self,
-        job_id: &Arc<str>,
-        stdin_ready: Arc<(Mutex<bool>, Condvar)>,
-        show_stdout: TaskOutputCondition,
-        show_stderr: TaskOutputCondition,
-    ) {
-        self.jobs.insert(
-            job_id.clone(),
-            TrackedJob {
-                job_state: TrackedJobState::InProgress,
-                buffer: Vec::new(),
-                on_fail_buffer: Vec::new(),
-                stdin_ready,
-                show_stdout,
-                show_stderr,
-                failed: false
-            },
-        );
-        self.print_status(&job_id, format!("-----> {}\n", job_id));
-        self.update_active_job();
-    }
-
-    pub fn print_status(&mut self, job_id: &Arc<str>, text: String) {
-        let is_active = match &self.active_job {
-            Some(active_job_id) => active_job_id == job_id,
-            None => false,
-        };
-
-        let job_opt = self.jobs.get_mut(job_id);
-        if let Some(job) = job_opt {
-            if is_active {
-                print!("{}", text);
-            } else {
-                if let TrackedJobState::Complete = job.job_state {
-                    return;
-                }
-
-                job.buffer.push(Output::Status(text));
-            }
+    pub fn new_on_stderr() -> ConcurrentIO {
+        ConcurrentIO {
+            jobs: HashMap::new(),
+            active_job: None,
+            stdout_to_stderr: true,
         }
     }
 
-    pub fn print_stdout(&mut self, job_id: &Arc<str>, text: String) {
-        let is_active = match &self.active_job {
-            Some(active_job_id) => active_job_id == job_id,
-            None => false,
-        };
 
-        let job_opt = self.jobs.get_mut(job_id);
-        if let Some(job) = job_opt {
-            if is_active {
-                match job.show_stdout {
-                    TaskOutputCondition::Always => { print!("{}", text); }
-                    TaskOutputCondition::OnFail => { job.on_fail_buffer.push(Output::Stdout(text)); }
-                    TaskOutputCondition::Never => { /* Ignore */ }
-                }
-            } else {
-                if let TrackedJobState::Complete = job.job_state {
-                    return;
-                }
-
-                job.buffer.push(Output::Stdout(text));
-            }
-        }
-    }
-
-    pub fn print_stderr(&mut self, job_id: &Arc<str>, text: String) {
+    pub fn print_status(&mut self, job_id: &Arc<str>, text: String) {
         let is_active = match &self.active_job {
             Some(active_job_id) => active_job_id == job_id,
             None => false,
@@ -122,44 +94,14 @@ impl ConcurrentIO {
         let job_opt = self.jobs.get_mut(job_id);
         if let Some(job) = job_opt {
             if is_active {
-                match job.show_stdout {
-                    TaskOutputCondition::Always => { eprint!("{}", text); }
-                    TaskOutputCondition::OnFail => { job.on_fail_buffer.push(Output::Stderr(text)); }
-                    TaskOutputCondition::Never => { /* Ignore */}
-                } 
+                print_out(self.stdout_to_stderr, &text);
             } else {
                 if let TrackedJobState::Complete = job.job_state {
                     return;
                 }
 
-                job.buffer.push(Output::Stderr(text));
-            }
-        }
-    }
-
-    pub fn job_completed(&mut self, job_id: &Arc<str>, task_result: &TaskResult) {
-        match task_result {
-            TaskResult::UpToDate => {
-                self.print_status(&job_id, format!("[UPTD] {}\n", job_id));
-            }
-            TaskResult::Success => {
-                self.print_status(&job_id, format!("[ OK ] {}\n", job_id));
-            }
-            TaskResult::Error(e) => {
-                self.print_status(&job_id, format!("[FAIL] {}: {}\n", job_id, e));
-            }
-        }
-
-        let job_state_opt = self.jobs.get(job_id).map(|j| j.job_state);
-
-        if let Some(job_state) = job_state_opt {
-            if let TrackedJobState::Closed = job_state {
-                return;
+                job.buffer.push(Output::Status(text));
             }
-            let job = self.jobs.get_mut(job_id).unwrap();
-            job.job_state = TrackedJobState::Complete;
-            job.failed = match task_result { TaskResult::Error(_) => true, _ => false };
-            self.update_active_job();
         }
     }
 
@@ -211,11 +153,12 @@ impl ConcurrentIO {
     }
 
     fn flush_buffer(&mut self, key: &Arc<str>) {
+        let stdout_to_stderr = self.stdout_to_stderr;
         if let Some(job) = self.jobs.get_mut(key) {
             for output in job.buffer.drain(..) {
                 match output {
                     Output::Stdout(s) => match job.show_stdout {
-                        TaskOutputCondition::Always => { print!("{}", s); }
+                        TaskOutputCondition::Always => { print_out(stdout_to_stderr, &s); }
                         TaskOutputCondition::OnFail => { job.on_fail_buffer.push(Output::Stdout(s)); }
                         TaskOutputCondition::Never => { /* Ignore */}
                     }
@@ -225,16 +168,16 @@ impl ConcurrentIO {
                         TaskOutputCondition::Never => { /* Ignore */ }
                     }
                     Output::Status(s) => {
-                        print!("{}", s);
+                        print_out(stdout_to_stderr, &s);
                     }
                 }
             }
             if job.failed {
                 for output in job.on_fail_buffer.drain(..) {
                     match output {
-                        Output::Stdout(s) => { print!("{}", s); }
+                        Output::Stdout(s) => { print_out(stdout_to_stderr, &s); }
                         Output::Stderr(s) => { eprint!("{}", s); }
-                        Output::Status(s) => { print!("{}", s); }
+                        Output::Status(s) => { print_out(stdout_to_stderr, &s); }
                     }
                 }
             }
@@ -242,6 +185,106 @@ impl ConcurrentIO {
     }
 }
 
+impl JobIO for ConcurrentIO {
+    fn job_started(
+        &mut self,
+        job_id: &Arc<str>,
+        stdin_ready: Arc<(Mutex<bool>, Condvar)>,
+        _interactive: bool,
+        show_stdout: TaskOutputCondition,
+        show_stderr: TaskOutputCondition,
+    ) {
+        self.jobs.insert(
+            job_id.clone(),
+            TrackedJob {
+                job_state: TrackedJobState::InProgress,
+                buffer: Vec::new(),
+                on_fail_buffer: Vec::new(),
+                stdin_ready,
+                show_stdout,
+                show_stderr,
+                failed: false
+            },
+        );
+        self.print_status(&job_id, format!("-----> {}\n", job_id));
+        self.update_active_job();
+    }
+
+    fn print_stdout(&mut self, job_id: &Arc<str>, text: String) {
+        let is_active = match &self.active_job {
+            Some(active_job_id) => active_job_id == job_id,
+            None => false,
+        };
+
+        let job_opt = self.jobs.get_mut(job_id);
+        if let Some(job) = job_opt {
+            if is_active {
+                match job.show_stdout {
+                    TaskOutputCondition::Always => { print_out(self.stdout_to_stderr, &text); }
+                    TaskOutputCondition::OnFail => { job.on_fail_buffer.push(Output::Stdout(text)); }
+                    TaskOutputCondition::Never => { /* Ignore */ }
+                }
+            } else {
+                if let TrackedJobState::Complete = job.job_state {
+                    return;
+                }
+
+                job.buffer.push(Output::Stdout(text));
+            }
+        }
+    }
+
+    fn print_stderr(&mut self, job_id: &Arc<str>, text: String) {
+        let is_active = match &self.active_job {
+            Some(active_job_id) => active_job_id == job_id,
+            None => false,
+        };
+
+        let job_opt = self.jobs.get_mut(job_id);
+        if let Some(job) = job_opt {
+            if is_active {
+                match job.show_stdout {
+                    TaskOutputCondition::Always => { eprint!("{}", text); }
+                    TaskOutputCondition::OnFail => { job.on_fail_buffer.push(Output::Stderr(text)); }
+                    TaskOutputCondition::Never => { /* Ignore */}
+                } 
+            } else {
+                if let TrackedJobState::Complete = job.job_state {
+                    return;
+                }
+
+                job.buffer.push(Output::Stderr(text));
+            }
+        }
+    }
+
+    fn job_completed(&mut self, job_id: &Arc<str>, task_result: &TaskResult) {
+        match task_result {
+            TaskResult::UpToDate => {
+                self.print_status(&job_id, format!("[UPTD] {}\n", job_id));
+            }
+            TaskResult::Success => {
+                self.print_status(&job_id, format!("[ OK ] {}\n", job_id));
+            }
+            TaskResult::Error(e) => {
+                self.print_status(&job_id, format!("[FAIL] {}: {}\n", job_id, e));
+            }
+        }
+
+        let job_state_opt = self.jobs.get(job_id).map(|j| j.job_state);
+
+        if let Some(job_state) = job_state_opt {
+            if let TrackedJobState::Closed = job_state {
+                return;
+            }
+            let job = self.jobs.get_mut(job_id).unwrap();
+            job.job_state = TrackedJobState::Complete;
+            job.failed = match task_result { TaskResult::Error(_) => true, _ => false };
+            self.update_active_job();
+        }
+    }
+}
+
 impl Drop for ConcurrentIO {
     fn drop(&mut self) {
         for (_job_id, job) in self.jobs.iter_mut() {
@@ -0,0 +1,428 @@
+// Cobble Build Automation
+// Copyright (C) 2024 Jeremiah Darais
+//
+// This program is licensed under the GPLv3.0 license (https://github.com/jdarais/cobble/blob/main/COPYING)
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::execute::execute::TaskResult;
+use crate::util::time::UtcDateTime;
+
+pub const LOGS_DIR: &str = ".cobble/logs";
+const LOG_FILE_EXTENSION: &str = "log";
+/// Escaped job id components never contain a bare '%', so this can't collide with a job's dir
+const ROOT_JOB_LOG_DIR_NAME: &str = "%root";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogStream {
+    Status,
+    Stdout,
+    Stderr,
+}
+
+impl LogStream {
+    fn tag(&self) -> &'static str {
+        match self {
+            LogStream::Status => "---",
+            LogStream::Stdout => "out",
+            LogStream::Stderr => "err",
+        }
+    }
+
+    fn from_tag(tag: &str) -> Option<LogStream> {
+        match tag {
+            "---" => Some(LogStream::Status),
+            "out" => Some(LogStream::Stdout),
+            "err" => Some(LogStream::Stderr),
+            _ => None,
+        }
+    }
+}
+
+/// A single line of output recorded in a job log
+#[derive(Clone, Debug)]
+pub struct JobLogLine {
+    pub time: String,
+    pub stream: LogStream,
+    pub text: String,
+}
+
+/// Create an identifier for a run that sorts in chronological order
+pub fn new_run_id() -> Arc<str> {
+    UtcDateTime::now().to_sortable_string().into()
+}
+
+/// Percent-encode any characters in a job id component that aren't safe to use in a file name on
+/// every platform, so that distinct job ids always get distinct log directories
+fn escape_job_id_component(component: &str) -> String {
+    if component == "." || component == ".." {
+        return component.replace('.', "%2E");
+    }
+
+    let mut escaped = String::with_capacity(component.len());
+    for c in component.chars() {
+        if c.is_ascii_alphanumeric() || "-_.+=@,~".contains(c) {
+            escaped.push(c);
+        } else {
+            let mut utf8_buf = [0u8; 4];
+            for b in c.encode_utf8(&mut utf8_buf).bytes() {
+                escaped.push_str(format!("%{:02X}", b).as_str());
+            }
+        }
+    }
+    escaped
+}
+
+/// Returns the directory containing the logs of each run of a job.  Job ids are mapped to nested
+/// directories, so the logs for "/project/build" are stored in ".cobble/logs/project/build".
+pub fn job_log_dir(workspace_dir: &Path, job_id: &str) -> PathBuf {
+    let mut log_dir = workspace_dir.join(LOGS_DIR);
+    let mut has_components = false;
+    for component in job_id.split('/').filter(|c| !c.is_empty()) {
+        log_dir.push(escape_job_id_component(component));
+        has_components = true;
+    }
+
+    if !has_components {
+        log_dir.push(ROOT_JOB_LOG_DIR_NAME);
+    }
+
+    log_dir
+}
+
+pub fn job_log_path(workspace_dir: &Path, job_id: &str, run_id: &str) -> PathBuf {
+    job_log_dir(workspace_dir, job_id).join(format!("{}.{}", run_id, LOG_FILE_EXTENSION))
+}
+
+/// Returns the ids of the recorded runs of a job, oldest first
+pub fn list_job_log_runs(workspace_dir: &Path, job_id: &str) -> io::Result<Vec<String>> {
+    let log_dir = job_log_dir(workspace_dir, job_id);
+    if !log_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut run_ids: Vec<String> = Vec::new();
+    for entry_res in fs::read_dir(log_dir)? {
+        let entry = entry_res?;
+        let path = entry.path();
+        if !path.is_file() || path.extension().and_then(|e| e.to_str()) != Some(LOG_FILE_EXTENSION) {
+            continue;
+        }
+        if let Some(run_id) = path.file_stem().and_then(|s| s.to_str()) {
+            run_ids.push(run_id.to_owned());
+        }
+    }
+
+    run_ids.sort();
+    Ok(run_ids)
+}
+
+pub fn read_job_log(path: &Path) -> io::Result<Vec<JobLogLine>> {
+    let file = File::open(path)?;
+    let mut lines: Vec<JobLogLine> = Vec::new();
+    for line_res in BufReader::new(file).lines() {
+        let line = line_res?;
+        let parsed = line.split_once(" | ").and_then(|(prefix, text)| {
+            let (time, tag) = prefix.split_once(' ')?;
+            Some(JobLogLine {
+                time: time.to_owned(),
+                stream: LogStream::from_tag(tag)?,
+                text: text.to_owned(),
+            })
+        });
+        match parsed {
+            Some(log_line) => lines.push(log_line),
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Unrecognized line in log file {}: {}", path.display(), line),
+                ));
+            }
+        }
+    }
+    Ok(lines)
+}
+
+struct JobLog {
+    file: BufWriter<File>,
+    /// Whether the file was created for this run of the job, rather than appended to
+    created_file: bool,
+    stdout_line: String,
+    stderr_line: String,
+}
+
+impl JobLog {
+    fn write_line(&mut self, stream: LogStream, text: &str) -> io::Result<()> {
+        self.write_line_at(UtcDateTime::now().to_time_string().as_str(), stream, text)
+    }
+
+    fn write_line_at(&mut self, time: &str, stream: LogStream, text: &str) -> io::Result<()> {
+        writeln!(self.file, "{} {} | {}", time, stream.tag(), text)
+    }
+
+    /// Write any complete lines in the given output to the log, keeping partial lines buffered
+    /// until the rest of the line arrives
+    fn write_output(&mut self, stream: LogStream, text: &str) -> io::Result<()> {
+        let mut line_buf = match stream {
+            LogStream::Stderr => std::mem::take(&mut self.stderr_line),
+            _ => std::mem::take(&mut self.stdout_line),
+        };
+        line_buf.push_str(text);
+
+        let mut remaining = line_buf.as_str();
+        while let Some(newline_idx) = remaining.find('\n') {
+            let line = remaining[..newline_idx].trim_end_matches('\r');
+            self.write_line(stream, line)?;
+            remaining = &remaining[newline_idx + 1..];
+        }
+
+        let remaining = remaining.to_owned();
+        match stream {
+            LogStream::Stderr => self.stderr_line = remaining,
+            _ => self.stdout_line = remaining,
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let stdout_line = std::mem::take(&mut self.stdout_line);
+        if !stdout_line.is_empty() {
+            self.write_line(LogStream::Stdout, stdout_line.as_str())?;
+        }
+        let stderr_line = std::mem::take(&mut self.stderr_line);
+        if !stderr_line.is_empty() {
+            self.write_line(LogStream::Stderr, stderr_line.as_str())?;
+        }
+        self.file.flush()
+    }
+}
+
+/// The log of a started job.  Most jobs turn out to be up to date, so the log file isn't created
+/// until the job produces output or runs its actions.  Otherwise, runs where nothing happened
+/// would push the logs of earlier runs out of the retention limit.
+enum StartedJob {
+    /// The time the job started
+    Pending(String),
+    Open(JobLog),
+}
+
+/// Records the output of every job in a run to a log file per job, and removes logs of old runs
+pub struct JobLogger {
+    workspace_dir: PathBuf,
+    run_id: Arc<str>,
+    retention: usize,
+    jobs: HashMap<Arc<str>, StartedJob>,
+}
+
+impl JobLogger {
+    /// Create a logger for a run.  A retention of 0 disables logging.
+    pub fn new(workspace_dir: &Path, run_id: Arc<str>, retention: usize) -> JobLogger {
+        JobLogger {
+            workspace_dir: PathBuf::from(workspace_dir),
+            run_id,
+            retention,
+            jobs: HashMap::new(),
+        }
+    }
+
+    fn open_job_log(&self, job_id: &str, start_time: &str) -> io::Result<JobLog> {
+        let log_path = job_log_path(&self.workspace_dir, job_id, &self.run_id);
+        if let Some(log_dir) = log_path.parent() {
+            fs::create_dir_all(log_dir)?;
+        }
+
+        // A job may be started more than once in a run, (e.g. when it is used to calculate
+        // dependencies,) so append to the log if it already exists
+        let created_file = !log_path.exists();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&log_path)?;
+
+        self.remove_old_runs(job_id)?;
+
+        let mut job_log = JobLog {
+            file: BufWriter::new(file),
+            created_file,
+            stdout_line: String::new(),
+            stderr_line: String::new(),
+        };
+        job_log.write_line_at(start_time, LogStream::Status, format!("-----> {}", job_id).as_str())?;
+        Ok(job_log)
+    }
+
+    fn remove_old_runs(&self, job_id: &str) -> io::Result<()> {
+        let run_ids = list_job_log_runs(&self.workspace_dir, job_id)?;
+        if run_ids.len() > self.retention {
+            for run_id in run_ids[..(run_ids.len() - self.retention)].iter() {
+                fs::remove_file(job_log_path(&self.workspace_dir, job_id, run_id))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Run an operation on a job's log, creating the log file if it hasn't been yet.  Failing to
+    /// write a log shouldn't fail the build, so on error, print a warning and stop logging the job.
+    fn with_job_log<F>(&mut self, job_id: &Arc<str>, f: F)
+    where
+        F: FnOnce(&mut JobLog) -> io::Result<()>,
+    {
+        if let Some(StartedJob::Pending(start_time)) = self.jobs.get(job_id) {
+            match self.open_job_log(job_id, start_time.as_str()) {
+                Ok(job_log) => {
+                    self.jobs.insert(job_id.clone(), StartedJob::Open(job_log));
+                }
+                Err(e) => {
+                    eprintln!("WARNING: Unable to create log for {}: {}", job_id, e);
+                    self.jobs.remove(job_id);
+                    return;
+                }
+            }
+        }
+
+        let res = match self.jobs.get_mut(job_id) {
+            Some(StartedJob::Open(job_log)) => f(job_log),
+            _ => Ok(()),
+        };
+        if let Err(e) = res {
+            eprintln!("WARNING: Unable to write log for {}: {}", job_id, e);
+            self.jobs.remove(job_id);
+        }
+    }
+
+    pub fn job_started(&mut self, job_id: &Arc<str>) {
+        if self.retention == 0 {
+            return;
+        }
+
+        self.jobs.insert(
+            job_id.clone(),
+            StartedJob::Pending(UtcDateTime::now().to_time_string()),
+        );
+    }
+
+    pub fn job_stdout(&mut self, job_id: &Arc<str>, text: &str) {
+        self.with_job_log(job_id, |log| log.write_output(LogStream::Stdout, text));
+    }
+
+    pub fn job_stderr(&mut self, job_id: &Arc<str>, text: &str) {
+        self.with_job_log(job_id, |log| log.write_output(LogStream::Stderr, text));
+    }
+
+    pub fn job_completed(&mut self, job_id: &Arc<str>, task_result: &TaskResult) {
+        let status = match task_result {
+            TaskResult::UpToDate => {
+                // Only keep the log of an up-to-date job if it was already written to earlier in
+                // the run
+                if let Some(StartedJob::Open(job_log)) = self.jobs.remove(job_id) {
+                    if job_log.created_file {
+                        drop(job_log);
+                        let _ = fs::remove_file(job_log_path(&self.workspace_dir, job_id, &self.run_id));
+                    }
+                }
+                return;
+            }
+            TaskResult::Success => format!("[ OK ] {}", job_id),
+            TaskResult::Error(e) => format!("[FAIL] {}: {}", job_id, e),
+        };
+        self.with_job_log(job_id, |log| {
+            log.finish()?;
+            for status_line in status.lines() {
+                log.write_line(LogStream::Status, status_line)?;
+            }
+            log.file.flush()
+        });
+        self.jobs.remove(job_id);
+    }
+}
+
+impl Drop for JobLogger {
+    fn drop(&mut self) {
+        // Jobs may still be running if the run ended early due to a failure
+        for (_job_id, started_job) in self.jobs.iter_mut() {
+            if let StartedJob::Open(job_log) = started_job {
+                let _ = job_log.finish();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execute::execute::TaskExecutionError;
+
+    #[test]
+    fn test_job_log_dir() {
+        let ws_dir = Path::new("/ws");
+        assert_eq!(job_log_dir(ws_dir, "/proj/build"), PathBuf::from("/ws/.cobble/logs/proj/build"));
+        assert_eq!(job_log_dir(ws_dir, "/"), PathBuf::from("/ws/.cobble/logs/%root"));
+        assert_eq!(
+            job_log_dir(ws_dir, "clean:/proj/build"),
+            PathBuf::from("/ws/.cobble/logs/clean%3A/proj/build")
+        );
+        assert_eq!(job_log_dir(ws_dir, "/proj/../x"), PathBuf::from("/ws/.cobble/logs/proj/%2E%2E/x"));
+
+        // Job ids that differ only in characters that need escaping get different dirs
+        assert_ne!(job_log_dir(ws_dir, "clean:/proj"), job_log_dir(ws_dir, "clean_/proj"));
+        assert_ne!(job_log_dir(ws_dir, "/a:b"), job_log_dir(ws_dir, "/a%3Ab"));
+        assert_ne!(job_log_dir(ws_dir, "/"), job_log_dir(ws_dir, "/_root"));
+    }
+
+    #[test]
+    fn test_job_logger_up_to_date() {
+        let tmpdir = mktemp::Temp::new_dir().unwrap();
+        let job_id = Arc::<str>::from("/proj/test");
+
+        let mut logger = JobLogger::new(tmpdir.as_path(), "20240101-000000-000".into(), 1);
+        logger.job_started(&job_id);
+        logger.job_stdout(&job_id, "output\n");
+        logger.job_completed(&job_id, &TaskResult::Error(TaskExecutionError::ActionFailed(String::from("failed"))));
+
+        // Runs where the job is up to date don't replace the log of the failed run
+        for run_id in ["20240102-000000-000", "20240103-000000-000"] {
+            let mut logger = JobLogger::new(tmpdir.as_path(), run_id.into(), 1);
+            logger.job_started(&job_id);
+            logger.job_completed(&job_id, &TaskResult::UpToDate);
+        }
+
+        let runs = list_job_log_runs(tmpdir.as_path(), &job_id).unwrap();
+        assert_eq!(runs, vec!["20240101-000000-000"]);
+    }
+
+    #[test]
+    fn test_job_logger() {
+        let tmpdir = mktemp::Temp::new_dir().unwrap();
+        let job_id = Arc::<str>::from("/proj/test");
+
+        for run_id in ["20240101-000000-000", "20240102-000000-000", "20240103-000000-000"] {
+            let mut logger = JobLogger::new(tmpdir.as_path(), run_id.into(), 2);
+            logger.job_started(&job_id);
+            logger.job_stdout(&job_id, "first line\nsecond ");
+            logger.job_stderr(&job_id, "error line\n");
+            logger.job_stdout(&job_id, "line\n");
+            logger.job_completed(&job_id, &TaskResult::Success);
+        }
+
+        let runs = list_job_log_runs(tmpdir.as_path(), &job_id).unwrap();
+        assert_eq!(runs, vec!["20240102-000000-000", "20240103-000000-000"]);
+
+        let lines = read_job_log(&job_log_path(tmpdir.as_path(), &job_id, &runs[1])).unwrap();
+        let line_contents: Vec<(LogStream, &str)> =
+            lines.iter().map(|l| (l.stream, l.text.as_str())).collect();
+        assert_eq!(
+            line_contents,
+            vec![
+                (LogStream::Status, "-----> /proj/test"),
+                (LogStream::Stdout, "first line"),
+                (LogStream::Stderr, "error line"),
+                (LogStream::Stdout, "second line"),
+                (LogStream::Status, "[ OK ] /proj/test"),
+            ]
+        );
+    }
+}
@@ -4,11 +4,16 @@
 // This program is licensed under the GPLv3.0 license (https://github.com/jdarais/cobble/blob/main/COPYING)
 
 pub mod execute;
+pub mod job_log;
+pub mod status;
 
 mod action;
 mod clean_task_job;
 mod env_action_job;
 mod job_io;
+mod prefixed_io;
+mod secrets;
 mod task_job;
 mod tool_check_job;
+mod tui_io;
 mod worker;
@@ -0,0 +1,368 @@
+// Cobble Build Automation
+// Copyright (C) 2024 Jeremiah Darais
+//
+// This program is licensed under the GPLv3.0 license (https://github.com/jdarais/cobble/blob/main/COPYING)
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, IsTerminal, Write};
+use std::sync::{Arc, Condvar, Mutex};
+
+use crate::config::TaskOutputCondition;
+use crate::execute::execute::TaskResult;
+use crate::execute::job_io::JobIO;
+
+// ANSI foreground colors used to tell jobs apart.  Red is left out so that it isn't confused with errors.
+const PREFIX_COLORS: [&str; 6] = ["36", "33", "32", "35", "34", "96"];
+const RESET_COLOR: &str = "\x1b[0m";
+
+type StdinReady = Arc<(Mutex<bool>, Condvar)>;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Stream {
+    Stdout,
+    Stderr,
+}
+
+struct PrefixedJob {
+    prefix: String,
+    show_stdout: TaskOutputCondition,
+    show_stderr: TaskOutputCondition,
+    stdout_line: String,
+    stderr_line: String,
+    on_fail_buffer: Vec<(Stream, String)>,
+}
+
+/// Displays the output of all running jobs as soon as each line is available, with each line
+/// prefixed by the name of the job it came from
+pub struct PrefixedIO {
+    jobs: HashMap<Arc<str>, PrefixedJob>,
+    prefix_width: usize,
+    use_color: bool,
+    next_color: usize,
+    /// Interactive jobs, in the order they started.  The job at the front holds stdin.
+    stdin_queue: VecDeque<(Arc<str>, StdinReady)>,
+    /// A partial line of output that has been printed without a trailing newline, so that a
+    /// prompt from the job holding stdin is visible while it waits for input
+    open_line: Option<(Arc<str>, Stream)>,
+}
+
+impl PrefixedIO {
+    /// Create a PrefixedIO that aligns output for the given jobs
+    pub fn new<'a, I>(job_ids: I) -> PrefixedIO
+    where
+        I: Iterator<Item = &'a Arc<str>>,
+    {
+        let use_color = io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+        PrefixedIO {
+            jobs: HashMap::new(),
+            prefix_width: job_ids.map(|id| id.chars().count()).max().unwrap_or(0),
+            use_color,
+            next_color: 0,
+            stdin_queue: VecDeque::new(),
+            open_line: None,
+        }
+    }
+
+    fn format_prefix(&mut self, job_id: &str) -> String {
+        let padded_job_id = format!("{:width$} |", job_id, width = self.prefix_width);
+        if self.use_color {
+            let color = PREFIX_COLORS[self.next_color % PREFIX_COLORS.len()];
+            self.next_color += 1;
+            format!("\x1b[{}m{}{}", color, padded_job_id, RESET_COLOR)
+        } else {
+            padded_job_id
+        }
+    }
+
+    /// Only one interactive job can read from stdin at a time, so give it to interactive jobs in
+    /// the order they started
+    fn update_stdin_job(&mut self) {
+        if let Some((_job_id, stdin_ready)) = self.stdin_queue.front() {
+            set_stdin_ready(stdin_ready);
+        }
+    }
+
+    fn is_stdin_job(&self, job_id: &Arc<str>) -> bool {
+        self.stdin_queue
+            .front()
+            .map(|(stdin_job_id, _)| stdin_job_id == job_id)
+            .unwrap_or(false)
+    }
+
+    /// Finish a partial line printed by print_output, so that other output starts on a new line
+    fn end_open_line(&mut self) {
+        if let Some((_job_id, stream)) = self.open_line.take() {
+            print_text(stream, "\n");
+        }
+    }
+
+    fn print_output(&mut self, job_id: &Arc<str>, stream: Stream, text: String) {
+        let is_stdin_job = self.is_stdin_job(job_id);
+        let continues_open_line = self
+            .open_line
+            .as_ref()
+            .map(|(open_job_id, open_stream)| open_job_id == job_id && *open_stream == stream)
+            .unwrap_or(false);
+
+        let job = match self.jobs.get_mut(job_id) {
+            Some(job) => job,
+            None => return,
+        };
+        let (line_buf, show) = match stream {
+            Stream::Stdout => (&mut job.stdout_line, &job.show_stdout),
+            Stream::Stderr => (&mut job.stderr_line, &job.show_stderr),
+        };
+
+        match show {
+            TaskOutputCondition::Never => {
+                return;
+            }
+            TaskOutputCondition::OnFail => {
+                job.on_fail_buffer.push((stream, text));
+                return;
+            }
+            TaskOutputCondition::Always => { /* Print below */ }
+        }
+
+        line_buf.push_str(text.as_str());
+        let lines = take_complete_lines(line_buf);
+        // Show a partial line right away if the job may be waiting for input, (e.g. a prompt)
+        let partial_line = if is_stdin_job && !line_buf.is_empty() {
+            Some(std::mem::take(line_buf))
+        } else {
+            None
+        };
+        if lines.is_empty() && partial_line.is_none() {
+            return;
+        }
+
+        let prefix = job.prefix.clone();
+        if !continues_open_line {
+            self.end_open_line();
+        }
+        self.open_line = None;
+
+        for (i, line) in lines.iter().enumerate() {
+            if i == 0 && continues_open_line {
+                print_text(stream, format!("{}\n", line).as_str());
+            } else {
+                print_line(&prefix, stream, line.as_str());
+            }
+        }
+
+        if let Some(partial_line) = partial_line {
+            if continues_open_line && lines.is_empty() {
+                print_text(stream, partial_line.as_str());
+            } else {
+                print_text(stream, format!("{} {}", prefix, partial_line).as_str());
+            }
+            self.open_line = Some((job_id.clone(), stream));
+        }
+    }
+}
+
+fn set_stdin_ready(stdin_ready: &StdinReady) {
+    let (ready_lock, ready_condvar) = &**stdin_ready;
+    *ready_lock.lock().unwrap() = true;
+    ready_condvar.notify_all();
+}
+
+/// Removes and returns the complete lines from the start of a buffer, leaving any partial line
+fn take_complete_lines(line_buf: &mut String) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    while let Some(newline_idx) = line_buf.find('\n') {
+        let line: String = line_buf.drain(..=newline_idx).collect();
+        lines.push(String::from(line.trim_end_matches(['\n', '\r'])));
+    }
+    lines
+}
+
+/// Print text as-is, flushing it so that partial lines are displayed
+fn print_text(stream: Stream, text: &str) {
+    match stream {
+        Stream::Stdout => {
+            let mut stdout = io::stdout().lock();
+            let _ = write!(stdout, "{}", text);
+            let _ = stdout.flush();
+        }
+        Stream::Stderr => {
+            let mut stderr = io::stderr().lock();
+            let _ = write!(stderr, "{}", text);
+            let _ = stderr.flush();
+        }
+    }
+}
+
+fn print_line(prefix: &str, stream: Stream, line: &str) {
+    match stream {
+        Stream::Stdout => {
+            let mut stdout = io::stdout().lock();
+            let _ = writeln!(stdout, "{} {}", prefix, line);
+        }
+        Stream::Stderr => {
+            let mut stderr = io::stderr().lock();
+            let _ = writeln!(stderr, "{} {}", prefix, line);
+        }
+    }
+}
+
+impl JobIO for PrefixedIO {
+    fn job_started(
+        &mut self,
+        job_id: &Arc<str>,
+        stdin_ready: Arc<(Mutex<bool>, Condvar)>,
+        interactive: bool,
+        show_stdout: TaskOutputCondition,
+        show_stderr: TaskOutputCondition,
+    ) {
+        self.end_open_line();
+        let prefix = self.format_prefix(job_id);
+        print_line(
+            &prefix,
+            Stream::Stdout,
+            format!("-----> {}", job_id).as_str(),
+        );
+        self.jobs.insert(
+            job_id.clone(),
+            PrefixedJob {
+                prefix,
+                show_stdout,
+                show_stderr,
+                stdout_line: String::new(),
+                stderr_line: String::new(),
+                on_fail_buffer: Vec::new(),
+            },
+        );
+
+        // Jobs that aren't interactive don't read from stdin, so they don't need to wait for it
+        if interactive {
+            self.stdin_queue.push_back((job_id.clone(), stdin_ready));
+            self.update_stdin_job();
+        } else {
+            set_stdin_ready(&stdin_ready);
+        }
+    }
+
+    fn print_stdout(&mut self, job_id: &Arc<str>, text: String) {
+        self.print_output(job_id, Stream::Stdout, text);
+    }
+
+    fn print_stderr(&mut self, job_id: &Arc<str>, text: String) {
+        self.print_output(job_id, Stream::Stderr, text);
+    }
+
+    fn job_completed(&mut self, job_id: &Arc<str>, task_result: &TaskResult) {
+        self.end_open_line();
+        if let Some(mut job) = self.jobs.remove(job_id) {
+            if let TaskResult::Error(_) = task_result {
+                for (stream, text) in job.on_fail_buffer.drain(..) {
+                    let line_buf = match stream {
+                        Stream::Stdout => &mut job.stdout_line,
+                        Stream::Stderr => &mut job.stderr_line,
+                    };
+                    line_buf.push_str(text.as_str());
+                    for line in take_complete_lines(line_buf) {
+                        print_line(&job.prefix, stream, line.as_str());
+                    }
+                }
+            }
+
+            for (stream, mut line_buf) in [
+                (Stream::Stdout, job.stdout_line),
+                (Stream::Stderr, job.stderr_line),
+            ] {
+                if !line_buf.is_empty() {
+                    line_buf.push('\n');
+                }
+                for line in take_complete_lines(&mut line_buf) {
+                    print_line(&job.prefix, stream, line.as_str());
+                }
+            }
+
+            let status = match task_result {
+                TaskResult::UpToDate => format!("[UPTD] {}", job_id),
+                TaskResult::Success => format!("[ OK ] {}", job_id),
+                TaskResult::Error(e) => format!("[FAIL] {}: {}", job_id, e),
+            };
+            for status_line in status.lines() {
+                print_line(&job.prefix, Stream::Stdout, status_line);
+            }
+        }
+
+        let is_stdin_job = self.is_stdin_job(job_id);
+        self.stdin_queue
+            .retain(|(stdin_job_id, _)| stdin_job_id != job_id);
+        if is_stdin_job {
+            self.update_stdin_job();
+
+            // Show any prompt the next job printed while it was waiting for stdin
+            if let Some((next_job_id, _)) = self.stdin_queue.front().cloned() {
+                self.print_output(&next_job_id, Stream::Stdout, String::new());
+                self.print_output(&next_job_id, Stream::Stderr, String::new());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_complete_lines() {
+        let mut line_buf = String::from("first\r\nsecond\nthird");
+        assert_eq!(take_complete_lines(&mut line_buf), vec!["first", "second"]);
+        assert_eq!(line_buf, "third");
+
+        line_buf.push_str(" line\n");
+        assert_eq!(take_complete_lines(&mut line_buf), vec!["third line"]);
+        assert!(line_buf.is_empty());
+    }
+
+    #[test]
+    fn test_stdin_goes_to_interactive_jobs() {
+        let job_ids: Vec<Arc<str>> = vec!["build".into(), "login".into(), "deploy".into()];
+        let mut io = PrefixedIO::new(job_ids.iter());
+        let new_stdin_ready = || -> StdinReady { Arc::new((Mutex::new(false), Condvar::new())) };
+        let is_ready = |stdin_ready: &StdinReady| *stdin_ready.0.lock().unwrap();
+
+        let build_ready = new_stdin_ready();
+        io.job_started(
+            &job_ids[0],
+            build_ready.clone(),
+            false,
+            TaskOutputCondition::Never,
+            TaskOutputCondition::Never,
+        );
+        let login_ready = new_stdin_ready();
+        io.job_started(
+            &job_ids[1],
+            login_ready.clone(),
+            true,
+            TaskOutputCondition::Always,
+            TaskOutputCondition::Always,
+        );
+        let deploy_ready = new_stdin_ready();
+        io.job_started(
+            &job_ids[2],
+            deploy_ready.clone(),
+            true,
+            TaskOutputCondition::Never,
+            TaskOutputCondition::Never,
+        );
+
+        // An interactive job doesn't wait for jobs that don't read from stdin
+        assert!(is_ready(&build_ready));
+        assert!(is_ready(&login_ready));
+        assert!(!is_ready(&deploy_ready));
+
+        // A prompt from the job holding stdin is shown without waiting for the end of the line
+        io.print_stdout(&job_ids[1], String::from("Password: "));
+        assert!(io.jobs[&job_ids[1]].stdout_line.is_empty());
+        assert!(io.open_line.is_some());
+
+        io.job_completed(&job_ids[1], &TaskResult::Success);
+        assert!(io.open_line.is_none());
+        assert!(is_ready(&deploy_ready));
+    }
+}
@@ -0,0 +1,230 @@
+// Cobble Build Automation
+// Copyright (C) 2024 Jeremiah Darais
+//
+// This program is licensed under the GPLv3.0 license (https://github.com/jdarais/cobble/blob/main/COPYING)
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::io;
+use std::sync::Arc;
+
+use crate::config::WorkspaceConfig;
+use crate::execute::execute::TaskExecutionError;
+use crate::project_def::types::TaskVar;
+use crate::util::hash::compute_hash_string;
+use crate::vars::get_var;
+
+const SECRET_MASK: &str = "****";
+
+// Masking a short number would also mask it everywhere else it shows up in the output, like in
+// line numbers and timings
+const MIN_MASKED_NUMBER_LEN: usize = 6;
+
+/// Create a salt to hash secret values with, so that the stored hash can't be compared against
+/// hashes of guessed values computed ahead of time
+pub fn new_salt() -> Result<String, io::Error> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).map_err(io::Error::from)?;
+
+    let mut salt = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        write!(&mut salt, "{:02x}", b).map_err(io::Error::other)?;
+    }
+    Ok(salt)
+}
+
+pub fn hash_secret(salt: &str, value: &TaskVar) -> String {
+    let mut data = String::from(salt);
+    data.push_str(serde_json::to_string(value).unwrap_or_default().as_str());
+    compute_hash_string(data.as_bytes()).expect("writing to a String should not fail")
+}
+
+/// Collect the strings to mask for a secret value.  Returns false if any part of the value is
+/// too short to be masked.
+fn collect_secret_strings(value: &TaskVar, secrets: &mut Vec<String>) -> bool {
+    match value {
+        TaskVar::String(s) => {
+            if !s.is_empty() {
+                secrets.push(s.clone());
+            }
+            true
+        }
+        TaskVar::Int(_) | TaskVar::Number(_) => {
+            let s = value.to_string();
+            if s.len() < MIN_MASKED_NUMBER_LEN {
+                return false;
+            }
+            secrets.push(s);
+            true
+        }
+        TaskVar::Bool(_) => true,
+        TaskVar::List(l) => {
+            let mut masked = true;
+            for v in l {
+                masked &= collect_secret_strings(v, secrets);
+            }
+            masked
+        }
+        TaskVar::Table(t) => {
+            let mut masked = true;
+            for v in t.values() {
+                masked &= collect_secret_strings(v, secrets);
+            }
+            masked
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// Replaces the values of secret variables in job output.  Output arrives in arbitrary chunks, so
+/// the end of a chunk that could be the start of a secret is held back until the next chunk.
+pub struct SecretMasker {
+    secrets: Vec<String>,
+    pending: HashMap<(Arc<str>, OutputStream), String>,
+}
+
+impl SecretMasker {
+    pub fn new(secrets: Vec<String>) -> SecretMasker {
+        let mut secrets = secrets;
+        secrets.sort();
+        secrets.dedup();
+        // Mask longer secrets first, in case one secret contains another
+        secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+        SecretMasker {
+            secrets,
+            pending: HashMap::new(),
+        }
+    }
+
+    pub fn from_workspace_config(config: &WorkspaceConfig) -> SecretMasker {
+        let mut secrets: Vec<String> = Vec::new();
+        for var_name in config.secret_vars.iter() {
+            if let Ok(value) = get_var(var_name, &config.vars) {
+                if !collect_secret_strings(value, &mut secrets) {
+                    eprintln!(
+                        "WARNING: Secret var {} contains a number that is too short to mask in output",
+                        var_name
+                    );
+                }
+            }
+        }
+        SecretMasker::new(secrets)
+    }
+
+    pub fn mask(&self, text: &str) -> String {
+        let mut masked = String::from(text);
+        for secret in self.secrets.iter() {
+            if masked.contains(secret.as_str()) {
+                masked = masked.replace(secret.as_str(), SECRET_MASK);
+            }
+        }
+        masked
+    }
+
+    pub fn mask_error(&self, error: TaskExecutionError) -> TaskExecutionError {
+        if self.secrets.is_empty() {
+            return error;
+        }
+
+        let message = error.to_string();
+        let masked_message = self.mask(message.as_str());
+        if masked_message == message {
+            error
+        } else {
+            TaskExecutionError::MaskedError(masked_message)
+        }
+    }
+
+    /// Mask a chunk of output, returning the part of it that is safe to display
+    pub fn push(&mut self, job_id: &Arc<str>, stream: OutputStream, text: String) -> String {
+        if self.secrets.is_empty() {
+            return text;
+        }
+
+        let key = (job_id.clone(), stream);
+        let mut buf = self.pending.remove(&key).unwrap_or_default();
+        buf.push_str(text.as_str());
+        let mut masked = self.mask(buf.as_str());
+
+        let hold_len = self.partial_secret_suffix_len(masked.as_str());
+        if hold_len > 0 {
+            let held = masked.split_off(masked.len() - hold_len);
+            self.pending.insert(key, held);
+        }
+        masked
+    }
+
+    /// Return any output held back for a job stream
+    pub fn flush(&mut self, job_id: &Arc<str>, stream: OutputStream) -> String {
+        self.pending
+            .remove(&(job_id.clone(), stream))
+            .unwrap_or_default()
+    }
+
+    /// The length of the longest suffix of the text that is the start of a secret
+    fn partial_secret_suffix_len(&self, text: &str) -> usize {
+        let mut longest = 0;
+        for secret in self.secrets.iter() {
+            let max_len = (secret.len() - 1).min(text.len());
+            for len in (longest + 1..=max_len).rev() {
+                let start = text.len() - len;
+                if text.is_char_boundary(start) && secret.starts_with(&text[start..]) {
+                    longest = len;
+                    break;
+                }
+            }
+        }
+        longest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_masker() {
+        let mut masker = SecretMasker::new(vec![String::from("hunter2"), String::from("abc")]);
+        let job_id = Arc::<str>::from("/proj/deploy");
+
+        assert_eq!(masker.mask("password: hunter2, abc"), "password: ****, ****");
+
+        // A secret split across chunks is still masked
+        assert_eq!(masker.push(&job_id, OutputStream::Stdout, String::from("token=hun")), "token=");
+        assert_eq!(masker.push(&job_id, OutputStream::Stdout, String::from("ter2\nok a")), "****\nok ");
+        assert_eq!(masker.flush(&job_id, OutputStream::Stdout), "a");
+        assert_eq!(masker.flush(&job_id, OutputStream::Stdout), "");
+    }
+
+    #[test]
+    fn test_secret_masker_numbers() {
+        let mut config = crate::config::parse_workspace_config(
+            r#"
+            [secret_vars]
+            pin = true
+            key = true
+            "#,
+            std::path::Path::new("/home/test/proj/cobble.toml"),
+        )
+        .unwrap();
+        config.vars.insert(String::from("pin"), TaskVar::Int(1234));
+        config.vars.insert(String::from("key"), TaskVar::Int(98765432));
+        let masker = SecretMasker::from_workspace_config(&config);
+
+        assert_eq!(masker.mask("key: 98765432, line 1234"), "key: ****, line 1234");
+    }
+
+    #[test]
+    fn test_hash_secret() {
+        let value = TaskVar::String(String::from("hunter2"));
+        let hash = hash_secret("salt1", &value);
+        assert_eq!(hash, hash_secret("salt1", &value));
+        assert_ne!(hash, hash_secret("salt2", &value));
+        assert!(!hash.contains("hunter2"));
+    }
+}
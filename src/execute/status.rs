@@ -0,0 +1,292 @@
+// Cobble Build Automation
+// Copyright (C) 2024 Jeremiah Darais
+//
+// This program is licensed under the GPLv3.0 license (https://github.com/jdarais/cobble/blob/main/COPYING)
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::config::WorkspaceConfig;
+use crate::db::{get_task_record, GetError};
+use crate::execute::execute::{get_task_job_dependencies, TaskExecutionError, TaskExecutorCache};
+use crate::execute::task_job::{check_task_record, get_current_task_input};
+use crate::workspace::Workspace;
+
+/// Why a task would be run if it were selected.  Where known, the alias or path of the input that
+/// changed is included.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StaleReason {
+    AlwaysRun,
+    ProjectSourceChanged(Option<String>),
+    FileDependencyChanged(Option<String>),
+    TaskOutputChanged(Option<String>),
+    VarChanged(Option<String>),
+    EnvVarsChanged,
+    ArtifactChanged(Option<String>),
+}
+
+impl fmt::Display for StaleReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use StaleReason::*;
+
+        let write_changed = |f: &mut fmt::Formatter<'_>, what: &str, name: &Option<String>| {
+            match name {
+                Some(name) => write!(f, "{} {} changed", what, name),
+                None => write!(f, "{}s changed", what),
+            }
+        };
+
+        match self {
+            AlwaysRun => f.write_str("task is set to always run"),
+            ProjectSourceChanged(name) => write_changed(f, "project file", name),
+            FileDependencyChanged(name) => write_changed(f, "file dependency", name),
+            TaskOutputChanged(name) => write_changed(f, "output of dependency", name),
+            VarChanged(name) => write_changed(f, "variable", name),
+            EnvVarsChanged => f.write_str("environment variables changed"),
+            ArtifactChanged(Some(name)) => write!(f, "artifact {} is missing or changed", name),
+            ArtifactChanged(None) => f.write_str("artifacts changed"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TaskStatus {
+    UpToDate,
+    Stale(StaleReason),
+    /// The task's own inputs match its last run, but the given dependency isn't up to date.  The
+    /// task will be run if the dependency's output changes when it is run, which can't be known
+    /// without running it.
+    MaybeStale(Arc<str>),
+    NeverRun,
+    /// The task has calculated dependencies or artifacts that haven't been resolved, so its status
+    /// can't be determined without running the calc tasks
+    Unresolved,
+}
+
+impl fmt::Display for TaskStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaskStatus::UpToDate => f.write_str("up to date"),
+            TaskStatus::Stale(_) => f.write_str("stale"),
+            TaskStatus::MaybeStale(_) => f.write_str("maybe stale"),
+            TaskStatus::NeverRun => f.write_str("never run"),
+            TaskStatus::Unresolved => f.write_str("unresolved"),
+        }
+    }
+}
+
+struct StatusContext<'a> {
+    workspace_config: &'a WorkspaceConfig,
+    workspace: &'a Workspace,
+    artifacts_calculated: bool,
+    db_env: &'a lmdb::Environment,
+    db: &'a lmdb::Database,
+    cache: &'a Arc<TaskExecutorCache>,
+}
+
+/// Determine whether each task in the dependency graphs of the given tasks would be run, without
+/// running anything.  Uses the same comparison that the executor uses to skip up-to-date tasks.
+/// `artifacts_calculated` indicates whether calculated artifacts have been added to the tasks in
+/// the workspace.
+pub fn compute_task_statuses<'a, T>(
+    workspace_config: &WorkspaceConfig,
+    workspace: &Workspace,
+    tasks: T,
+    artifacts_calculated: bool,
+    db_env: &lmdb::Environment,
+    db: &lmdb::Database,
+    cache: &Arc<TaskExecutorCache>,
+) -> Result<HashMap<Arc<str>, TaskStatus>, TaskExecutionError>
+where
+    T: Iterator<Item = &'a Arc<str>>,
+{
+    let ctx = StatusContext {
+        workspace_config,
+        workspace,
+        artifacts_calculated,
+        db_env,
+        db,
+        cache,
+    };
+
+    let mut statuses: HashMap<Arc<str>, TaskStatus> = HashMap::new();
+    for task_name in tasks {
+        compute_task_status(&ctx, task_name, &mut statuses)?;
+    }
+
+    Ok(statuses)
+}
+
+fn compute_task_status(
+    ctx: &StatusContext,
+    task_name: &Arc<str>,
+    statuses: &mut HashMap<Arc<str>, TaskStatus>,
+) -> Result<TaskStatus, TaskExecutionError> {
+    if let Some(status) = statuses.get(task_name) {
+        return Ok(status.clone());
+    }
+
+    let task = ctx
+        .workspace
+        .tasks
+        .get(task_name)
+        .ok_or_else(|| TaskExecutionError::TaskLookupError(task_name.clone()))?;
+
+    let mut deps = get_task_job_dependencies(task, ctx.workspace)?;
+    deps.sort();
+
+    let mut dep_not_up_to_date: Option<Arc<str>> = None;
+    for dep in deps.iter() {
+        let dep_status = compute_task_status(ctx, dep, statuses)?;
+        if dep_status != TaskStatus::UpToDate && dep_not_up_to_date.is_none() {
+            dep_not_up_to_date = Some(dep.clone());
+        }
+    }
+
+    let has_unresolved_calc = !task.calc_deps.is_empty()
+        || (!ctx.artifacts_calculated && !task.artifacts.calc.is_empty());
+
+    let status = if has_unresolved_calc {
+        TaskStatus::Unresolved
+    } else {
+        match get_task_record(ctx.db_env, *ctx.db, task_name) {
+            Err(GetError::NotFound(_)) => TaskStatus::NeverRun,
+            Err(e) => return Err(TaskExecutionError::DBGetError(e)),
+            Ok(_) if task.always_run => TaskStatus::Stale(StaleReason::AlwaysRun),
+            Ok(task_record) => match get_current_task_input(
+                ctx.workspace_config,
+                task,
+                ctx.db_env,
+                ctx.db,
+                ctx.cache,
+            ) {
+                Ok(current_task_input) => match (
+                    check_task_record(
+                        ctx.workspace_config.workspace_dir.as_path(),
+                        task,
+                        &task_record,
+                        &current_task_input,
+                    ),
+                    dep_not_up_to_date,
+                ) {
+                    (Err(reason), _) => TaskStatus::Stale(reason),
+                    (Ok(()), Some(dep)) => TaskStatus::MaybeStale(dep),
+                    (Ok(()), None) => TaskStatus::UpToDate,
+                },
+                // A dependency that has never run has no output to compare against yet
+                Err(TaskExecutionError::DBGetError(GetError::NotFound(_)))
+                    if dep_not_up_to_date.is_some() =>
+                {
+                    TaskStatus::MaybeStale(dep_not_up_to_date.unwrap())
+                }
+                Err(e) => return Err(e),
+            },
+        }
+    };
+
+    statuses.insert(task_name.clone(), status.clone());
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_workspace_config;
+    use crate::db::{delete_task_record, new_db_env, put_task_record, TaskOutput, TaskRecord};
+    use crate::execute::execute::TaskExecutorCache;
+    use crate::workspace::Task;
+    use std::sync::RwLock;
+
+    fn new_cache() -> Arc<TaskExecutorCache> {
+        Arc::new(TaskExecutorCache {
+            project_source_hashes: RwLock::new(HashMap::new()),
+            file_hashes: RwLock::new(HashMap::new()),
+            task_outputs: RwLock::new(HashMap::new()),
+        })
+    }
+
+    fn config_with_mode(mode: &str) -> WorkspaceConfig {
+        parse_workspace_config(
+            format!("[vars]\nmode = \"{}\"\n", mode).as_str(),
+            std::path::Path::new("cobble.toml"),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_compute_task_statuses() {
+        let tmpdir = mktemp::Temp::new_dir().unwrap();
+        let db_env = new_db_env(tmpdir.join(".cobble.db").as_path()).unwrap();
+        let db = db_env.open_db(None).unwrap();
+
+        // /b depends on /a, and only /a depends on the mode var
+        let task_a = Arc::new(Task {
+            name: Arc::<str>::from("/a"),
+            var_deps: HashMap::from([(Arc::<str>::from("mode"), Arc::<str>::from("mode"))]),
+            ..Default::default()
+        });
+        let task_b = Arc::new(Task {
+            name: Arc::<str>::from("/b"),
+            task_deps: HashMap::from([(Arc::<str>::from("a"), Arc::<str>::from("/a"))]),
+            ..Default::default()
+        });
+        let workspace = Workspace {
+            tasks: HashMap::from([
+                (task_a.name.clone(), task_a.clone()),
+                (task_b.name.clone(), task_b.clone()),
+            ]),
+            ..Default::default()
+        };
+
+        let statuses = |config: &WorkspaceConfig| {
+            let statuses = compute_task_statuses(
+                config,
+                &workspace,
+                [task_b.name.clone()].iter(),
+                true,
+                &db_env,
+                &db,
+                &new_cache(),
+            )
+            .unwrap();
+            (statuses["/a"].clone(), statuses["/b"].clone())
+        };
+
+        let debug_config = config_with_mode("debug");
+        assert_eq!(statuses(&debug_config), (TaskStatus::NeverRun, TaskStatus::NeverRun));
+
+        // Record both tasks as run with the current inputs
+        for task in [&task_a, &task_b] {
+            let input =
+                get_current_task_input(&debug_config, task, &db_env, &db, &new_cache()).unwrap();
+            let record = TaskRecord {
+                input,
+                output: TaskOutput {
+                    file_hashes: HashMap::new(),
+                    task_output: serde_json::Value::Null,
+                },
+                duration_ms: None,
+            };
+            put_task_record(&db_env, db, task.name.as_ref(), &record).unwrap();
+        }
+        assert_eq!(statuses(&debug_config), (TaskStatus::UpToDate, TaskStatus::UpToDate));
+
+        // /a's own input changed, but /b's inputs only change if /a's output does
+        let release_config = config_with_mode("release");
+        assert_eq!(
+            statuses(&release_config),
+            (
+                TaskStatus::Stale(StaleReason::VarChanged(Some(String::from("mode")))),
+                TaskStatus::MaybeStale(Arc::<str>::from("/a"))
+            )
+        );
+
+        // A dependency without a record has no output to compare against
+        delete_task_record(&db_env, db, "/a").unwrap();
+        assert_eq!(
+            statuses(&debug_config),
+            (TaskStatus::NeverRun, TaskStatus::MaybeStale(Arc::<str>::from("/a")))
+        );
+    }
+}
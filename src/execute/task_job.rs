@@ -7,7 +7,7 @@ use std::collections::HashMap;
 use std::path::Path;
 use std::sync::mpsc::Sender;
 use std::sync::{Arc, Condvar, Mutex};
-use std::time::SystemTime;
+use std::time::{Instant, SystemTime};
 
 use crate::config::WorkspaceConfig;
 use crate::db::{
@@ -17,10 +17,12 @@ use crate::execute::action::{create_task_action_context, invoke_action_protected
 use crate::execute::execute::{
     TaskExecutionError, TaskExecutorCache, TaskJob, TaskJobMessage, TaskResult,
 };
+use crate::execute::secrets::{hash_secret, new_salt};
+use crate::execute::status::StaleReason;
 use crate::lua::detached::DetachedLuaValue;
 use crate::lua::lua_env::COBBLE_JOB_INTERACTIVE_ENABLED;
 use crate::util::hash::compute_file_hash;
-use crate::vars::get_var;
+use crate::vars::{get_var, is_secret_var};
 use crate::workspace::{Task, Workspace};
 
 fn execute_task_actions<'lua>(
@@ -55,7 +57,17 @@ fn execute_task_actions<'lua>(
     Ok(args)
 }
 
-fn get_current_task_input(
+/// Returns the environment variables set for every command run by a task, with task-level
+/// values taking precedence over workspace-level values
+pub fn get_task_env_vars(workspace_config: &WorkspaceConfig, task: &Task) -> HashMap<String, String> {
+    let mut env_vars = workspace_config.env_vars.clone();
+    for (name, value) in task.env_vars.iter() {
+        env_vars.insert(name.clone(), value.clone());
+    }
+    env_vars
+}
+
+pub fn get_current_task_input(
     workspace_config: &WorkspaceConfig,
     task: &Arc<Task>,
     db_env: &lmdb::Environment,
@@ -67,6 +79,13 @@ fn get_current_task_input(
         file_hashes: HashMap::new(),
         task_outputs: HashMap::new(),
         vars: HashMap::new(),
+        env_vars: get_task_env_vars(workspace_config, task),
+        secret_vars: HashMap::new(),
+        secret_var_salt: new_salt().map_err(|e| TaskExecutionError::IOError {
+            message: String::from("Error generating salt for secret vars"),
+            cause: e,
+        })?,
+        secret_var_hashes: HashMap::new(),
     };
 
     for project_source in task.project_source_deps.iter() {
@@ -180,9 +199,19 @@ fn get_current_task_input(
     for (var_alias, var_name) in task.var_deps.iter() {
         let var_value = get_var(var_name.as_ref(), &workspace_config.vars)
             .map_err(|e| TaskExecutionError::VarLookupError(e))?;
-        current_task_input
-            .vars
-            .insert(String::from(var_alias.as_ref()), var_value.clone());
+        if is_secret_var(workspace_config, var_name.as_ref()) {
+            current_task_input.secret_var_hashes.insert(
+                String::from(var_alias.as_ref()),
+                hash_secret(current_task_input.secret_var_salt.as_str(), var_value),
+            );
+            current_task_input
+                .secret_vars
+                .insert(String::from(var_alias.as_ref()), var_value.clone());
+        } else {
+            current_task_input
+                .vars
+                .insert(String::from(var_alias.as_ref()), var_value.clone());
+        }
     }
 
     Ok(current_task_input)
@@ -212,84 +241,120 @@ fn get_up_to_date_task_record(
         }
     };
 
+    check_task_record(workspace_dir, &task.task, &task_record, current_task_input)
+        .ok()
+        .map(|_| task_record)
+}
+
+/// Compare a task's current inputs and artifacts to the ones recorded the last time it ran, and
+/// return the first difference found, if any
+pub fn check_task_record(
+    workspace_dir: &Path,
+    task: &Task,
+    task_record: &TaskRecord,
+    current_task_input: &TaskInput,
+) -> Result<(), StaleReason> {
     // Check project source files
     if current_task_input.project_source_hashes.len()
         != task_record.input.project_source_hashes.len()
     {
-        return None;
+        return Err(StaleReason::ProjectSourceChanged(None));
     }
 
     for (source_file, source_hash) in current_task_input.project_source_hashes.iter() {
         let prev_hash = match task_record.input.project_source_hashes.get(source_file) {
             Some(hash) => hash,
             None => {
-                return None;
+                return Err(StaleReason::ProjectSourceChanged(Some(source_file.clone())));
             }
         };
 
         if prev_hash != source_hash {
-            return None;
+            return Err(StaleReason::ProjectSourceChanged(Some(source_file.clone())));
         }
     }
 
     // Check input files
     if current_task_input.file_hashes.len() != task_record.input.file_hashes.len() {
-        return None;
+        return Err(StaleReason::FileDependencyChanged(None));
     }
 
     for (file_alias, hash) in current_task_input.file_hashes.iter() {
         let prev_hash = match task_record.input.file_hashes.get(file_alias) {
             Some(hash) => hash,
             None => {
-                return None;
+                return Err(StaleReason::FileDependencyChanged(Some(file_alias.clone())));
             }
         };
 
         if prev_hash != hash {
-            return None;
+            return Err(StaleReason::FileDependencyChanged(Some(file_alias.clone())));
         }
     }
 
     // Check outputs of task and env dependencies
     if current_task_input.task_outputs.len() != task_record.input.task_outputs.len() {
-        return None;
+        return Err(StaleReason::TaskOutputChanged(None));
     }
 
     for (task_alias, task_output) in current_task_input.task_outputs.iter() {
         let prev_task_output = match task_record.input.task_outputs.get(task_alias) {
             Some(output) => output,
             None => {
-                return None;
+                return Err(StaleReason::TaskOutputChanged(Some(task_alias.clone())));
             }
         };
 
         if prev_task_output != task_output {
-            return None;
+            return Err(StaleReason::TaskOutputChanged(Some(task_alias.clone())));
         }
     }
 
     // Check input variables
     if current_task_input.vars.len() != task_record.input.vars.len() {
-        return None;
+        return Err(StaleReason::VarChanged(None));
     }
 
     for (var_alias, var_value) in current_task_input.vars.iter() {
         let prev_var = match task_record.input.vars.get(var_alias) {
             Some(var) => var,
             None => {
-                return None;
+                return Err(StaleReason::VarChanged(Some(var_alias.clone())));
             }
         };
 
         if prev_var != var_value {
-            return None;
+            return Err(StaleReason::VarChanged(Some(var_alias.clone())));
         }
     }
 
+    // Check secret variables, which can only be compared by hashing them with the previous salt
+    if current_task_input.secret_vars.len() != task_record.input.secret_var_hashes.len() {
+        return Err(StaleReason::VarChanged(None));
+    }
+
+    for (var_alias, var_value) in current_task_input.secret_vars.iter() {
+        let prev_hash = match task_record.input.secret_var_hashes.get(var_alias) {
+            Some(hash) => hash,
+            None => {
+                return Err(StaleReason::VarChanged(Some(var_alias.clone())));
+            }
+        };
+
+        if *prev_hash != hash_secret(task_record.input.secret_var_salt.as_str(), var_value) {
+            return Err(StaleReason::VarChanged(Some(var_alias.clone())));
+        }
+    }
+
+    // Check environment variables
+    if current_task_input.env_vars != task_record.input.env_vars {
+        return Err(StaleReason::EnvVarsChanged);
+    }
+
     // Check output files
     let mut current_output_file_hashes: HashMap<Arc<str>, String> =
-        HashMap::with_capacity(task.task.artifacts.files.len());
-    for artifact in task.task.artifacts.files.iter() {
+        HashMap::with_capacity(task.artifacts.files.len());
+    for artifact in task.artifacts.files.iter() {
         let output_file_hash_res = compute_file_hash(
             workspace_dir
                 .join(Path::new(artifact.as_ref()))
@@ -300,29 +365,29 @@ fn get_up_to_date_task_record(
                 current_output_file_hashes.insert(artifact.clone(), hash);
             }
             Err(_) => {
-                return None;
+                return Err(StaleReason::ArtifactChanged(Some(String::from(artifact.as_ref()))));
             }
         };
     }
 
     if current_output_file_hashes.len() != task_record.output.file_hashes.len() {
-        return None;
+        return Err(StaleReason::ArtifactChanged(None));
     }
 
     for (file_name, file_hash) in current_output_file_hashes {
         let prev_hash = match task_record.output.file_hashes.get(file_name.as_ref()) {
             Some(hash) => hash,
             None => {
-                return None;
+                return Err(StaleReason::ArtifactChanged(Some(String::from(file_name.as_ref()))));
             }
         };
 
         if prev_hash != &file_hash {
-            return None;
+            return Err(StaleReason::ArtifactChanged(Some(String::from(file_name.as_ref()))));
         }
     }
 
-    Some(task_record)
+    Ok(())
 }
 
 fn execute_task_actions_and_store_result(
@@ -346,6 +411,7 @@ fn execute_task_actions_and_store_result(
             .map_err(|e| TaskExecutionError::LuaError(e))?;
     }
 
+    let start_time = Instant::now();
     let result_res = execute_task_actions(
         lua,
         task,
@@ -356,6 +422,7 @@ fn execute_task_actions_and_store_result(
         cache,
         &task_result_sender,
     );
+    let duration = start_time.elapsed();
 
     lua.set_named_registry_value(COBBLE_JOB_INTERACTIVE_ENABLED, false)
         .map_err(|e| TaskExecutionError::LuaError(e))?;
@@ -408,6 +475,7 @@ fn execute_task_actions_and_store_result(
     let task_record = TaskRecord {
         input: current_task_input,
         output: task_output_record,
+        duration_ms: Some(duration.as_millis() as u64),
     };
     put_task_record(db_env, db.clone(), task.task_name.as_ref(), &task_record)
         .map_err(|e| TaskExecutionError::DBPutError(e))?;
@@ -459,7 +527,7 @@ pub fn execute_task_job(
         }
     };
 
-    if !workspace_config.force_run_tasks && !task.task.always_run {
+    if !workspace_config.force_run_tasks && !task.force_run && !task.task.always_run {
         let up_to_date_task_record = get_up_to_date_task_record(
             &workspace_config.workspace_dir,
             db_env,
@@ -521,11 +589,15 @@ mod tests {
     use std::sync::{mpsc, RwLock};
     use std::time::Duration;
 
-    use crate::config::TaskOutputCondition;
+    use std::collections::HashSet;
+
+    use crate::config::{OutputMode, TaskOutputCondition};
     use crate::db::new_db_env;
     use crate::execute::action::init_lua_for_task_executor;
     use crate::lua::{detached::dump_function, lua_env::create_lua_env};
+    use crate::project_def::var::{VarDef, VarType};
     use crate::project_def::{Action, ActionCmd, ExternalTool};
+    use crate::vars::resolve_declared_vars;
     use crate::workspace::{Task, TaskType, Workspace};
 
     use super::*;
@@ -538,10 +610,19 @@ mod tests {
             workspace_dir: PathBuf::from("."),
             root_projects: vec![String::from(".")],
             vars: HashMap::new(),
+            secret_vars: HashSet::new(),
+            var_sources: HashMap::new(),
+            profiles: HashMap::new(),
+            profile: None,
             force_run_tasks: false,
             num_threads: 1,
             show_stdout: TaskOutputCondition::Always,
-            show_stderr: TaskOutputCondition::Always
+            show_stderr: TaskOutputCondition::Always,
+            output_mode: OutputMode::Grouped,
+            env_vars: HashMap::new(),
+            env_passthrough: None,
+            pty: false,
+            log_retention: 0,
         });
         let workspace_dir: Arc<Path> = PathBuf::from(".").into();
         let lua = create_lua_env(workspace_dir.as_ref()).unwrap();
@@ -600,17 +681,17 @@ mod tests {
             tasks: vec![(test_task_name.clone(), task.clone())]
                 .into_iter()
                 .collect(),
-            build_envs: HashMap::new(),
             tools: vec![(tool_name.clone(), tool.clone())]
                 .into_iter()
                 .collect(),
-            file_providers: HashMap::new(),
+            ..Default::default()
         });
 
         let task_job = TaskJob {
             task_name: test_task_name.clone(),
             workspace: workspace.clone(),
             task: task.clone(),
+            force_run: false,
         };
 
         let stdin_ready = Arc::new((Mutex::new(true), Condvar::new()));
@@ -638,4 +719,161 @@ mod tests {
             _ => panic!("Did not get a completion message"),
         };
     }
+
+    /// Run a task with a single func action, and return the task's record
+    fn run_func_task(
+        workspace_config: Arc<WorkspaceConfig>,
+        var_deps: Vec<(&str, &str)>,
+        action_source: &str,
+    ) -> TaskRecord {
+        let tmpdir = mktemp::Temp::new_dir().unwrap();
+
+        let workspace_dir: Arc<Path> = PathBuf::from(".").into();
+        let lua = create_lua_env(workspace_dir.as_ref()).unwrap();
+        init_lua_for_task_executor(&lua).unwrap();
+
+        let db_env = Arc::new(new_db_env(tmpdir.as_path().join(".cobble.db").as_path()).unwrap());
+        let db = db_env.open_db(None).unwrap();
+        let (tx, rx) = mpsc::channel::<TaskJobMessage>();
+
+        let cache = Arc::new(TaskExecutorCache {
+            project_source_hashes: RwLock::new(HashMap::new()),
+            file_hashes: RwLock::new(HashMap::new()),
+            task_outputs: RwLock::new(HashMap::new()),
+        });
+
+        let action_func: mlua::Function = lua.load(action_source).eval().unwrap();
+
+        let test_task_name = Arc::<str>::from("test");
+        let task = Arc::new(Task {
+            name: test_task_name.clone(),
+            task_type: TaskType::Task,
+            dir: workspace_dir.clone(),
+            project_name: Arc::<str>::from("/"),
+            var_deps: var_deps
+                .into_iter()
+                .map(|(alias, name)| (Arc::<str>::from(alias), Arc::<str>::from(name)))
+                .collect(),
+            actions: vec![Action {
+                tools: HashMap::new(),
+                build_envs: HashMap::new(),
+                kwargs: HashMap::new(),
+                cmd: ActionCmd::Func(dump_function(&lua, action_func, &mut HashMap::new(), &mut Vec::new()).unwrap()),
+            }],
+            ..Default::default()
+        });
+
+        let workspace = Arc::new(Workspace {
+            tasks: vec![(test_task_name.clone(), task.clone())]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        });
+
+        let task_job = TaskJob {
+            task_name: test_task_name.clone(),
+            workspace: workspace.clone(),
+            task: task.clone(),
+            force_run: false,
+        };
+
+        let stdin_ready = Arc::new((Mutex::new(true), Condvar::new()));
+
+        execute_task_job(
+            &workspace_config,
+            &lua,
+            &db_env,
+            &db,
+            &task_job,
+            &tx,
+            &stdin_ready,
+            cache.clone(),
+        );
+
+        loop {
+            match rx.recv_timeout(Duration::from_secs(1)).unwrap() {
+                TaskJobMessage::Complete { result, .. } => match result {
+                    TaskResult::Success => break,
+                    res => panic!("Did not get a success message: {:?}", res),
+                },
+                _ => { /* Ignore output */ }
+            }
+        }
+
+        get_task_record(&db_env, db, "test").unwrap()
+    }
+
+    #[test]
+    fn test_secret_in_var_table_not_stored() {
+        let workspace_config = Arc::new(
+            crate::config::parse_workspace_config(
+                r#"
+                [vars]
+                db.password = "hunter2"
+                db.host = "localhost"
+
+                [secret_vars]
+                db.password = true
+            "#,
+                Path::new("cobble.toml"),
+            )
+            .unwrap(),
+        );
+
+        // The task still gets the whole table, including the secret
+        let task_record = run_func_task(
+            workspace_config,
+            vec![("db", "db")],
+            r#"function (c) assert(c.vars.db.password == "hunter2") end"#,
+        );
+
+        let serialized_record = serde_json::to_string(&task_record).unwrap();
+        assert!(!serialized_record.contains("hunter2"));
+        assert!(!serialized_record.contains("localhost"));
+        assert!(task_record.input.secret_var_hashes.contains_key("db"));
+    }
+
+    #[test]
+    fn test_declared_var_types() {
+        let mut workspace_config = crate::config::parse_workspace_config(
+            r#"
+            [vars]
+            verbose = "no"
+            jobs = "08"
+            ratio = "0.5"
+        "#,
+            Path::new("cobble.toml"),
+        )
+        .unwrap();
+
+        let var_defs: HashMap<Arc<str>, Arc<VarDef>> = vec![
+            ("verbose", VarType::Bool),
+            ("jobs", VarType::Int),
+            ("ratio", VarType::Number),
+        ]
+        .into_iter()
+        .map(|(name, var_type)| {
+            let var_def = Arc::new(VarDef {
+                name: Arc::<str>::from(name),
+                var_type,
+                choices: None,
+                default: None,
+                description: None,
+            });
+            (var_def.name.clone(), var_def)
+        })
+        .collect();
+        resolve_declared_vars(&var_defs, &mut workspace_config).unwrap();
+
+        run_func_task(
+            Arc::new(workspace_config),
+            vec![("verbose", "verbose"), ("jobs", "jobs"), ("ratio", "ratio")],
+            r#"function (c)
+                assert(not c.vars.verbose, "a false bool var should be falsy")
+                assert(c.vars.verbose == false)
+                assert(math.type(c.vars.jobs) == "integer" and c.vars.jobs == 8)
+                assert(math.type(c.vars.ratio) == "float" and c.vars.ratio == 0.5)
+            end"#,
+        );
+    }
 }
@@ -0,0 +1,501 @@
+// Cobble Build Automation
+// Copyright (C) 2024 Jeremiah Darais
+//
+// This program is licensed under the GPLv3.0 license (https://github.com/jdarais/cobble/blob/main/COPYING)
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor};
+use crossterm::terminal::{
+    self, disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen,
+    LeaveAlternateScreen,
+};
+use crossterm::{execute, queue};
+
+use crate::config::TaskOutputCondition;
+use crate::execute::execute::TaskResult;
+use crate::execute::job_io::JobIO;
+use crate::lua::cmd::kill_all_spawned_processes;
+
+// Don't redraw on every bit of output, or jobs with a lot of output would slow down the run
+const MIN_REDRAW_INTERVAL: Duration = Duration::from_millis(50);
+const MAX_REDRAW_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, PartialEq)]
+enum JobState {
+    Queued,
+    Running,
+    UpToDate,
+    Succeeded,
+    Failed,
+}
+
+impl JobState {
+    fn label(&self) -> &'static str {
+        match self {
+            JobState::Queued => "QUEUED",
+            JobState::Running => " RUN  ",
+            JobState::UpToDate => " UPTD ",
+            JobState::Succeeded => "  OK  ",
+            JobState::Failed => " FAIL ",
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            JobState::Queued => Color::DarkGrey,
+            JobState::Running => Color::Cyan,
+            JobState::UpToDate => Color::Blue,
+            JobState::Succeeded => Color::Green,
+            JobState::Failed => Color::Red,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Stream {
+    Stdout,
+    Stderr,
+}
+
+struct DashboardJob {
+    state: JobState,
+    started: Option<Instant>,
+    elapsed: Option<Duration>,
+    show_stdout: TaskOutputCondition,
+    show_stderr: TaskOutputCondition,
+    output: Vec<(Stream, String)>,
+    lines: Vec<String>,
+    partial_line: String,
+    status: Option<String>,
+}
+
+/// Displays a live view of every job in the graph, along with the most recent output of a selected
+/// job.  When the dashboard closes, the output of the run is printed the same way grouped output
+/// would show it, so that it is still available after the dashboard is gone.
+pub struct TuiIO {
+    job_ids: Vec<Arc<str>>,
+    jobs: HashMap<Arc<str>, DashboardJob>,
+    completion_order: Vec<Arc<str>>,
+    selected: usize,
+    follow_running: bool,
+    run_started: Instant,
+    last_draw: Option<Instant>,
+    needs_redraw: bool,
+}
+
+impl TuiIO {
+    pub fn new<'a, I>(job_ids: I) -> io::Result<TuiIO>
+    where
+        I: Iterator<Item = &'a Arc<str>>,
+    {
+        let mut job_ids: Vec<Arc<str>> = job_ids.cloned().collect();
+        job_ids.sort();
+
+        let jobs = job_ids
+            .iter()
+            .map(|id| {
+                (
+                    id.clone(),
+                    DashboardJob {
+                        state: JobState::Queued,
+                        started: None,
+                        elapsed: None,
+                        show_stdout: TaskOutputCondition::OnFail,
+                        show_stderr: TaskOutputCondition::OnFail,
+                        output: Vec::new(),
+                        lines: Vec::new(),
+                        partial_line: String::new(),
+                        status: None,
+                    },
+                )
+            })
+            .collect();
+
+        enable_raw_mode()?;
+        #[cfg(unix)]
+        if let Err(e) = enable_output_processing() {
+            let _ = disable_raw_mode();
+            return Err(e);
+        }
+        if let Err(e) = execute!(io::stdout(), EnterAlternateScreen, Hide) {
+            let _ = disable_raw_mode();
+            return Err(e);
+        }
+
+        Ok(TuiIO {
+            job_ids,
+            jobs,
+            completion_order: Vec::new(),
+            selected: 0,
+            follow_running: true,
+            run_started: Instant::now(),
+            last_draw: None,
+            needs_redraw: true,
+        })
+    }
+
+    fn print_output(&mut self, job_id: &Arc<str>, stream: Stream, text: String) {
+        if let Some(job) = self.jobs.get_mut(job_id) {
+            for c in text.chars() {
+                match c {
+                    '\n' => job.lines.push(std::mem::take(&mut job.partial_line)),
+                    '\r' => { /* Ignore */ }
+                    c => job.partial_line.push(c),
+                }
+            }
+            job.output.push((stream, text));
+            self.needs_redraw = true;
+        }
+    }
+
+    fn handle_input(&mut self) {
+        while let Ok(true) = event::poll(Duration::ZERO) {
+            let key = match event::read() {
+                Ok(Event::Key(key)) if key.kind != KeyEventKind::Release => key,
+                Ok(Event::Resize(_, _)) => {
+                    self.needs_redraw = true;
+                    continue;
+                }
+                _ => continue,
+            };
+
+            match key.code {
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    // Raw mode keeps the terminal from sending SIGINT, so handle interrupts here
+                    self.close();
+                    kill_all_spawned_processes();
+                    std::process::exit(130);
+                }
+                KeyCode::Up | KeyCode::Char('k') => {
+                    self.selected = self.selected.saturating_sub(1);
+                    self.follow_running = false;
+                }
+                KeyCode::Down | KeyCode::Char('j') => {
+                    self.selected = (self.selected + 1).min(self.job_ids.len().saturating_sub(1));
+                    self.follow_running = false;
+                }
+                KeyCode::Char('f') => {
+                    self.follow_running = true;
+                }
+                _ => {}
+            }
+            self.needs_redraw = true;
+        }
+    }
+
+    fn redraw(&mut self, force: bool) {
+        let now = Instant::now();
+        let since_last_draw = self.last_draw.map(|t| now - t);
+        let should_draw = match since_last_draw {
+            None => true,
+            Some(d) => (self.needs_redraw && (force || d >= MIN_REDRAW_INTERVAL)) || d >= MAX_REDRAW_INTERVAL,
+        };
+        if !should_draw {
+            return;
+        }
+
+        if self.follow_running {
+            let running_idx = self
+                .job_ids
+                .iter()
+                .enumerate()
+                .filter(|(_, id)| self.jobs[*id].state == JobState::Running)
+                .max_by_key(|(_, id)| self.jobs[*id].started)
+                .map(|(idx, _)| idx);
+            if let Some(idx) = running_idx {
+                self.selected = idx;
+            }
+        }
+
+        // Drawing is best-effort.  If the terminal goes away, there's nothing useful to do about it.
+        let _ = self.draw();
+        self.last_draw = Some(now);
+        self.needs_redraw = false;
+    }
+
+    fn draw(&self) -> io::Result<()> {
+        let (width, height) = terminal::size()?;
+        let width = width as usize;
+        let height = height as usize;
+        let mut stdout = io::stdout().lock();
+
+        queue!(stdout, MoveTo(0, 0), Clear(ClearType::All))?;
+
+        // Header
+        let completed = self.completion_order.len();
+        let running = self.jobs.values().filter(|j| j.state == JobState::Running).count();
+        let failed = self.jobs.values().filter(|j| j.state == JobState::Failed).count();
+        let header = format!(
+            "cobble  [{}/{}] complete, {} running, {} failed  ({})",
+            completed,
+            self.job_ids.len(),
+            running,
+            failed,
+            format_duration(self.run_started.elapsed())
+        );
+        queue!(
+            stdout,
+            SetAttribute(Attribute::Bold),
+            Print(truncate(&header, width)),
+            SetAttribute(Attribute::Reset)
+        )?;
+
+        // Job list, taking up to half the screen, scrolled to keep the selection visible
+        let list_height = (height.saturating_sub(4) / 2).max(1).min(self.job_ids.len());
+        let list_start = if self.selected >= list_height {
+            self.selected + 1 - list_height
+        } else {
+            0
+        };
+        for (row, job_id) in self.job_ids.iter().skip(list_start).take(list_height).enumerate() {
+            let idx = list_start + row;
+            let job = &self.jobs[job_id];
+            let elapsed = match (job.elapsed, job.started) {
+                (Some(elapsed), _) => format_duration(elapsed),
+                (None, Some(started)) => format_duration(started.elapsed()),
+                (None, None) => String::new(),
+            };
+
+            queue!(stdout, MoveTo(0, (row + 1) as u16))?;
+            if idx == self.selected {
+                queue!(stdout, SetAttribute(Attribute::Reverse))?;
+            }
+            queue!(
+                stdout,
+                SetForegroundColor(job.state.color()),
+                Print(format!("[{}]", job.state.label())),
+                ResetColor,
+            )?;
+            if idx == self.selected {
+                queue!(stdout, SetAttribute(Attribute::Reverse))?;
+            }
+            let line = format!(" {} {}", job_id, elapsed);
+            queue!(
+                stdout,
+                Print(truncate(&line, width.saturating_sub(8))),
+                SetAttribute(Attribute::Reset)
+            )?;
+        }
+
+        // Output of the selected job
+        let output_top = list_height + 1;
+        let footer_row = height.saturating_sub(1);
+        if let Some(selected_id) = self.job_ids.get(self.selected) {
+            let job = &self.jobs[selected_id];
+            let title = format!("--- output: {} ", selected_id);
+            queue!(
+                stdout,
+                MoveTo(0, output_top as u16),
+                SetAttribute(Attribute::Dim),
+                Print(truncate(&format!("{:-<width$}", title, width = width), width)),
+                SetAttribute(Attribute::Reset)
+            )?;
+
+            let output_height = footer_row.saturating_sub(output_top + 1);
+            let mut lines: Vec<&str> = job.lines.iter().map(String::as_str).collect();
+            if !job.partial_line.is_empty() {
+                lines.push(job.partial_line.as_str());
+            }
+            if let Some(status) = &job.status {
+                lines.extend(status.lines());
+            }
+            let first_line = lines.len().saturating_sub(output_height);
+            for (row, line) in lines[first_line..].iter().enumerate() {
+                queue!(
+                    stdout,
+                    MoveTo(0, (output_top + 1 + row) as u16),
+                    Print(truncate(&strip_control_sequences(line), width))
+                )?;
+            }
+        }
+
+        let footer = if self.follow_running {
+            "up/down: select job   ctrl-c: abort"
+        } else {
+            "up/down: select job   f: follow running jobs   ctrl-c: abort"
+        };
+        queue!(
+            stdout,
+            MoveTo(0, footer_row as u16),
+            SetAttribute(Attribute::Dim),
+            Print(truncate(footer, width)),
+            SetAttribute(Attribute::Reset)
+        )?;
+
+        stdout.flush()
+    }
+
+    fn close(&mut self) {
+        let _ = execute!(io::stdout(), Show, LeaveAlternateScreen);
+        let _ = disable_raw_mode();
+    }
+
+    /// Print a transcript of the run, the same as grouped output would have shown it
+    fn print_transcript(&self) {
+        for job_id in self.completion_order.iter() {
+            let job = &self.jobs[job_id];
+            let failed = job.state == JobState::Failed;
+            println!("-----> {}", job_id);
+            for (stream, text) in job.output.iter() {
+                let show = match stream {
+                    Stream::Stdout => &job.show_stdout,
+                    Stream::Stderr => &job.show_stderr,
+                };
+                let should_print = match show {
+                    TaskOutputCondition::Always => true,
+                    TaskOutputCondition::OnFail => failed,
+                    TaskOutputCondition::Never => false,
+                };
+                if should_print {
+                    match stream {
+                        Stream::Stdout => print!("{}", text),
+                        Stream::Stderr => eprint!("{}", text),
+                    }
+                }
+            }
+            if let Some(status) = &job.status {
+                println!("{}", status);
+            }
+        }
+    }
+}
+
+impl JobIO for TuiIO {
+    fn job_started(
+        &mut self,
+        job_id: &Arc<str>,
+        stdin_ready: Arc<(Mutex<bool>, Condvar)>,
+        _interactive: bool,
+        show_stdout: TaskOutputCondition,
+        show_stderr: TaskOutputCondition,
+    ) {
+        // The dashboard isn't used for runs with interactive jobs, so no job needs to wait for stdin
+        let (ready_lock, ready_condvar) = &*stdin_ready;
+        *ready_lock.lock().unwrap() = true;
+        ready_condvar.notify_all();
+
+        if let Some(job) = self.jobs.get_mut(job_id) {
+            job.state = JobState::Running;
+            job.started = Some(Instant::now());
+            job.show_stdout = show_stdout;
+            job.show_stderr = show_stderr;
+        }
+        self.needs_redraw = true;
+        self.redraw(true);
+    }
+
+    fn print_stdout(&mut self, job_id: &Arc<str>, text: String) {
+        self.print_output(job_id, Stream::Stdout, text);
+        self.redraw(false);
+    }
+
+    fn print_stderr(&mut self, job_id: &Arc<str>, text: String) {
+        self.print_output(job_id, Stream::Stderr, text);
+        self.redraw(false);
+    }
+
+    fn job_completed(&mut self, job_id: &Arc<str>, task_result: &TaskResult) {
+        if let Some(job) = self.jobs.get_mut(job_id) {
+            let (state, status) = match task_result {
+                TaskResult::UpToDate => (JobState::UpToDate, format!("[UPTD] {}", job_id)),
+                TaskResult::Success => (JobState::Succeeded, format!("[ OK ] {}", job_id)),
+                TaskResult::Error(e) => (JobState::Failed, format!("[FAIL] {}: {}", job_id, e)),
+            };
+            job.state = state;
+            job.status = Some(status);
+            job.elapsed = job.started.map(|t| t.elapsed());
+            self.completion_order.push(job_id.clone());
+        }
+        self.needs_redraw = true;
+        self.redraw(true);
+    }
+
+    fn tick(&mut self) {
+        self.handle_input();
+        self.redraw(false);
+    }
+}
+
+impl Drop for TuiIO {
+    fn drop(&mut self) {
+        self.close();
+        self.print_transcript();
+    }
+}
+
+/// Raw mode turns off output processing, which would make anything printed outside of the
+/// dashboard, like warnings on stderr, move down a line without returning to the first column.
+#[cfg(unix)]
+fn enable_output_processing() -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    // Use the same terminal that crossterm put into raw mode
+    let tty = std::fs::File::open("/dev/tty")?;
+    let fd = tty.as_raw_fd();
+    let mut termios = std::mem::MaybeUninit::<libc::termios>::uninit();
+    if unsafe { libc::tcgetattr(fd, termios.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut termios = unsafe { termios.assume_init() };
+    termios.c_oflag |= libc::OPOST;
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    if secs < 60 {
+        format!("{:.1}s", d.as_secs_f32())
+    } else {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    }
+}
+
+fn truncate(s: &str, width: usize) -> String {
+    s.chars().take(width).collect()
+}
+
+/// Remove terminal escape sequences and other control characters from a line of output, so that
+/// they don't interfere with drawing the dashboard
+fn strip_control_sequences(line: &str) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => {
+                if chars.peek() == Some(&'[') {
+                    chars.next();
+                    // Skip parameters until the final byte of the sequence
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+            }
+            '\t' => result.push_str("    "),
+            c if c.is_control() => { /* Ignore */ }
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_control_sequences() {
+        assert_eq!(strip_control_sequences("\x1b[1;31mred\x1b[0m text"), "red text");
+        assert_eq!(strip_control_sequences("a\tb\x07"), "a    b");
+    }
+}
@@ -12,9 +12,13 @@ use crate::execute::action::init_lua_for_task_executor;
 use crate::execute::clean_task_job::execute_clean_job;
 use crate::execute::env_action_job::execute_env_action_job;
 use crate::execute::execute::{ExecutorJob, TaskExecutorCache, TaskJobMessage};
-use crate::execute::task_job::execute_task_job;
+use crate::execute::task_job::{execute_task_job, get_task_env_vars};
 use crate::execute::tool_check_job::execute_tool_check_job;
-use crate::lua::lua_env::create_lua_env;
+use crate::lua::cmd::kill_spawned_processes;
+use crate::lua::lua_env::{
+    create_lua_env, COBBLE_ENV_PASSTHROUGH, COBBLE_JOB_ENV_VARS, COBBLE_JOB_PTY_ENABLED,
+};
+use crate::trace::Tracer;
 
 pub struct TaskExecutorWorkerArgs {
     pub workspace_config: Arc<WorkspaceConfig>,
@@ -23,6 +27,8 @@ pub struct TaskExecutorWorkerArgs {
     pub task_queue: Arc<(Mutex<Option<VecDeque<ExecutorJob>>>, Condvar)>,
     pub task_result_sender: Sender<TaskJobMessage>,
     pub cache: Arc<TaskExecutorCache>,
+    pub worker_id: u64,
+    pub tracer: Option<Arc<Tracer>>,
 }
 
 fn poll_next_task(
@@ -60,6 +66,15 @@ pub fn run_task_executor_worker(args: TaskExecutorWorkerArgs) {
         .expect("Lua environment creation should always succeed");
     init_lua_for_task_executor(&lua)
         .expect("Initializing lua environment for a task executor should always succeed");
+    lua.set_named_registry_value(
+        COBBLE_ENV_PASSTHROUGH,
+        args.workspace_config.env_passthrough.clone(),
+    )
+    .expect("Setting a registry value should always succeed");
+
+    if let Some(tracer) = &args.tracer {
+        tracer.set_thread_name(args.worker_id, format!("worker {}", args.worker_id).as_str());
+    }
 
     loop {
         let next_task_opt = poll_next_task(&args.task_queue);
@@ -73,6 +88,36 @@ pub fn run_task_executor_worker(args: TaskExecutorWorkerArgs) {
 
         let stdin_ready = Arc::new((Mutex::new(false), Condvar::new()));
 
+        let (job_env_vars, job_pty) = match &next_task {
+            ExecutorJob::Task(task) => (
+                get_task_env_vars(&args.workspace_config, &task.task),
+                task.task.pty.unwrap_or(args.workspace_config.pty),
+            ),
+            ExecutorJob::Clean(clean) => (
+                get_task_env_vars(&args.workspace_config, &clean.task),
+                clean.task.pty.unwrap_or(args.workspace_config.pty),
+            ),
+            _ => (
+                args.workspace_config.env_vars.clone(),
+                args.workspace_config.pty,
+            ),
+        };
+        lua.set_named_registry_value(COBBLE_JOB_ENV_VARS, job_env_vars)
+            .expect("Setting a registry value should always succeed");
+        lua.set_named_registry_value(COBBLE_JOB_PTY_ENABLED, job_pty)
+            .expect("Setting a registry value should always succeed");
+
+        let (trace_name, trace_category) = match &next_task {
+            ExecutorJob::Task(task) => (task.task_name.clone(), "task"),
+            ExecutorJob::Clean(clean) => (clean.job_id.clone(), "clean"),
+            ExecutorJob::ToolCheck(tool_check) => (tool_check.job_id.clone(), "tool_check"),
+            ExecutorJob::EnvAction(env_action) => (env_action.job_id.clone(), "env_action"),
+        };
+        let trace_span = args
+            .tracer
+            .as_ref()
+            .map(|t| t.span(&trace_name, trace_category, args.worker_id));
+
         match next_task {
             ExecutorJob::Task(task) => {
                 let show_stdout = task
@@ -91,6 +136,7 @@ pub fn run_task_executor_worker(args: TaskExecutorWorkerArgs) {
                     .send(TaskJobMessage::Started {
                         task: task.task_name.clone(),
                         stdin_ready: stdin_ready.clone(),
+                        interactive: task.task.is_interactive,
                         show_stdout,
                         show_stderr,
                     })
@@ -123,6 +169,7 @@ pub fn run_task_executor_worker(args: TaskExecutorWorkerArgs) {
                     .send(TaskJobMessage::Started {
                         task: clean.job_id.clone(),
                         stdin_ready: stdin_ready.clone(),
+                        interactive: false,
                         show_stdout,
                         show_stderr,
                     })
@@ -142,6 +189,7 @@ pub fn run_task_executor_worker(args: TaskExecutorWorkerArgs) {
                     .send(TaskJobMessage::Started {
                         task: tool_check.job_id.clone(),
                         stdin_ready: stdin_ready.clone(),
+                        interactive: false,
                         show_stdout: args.workspace_config.show_stdout.clone(),
                         show_stderr: args.workspace_config.show_stderr.clone(),
                     })
@@ -161,6 +209,7 @@ pub fn run_task_executor_worker(args: TaskExecutorWorkerArgs) {
                     .send(TaskJobMessage::Started {
                         task: env_action_job.job_id.clone(),
                         stdin_ready: stdin_ready.clone(),
+                        interactive: true,
                         show_stdout: args.workspace_config.show_stdout.clone(),
                         show_stderr: args.workspace_config.show_stderr.clone(),
                     })
@@ -176,5 +225,12 @@ pub fn run_task_executor_worker(args: TaskExecutorWorkerArgs) {
                 );
             }
         };
+
+        // Background processes spawned by a job don't outlive it, whether it succeeded or failed
+        if let Err(e) = kill_spawned_processes(&lua) {
+            eprintln!("Error stopping background processes: {}", e);
+        }
+
+        drop(trace_span);
     }
 }
@@ -8,6 +8,7 @@ pub mod project_def;
 pub mod util;
 
 pub mod calc_artifacts;
+pub mod changed_files;
 pub mod config;
 pub mod db;
 pub mod dependency;
@@ -16,5 +17,7 @@ pub mod load;
 pub mod query;
 pub mod resolve;
 pub mod task_selection;
+pub mod trace;
 pub mod vars;
 pub mod workspace;
+pub mod workspace_json;
@@ -9,14 +9,17 @@ use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use crate::config::PROJECT_FILE_NAME;
+use crate::lua::cmd::kill_spawned_processes;
 use crate::lua::lua_env::{create_lua_env, COBBLE_JOB_INTERACTIVE_ENABLED};
 use crate::lua::detached::dump_function;
 use crate::project_def::build_env::validate_build_env;
 use crate::project_def::task::validate_task;
 use crate::project_def::tool::validate_tool;
+use crate::project_def::var::{validate_var, VarDef};
 use crate::project_def::{Action, ActionCmd, ExternalTool, Project};
 use crate::resolve::resolve_names_in_project;
 use crate::util::onscopeexit::OnScopeExit;
+use crate::workspace::INTERNAL_PROJECT_NAME;
 
 fn process_project(
     lua: &mlua::Lua,
@@ -138,6 +141,9 @@ pub fn init_lua_for_project_config(lua: &mlua::Lua, workspace_dir: &Path) -> mlu
     let validate_tool = lua.create_function(|lua, val: mlua::Value| validate_tool(lua, &val))?;
     cxt.set("validate_tool", validate_tool)?;
 
+    let validate_var = lua.create_function(|lua, val: mlua::Value| validate_var(lua, &val))?;
+    cxt.set("validate_var", validate_var)?;
+
     cxt.set("project_file_name", PROJECT_FILE_NAME)?;
 
     let project_config_source = include_bytes!("project_config.lua");
@@ -163,6 +169,8 @@ pub fn extract_project_defs(lua: &mlua::Lua) -> mlua::Result<HashMap<String, Pro
         }
     }
 
+    check_var_declarations(&projects)?;
+
     //
     // Inject an __COBBLE_INTERNAL__ project with the "cmd" tool
     //
@@ -175,6 +183,11 @@ pub fn extract_project_defs(lua: &mlua::Lua) -> mlua::Result<HashMap<String, Pro
             c.println("cmd: "..table.concat(c.args, " "))
 
             local args = tblext.extend({}, c.args)
+            -- "env" is reserved for build envs in arg-list actions, so also accept env vars as "env_vars"
+            if args.env_vars ~= nil then
+                args.env = tblext.extend(args.env or {}, args.env_vars)
+                args.env_vars = nil
+            end
             if args.cwd ~= nil then
                 args.cwd = path.join(c.project.dir, args.cwd)
             end
@@ -201,13 +214,14 @@ pub fn extract_project_defs(lua: &mlua::Lua) -> mlua::Result<HashMap<String, Pro
     };
 
     projects.insert(
-        String::from("/__COBBLE_INTERNAL__"),
+        String::from(INTERNAL_PROJECT_NAME),
         Project {
-            name: Arc::<str>::from("/__COBBLE_INTERNAL__"),
-            path: PathBuf::from("./__COBBLE_INTERNAL__").into(),
+            name: Arc::<str>::from(INTERNAL_PROJECT_NAME),
+            path: PathBuf::from(format!(".{}", INTERNAL_PROJECT_NAME)).into(),
             build_envs: Vec::new(),
             tasks: Vec::new(),
             tools: vec![cmd_tool],
+            vars: Vec::new(),
             child_project_names: Vec::new(),
             project_source_deps: Vec::new(),
         },
@@ -217,6 +231,54 @@ pub fn extract_project_defs(lua: &mlua::Lua) -> mlua::Result<HashMap<String, Pro
     Ok(projects)
 }
 
+/// Vars are global, so a var declared in more than one project has to be declared the same way in
+/// each of them
+fn check_var_declarations(projects: &HashMap<String, Project>) -> mlua::Result<()> {
+    let mut project_names: Vec<&String> = projects.keys().collect();
+    project_names.sort();
+
+    let mut declared_vars: HashMap<&str, (&str, &VarDef)> = HashMap::new();
+    for project_name in project_names {
+        let project = &projects[project_name];
+        for var in project.vars.iter() {
+            match declared_vars.get(var.name.as_ref()) {
+                Some((other_project_name, other_var)) if *other_var != var => {
+                    return Err(mlua::Error::runtime(format!(
+                        "Var '{}' is declared differently in projects '{}' and '{}'",
+                        var.name, other_project_name, project.name
+                    )));
+                }
+                Some(_) => {}
+                None => {
+                    declared_vars.insert(var.name.as_ref(), (project.name.as_ref(), var));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Run the project files of the root projects, (and the subprojects they add,) in a Lua
+/// environment that has been initialized with init_lua_for_project_config.  The definitions are
+/// left in the cobble.projects table.
+pub fn load_project_files<'a, P>(
+    lua: &mlua::Lua,
+    workspace_dir: &Path,
+    root_projects: P,
+) -> mlua::Result<()>
+where
+    P: Iterator<Item = &'a str>,
+{
+    let load_res = root_projects
+        .map(|project_dir| process_project_file(lua, project_dir, workspace_dir))
+        .collect::<mlua::Result<Vec<()>>>();
+
+    // Background processes started by project definition files don't outlive loading the workspace
+    kill_spawned_processes(lua)?;
+    load_res.and(Ok(()))
+}
+
 pub fn load_projects<'a, P>(
     workspace_dir: &Path,
     root_projects: P,
@@ -227,10 +289,7 @@ where
     let project_def_lua = create_lua_env(workspace_dir)?;
 
     init_lua_for_project_config(&project_def_lua, workspace_dir)?;
-
-    for project_dir in root_projects {
-        process_project_file(&project_def_lua, project_dir, workspace_dir)?;
-    }
+    load_project_files(&project_def_lua, workspace_dir, root_projects)?;
 
     extract_project_defs(&project_def_lua)
 }
@@ -273,4 +332,70 @@ mod tests {
         assert_eq!(project.tasks.len(), 0);
         assert_eq!(project.path.as_ref(), Path::new("."))
     }
+
+    #[test]
+    fn test_load_task_metadata() {
+        let tmpdir = mktemp::Temp::new_dir().unwrap();
+        {
+            let mut f = File::create(tmpdir.as_path().join("project.lua")).unwrap();
+            f.write_all(
+                br#"
+                task {
+                    name = "build",
+                    description = "Build it",
+                    group = "build",
+                    actions = { function (c) end }
+                }
+                task {
+                    name = "helper",
+                    private = true,
+                    actions = { function (c) end }
+                }
+            "#,
+            )
+            .unwrap();
+            f.flush().unwrap();
+        }
+
+        let projects = load_projects(tmpdir.as_path(), std::iter::once(".")).unwrap();
+        let workspace = crate::workspace::create_workspace(projects.values());
+
+        let build_task = workspace.tasks.get("/build").unwrap();
+        assert_eq!(build_task.description.as_deref(), Some("Build it"));
+        assert_eq!(build_task.group.as_deref(), Some("build"));
+        assert!(!build_task.is_private);
+
+        let helper_task = workspace.tasks.get("/helper").unwrap();
+        assert!(helper_task.is_private);
+    }
+
+    #[test]
+    fn test_load_conflicting_var_declarations() {
+        let tmpdir = mktemp::Temp::new_dir().unwrap();
+        std::fs::create_dir(tmpdir.as_path().join("sub")).unwrap();
+        std::fs::write(
+            tmpdir.as_path().join("project.lua"),
+            r#"
+            var { name = "build.mode", default = "debug" }
+            project_dir("sub")
+            "#,
+        )
+        .unwrap();
+        std::fs::write(
+            tmpdir.as_path().join("sub").join("project.lua"),
+            r#"var { name = "build.mode", default = "release" }"#,
+        )
+        .unwrap();
+
+        let err = load_projects(tmpdir.as_path(), std::iter::once(".")).unwrap_err();
+        assert!(err.to_string().contains("Var 'build.mode' is declared differently"));
+
+        // The same declaration in more than one project is fine
+        std::fs::write(
+            tmpdir.as_path().join("sub").join("project.lua"),
+            r#"var { name = "build.mode", default = "debug" }"#,
+        )
+        .unwrap();
+        assert!(load_projects(tmpdir.as_path(), std::iter::once(".")).is_ok());
+    }
 }
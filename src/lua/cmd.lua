@@ -5,6 +5,17 @@
 
 local cmd_lib = ...
 
-return function (...)
-    return cmd_lib.cmd(...)
-end
+local cmd_module = {
+    spawn = function (...)
+        return cmd_lib.spawn(...)
+    end,
+    pipe = function (...)
+        return cmd_lib.pipe(...)
+    end
+}
+
+return setmetatable(cmd_module, {
+    __call = function (_, ...)
+        return cmd_lib.cmd(...)
+    end
+})
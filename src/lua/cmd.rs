@@ -3,21 +3,39 @@
 //
 // This program is licensed under the GPLv3.0 license (https://github.com/jdarais/cobble/blob/main/COPYING)
 
-use std::io::Read;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
 use std::path::PathBuf;
-use std::process::{Command, Stdio};
-use std::sync::mpsc::channel;
-use std::thread;
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
-use mlua::{Error, Function, Lua, Table, UserData, Value};
+use mlua::{AnyUserData, Error, Function, Lua, RegistryKey, Table, UserData, Value};
 
-use crate::lua::lua_env::COBBLE_JOB_INTERACTIVE_ENABLED;
+use crate::lua::lua_env::{
+    COBBLE_ENV_PASSTHROUGH, COBBLE_JOB_ENV_VARS, COBBLE_JOB_INTERACTIVE_ENABLED,
+    COBBLE_JOB_PTY_ENABLED, COBBLE_JOB_SPAWNED_PROCESSES,
+};
+#[cfg(target_os = "linux")]
+use crate::lua::pty::open_pty;
+
+/// Processes started with `cmd.spawn` that have not been killed yet, keyed by pid.
+/// Kept globally so that they can be cleaned up if cobble is interrupted.
+static SPAWNED_PROCESSES: Mutex<Option<HashMap<u32, Arc<Mutex<SpawnedChild>>>>> = Mutex::new(None);
+
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(20);
 
 pub struct CmdLib;
 
 impl UserData for CmdLib {
     fn add_methods<'lua, M: mlua::prelude::LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
         methods.add_function("cmd", exec_shell_command);
+        methods.add_function("spawn", spawn_shell_command);
+        methods.add_function("pipe", exec_pipe_command);
     }
 }
 
@@ -28,7 +46,80 @@ enum ChildMessage {
     StderrDone,
 }
 
-fn exec_shell_command<'lua>(lua: &'lua Lua, args: Table<'lua>) -> mlua::Result<Table<'lua>> {
+enum CmdInput {
+    Text(Vec<u8>),
+    File(PathBuf),
+}
+
+/// Options that can be given to both a single command and a pipeline of commands
+#[derive(Default)]
+struct CmdOptions<'lua> {
+    cwd: Option<PathBuf>,
+    out_func: Option<Function<'lua>>,
+    err_func: Option<Function<'lua>>,
+    env: HashMap<String, String>,
+    clear_env: bool,
+    input: Option<CmdInput>,
+    pty: Option<bool>,
+}
+
+struct CmdArgs<'lua> {
+    cmd_with_args: Vec<String>,
+    options: CmdOptions<'lua>,
+}
+
+impl<'lua> CmdArgs<'lua> {
+    fn cmd_string(&self) -> String {
+        self.cmd_with_args.join(" ")
+    }
+}
+
+fn parse_cmd_option<'lua>(
+    lua: &'lua Lua,
+    key: mlua::String<'lua>,
+    value: Value<'lua>,
+    options: &mut CmdOptions<'lua>,
+) -> mlua::Result<()> {
+    let key_str = key
+        .to_str()
+        .map_err(|e| Error::runtime(format!("Error reading lua string value: {}", e)))?;
+    match key_str {
+        "cwd" => {
+            options.cwd = Some(PathBuf::from(lua.unpack::<String>(value)?));
+        }
+        "out" => {
+            options.out_func = Some(lua.unpack(value)?);
+        }
+        "err" => {
+            options.err_func = Some(lua.unpack(value)?);
+        }
+        "env" => {
+            options.env = lua.unpack(value)?;
+        }
+        "clear_env" => {
+            options.clear_env = lua.unpack(value)?;
+        }
+        "input" => {
+            let input: mlua::String = lua.unpack(value)?;
+            options.input = Some(CmdInput::Text(input.as_bytes().to_vec()));
+        }
+        "input_file" => {
+            options.input = Some(CmdInput::File(PathBuf::from(lua.unpack::<String>(value)?)));
+        }
+        "pty" => {
+            options.pty = Some(lua.unpack(value)?);
+        }
+        _ => {
+            return Err(Error::runtime(format!(
+                "Unknown key in cmd input: {}",
+                key_str
+            )));
+        }
+    };
+    Ok(())
+}
+
+fn parse_cmd_args<'lua>(lua: &'lua Lua, args: Table<'lua>) -> mlua::Result<CmdArgs<'lua>> {
     let args_len_int = args.len()?;
     let args_len: usize = args_len_int
         .try_into()
@@ -37,9 +128,7 @@ fn exec_shell_command<'lua>(lua: &'lua Lua, args: Table<'lua>) -> mlua::Result<T
     let mut cmd_with_args: Vec<String> = Vec::with_capacity(args_len);
     cmd_with_args.resize(args_len, String::new());
 
-    let mut cwd: Option<PathBuf> = None;
-    let mut out_func: Option<Function> = None;
-    let mut err_func: Option<Function> = None;
+    let mut options = CmdOptions::default();
 
     for pair in args.pairs() {
         let (k, v): (Value, Value) = pair?;
@@ -57,26 +146,7 @@ fn exec_shell_command<'lua>(lua: &'lua Lua, args: Table<'lua>) -> mlua::Result<T
                 cmd_with_args[idx - 1] = lua.unpack(v)?;
             }
             Value::String(s) => {
-                let s_str = s.to_str().map_err(|e| {
-                    Error::runtime(format!("Error reading lua string value: {}", e))
-                })?;
-                match s_str {
-                    "cwd" => {
-                        cwd = Some(PathBuf::from(lua.unpack::<String>(v)?));
-                    }
-                    "out" => {
-                        out_func = Some(lua.unpack(v)?);
-                    }
-                    "err" => {
-                        err_func = Some(lua.unpack(v)?);
-                    }
-                    _ => {
-                        return Err(Error::runtime(format!(
-                            "Unknown key in cmd input: {}",
-                            s.to_str().unwrap_or("<error reading value>")
-                        )));
-                    }
-                };
+                parse_cmd_option(lua, s, v, &mut options)?;
             }
             _ => {
                 return Err(Error::runtime(format!(
@@ -91,6 +161,13 @@ fn exec_shell_command<'lua>(lua: &'lua Lua, args: Table<'lua>) -> mlua::Result<T
         return Err(Error::runtime("No command given"));
     }
 
+    Ok(CmdArgs {
+        cmd_with_args,
+        options,
+    })
+}
+
+fn build_command(lua: &Lua, cmd_with_args: &[String], options: &CmdOptions) -> mlua::Result<Command> {
     let cmd_cmd = &cmd_with_args[0];
     let cmd_args = &cmd_with_args[1..];
 
@@ -99,125 +176,253 @@ fn exec_shell_command<'lua>(lua: &'lua Lua, args: Table<'lua>) -> mlua::Result<T
     cmd.stderr(Stdio::piped());
     cmd.args(cmd_args);
 
-    if let Some(d) = cwd {
+    if let Some(d) = &options.cwd {
         cmd.current_dir(d);
     }
 
-    let interactive_enabled: bool = lua.named_registry_value(COBBLE_JOB_INTERACTIVE_ENABLED)?;
-    if interactive_enabled {
-        cmd.stdin(Stdio::inherit());
-    } else {
-        cmd.stdin(Stdio::null());
+    // If the workspace defines an allow-list of host environment variables, only those variables
+    // are inherited from the parent process.  Setting clear_env inherits none of them.
+    let env_passthrough: Option<Vec<String>> = lua.named_registry_value(COBBLE_ENV_PASSTHROUGH)?;
+    if options.clear_env || env_passthrough.is_some() {
+        cmd.env_clear();
+    }
+    if let (false, Some(passthrough_vars)) = (options.clear_env, env_passthrough) {
+        for var_name in passthrough_vars {
+            if let Some(var_value) = std::env::var_os(var_name.as_str()) {
+                cmd.env(var_name, var_value);
+            }
+        }
     }
 
-    let child_res = cmd.spawn();
+    let job_env_vars: Option<HashMap<String, String>> = lua.named_registry_value(COBBLE_JOB_ENV_VARS)?;
+    if let Some(job_env_vars) = job_env_vars {
+        cmd.envs(job_env_vars);
+    }
+    cmd.envs(options.env.iter());
 
-    match child_res {
-        Err(e) => Err(Error::runtime(format!("Error executing command '{} {}': {}", cmd_cmd, cmd_args.join(" "), e))),
-        Ok(mut child) => {
-            let (tx, rx) = channel();
-
-            let stdout_tx = tx.clone();
-            let mut stdout = child.stdout.take().unwrap();
-            let stdout_thread = thread::spawn(move || {
-                let mut buf: Vec<u8> = Vec::with_capacity(256);
-                buf.resize(256, 0);
-                let mut start_from = 0usize;
-                loop {
-                    let res = stdout.read(&mut buf[start_from..]);
-                    match res {
-                        Ok(bytes_read) => {
-                            if bytes_read == 0 {
-                                break;
-                            }
-                            match std::str::from_utf8(&buf[start_from..(start_from+bytes_read)]) {
-                                Ok(out) => {
-                                    stdout_tx.send(ChildMessage::Stdout(String::from(out))).unwrap();
-                                    start_from = 0;
-                                },
-                                Err(_) => {
-                                    start_from = start_from + bytes_read;
-                                    if start_from >= buf.len() {
-                                        buf.resize(buf.len()*2, 0);
-                                    }
-                                }
-                            }
-                        }
-                        Err(_) => {
-                            break;
-                        }
+    Ok(cmd)
+}
+
+/// Set up stdin for a command from the `input` or `input_file` options.  Returns any text that
+/// needs to be written to the child's stdin once it has started.
+fn set_command_input(
+    lua: &Lua,
+    cmd: &mut Command,
+    input: &Option<CmdInput>,
+) -> mlua::Result<Option<Vec<u8>>> {
+    match input {
+        Some(CmdInput::Text(text)) => {
+            cmd.stdin(Stdio::piped());
+            Ok(Some(text.clone()))
+        }
+        Some(CmdInput::File(path)) => {
+            let file = File::open(path).map_err(|e| {
+                Error::runtime(format!("Error opening input file {}: {}", path.display(), e))
+            })?;
+            cmd.stdin(Stdio::from(file));
+            Ok(None)
+        }
+        None => {
+            let interactive_enabled: bool =
+                lua.named_registry_value(COBBLE_JOB_INTERACTIVE_ENABLED)?;
+            if interactive_enabled {
+                cmd.stdin(Stdio::inherit());
+            } else {
+                cmd.stdin(Stdio::null());
+            }
+            Ok(None)
+        }
+    }
+}
+
+/// Write input text to a child's stdin in a separate thread, so that a child producing output
+/// before it has read all of its input doesn't deadlock with us
+fn spawn_input_writer(child: &mut Child, input_text: Option<Vec<u8>>) -> Option<JoinHandle<()>> {
+    match (child.stdin.take(), input_text) {
+        (Some(mut stdin), Some(text)) => Some(thread::spawn(move || {
+            // The child may exit without reading all of its input, which isn't an error for us
+            let _ = stdin.write_all(text.as_slice());
+        })),
+        _ => None,
+    }
+}
+
+/// Connect a command's stdout and stderr to a new pseudo-terminal, so that tools keep their
+/// colored and progress output.  Returns the master end of the terminal to read output from.
+#[cfg(target_os = "linux")]
+fn attach_pty(cmd: &mut Command) -> mlua::Result<Option<File>> {
+    let pty = open_pty().map_err(|e| Error::runtime(format!("Error opening pty: {}", e)))?;
+    let slave_stderr = pty
+        .slave
+        .try_clone()
+        .map_err(|e| Error::runtime(format!("Error opening pty: {}", e)))?;
+    cmd.stdout(Stdio::from(pty.slave));
+    cmd.stderr(Stdio::from(slave_stderr));
+    Ok(Some(pty.master))
+}
+
+/// Pseudo-terminals are only supported on Linux.  Elsewhere, commands always run with pipes.
+#[cfg(not(target_os = "linux"))]
+fn attach_pty(_cmd: &mut Command) -> mlua::Result<Option<File>> {
+    Ok(None)
+}
+
+fn spawn_output_reader<R: Read + Send + 'static>(
+    mut reader: R,
+    tx: Sender<ChildMessage>,
+    to_message: fn(String) -> ChildMessage,
+    done_message: ChildMessage,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut buf: Vec<u8> = Vec::with_capacity(256);
+        buf.resize(256, 0);
+        let mut start_from = 0usize;
+        loop {
+            let res = reader.read(&mut buf[start_from..]);
+            match res {
+                Ok(bytes_read) => {
+                    if bytes_read == 0 {
+                        break;
                     }
-                }
-                stdout_tx.send(ChildMessage::StdoutDone).unwrap();
-            });
-
-            let stderr_tx = tx.clone();
-            let mut stderr = child.stderr.take().unwrap();
-            let stderr_thread = thread::spawn(move || {
-                let mut buf: Vec<u8> = Vec::with_capacity(256);
-                buf.resize(256, 0);
-                let mut start_from = 0usize;
-                loop {
-                    let res = stderr.read(&mut buf);
-                    match res {
-                        Ok(bytes_read) => {
-                            if bytes_read == 0 {
-                                break;
-                            }
-                            match std::str::from_utf8(&buf[start_from..(start_from+bytes_read)]) {
-                                Ok(err) => {
-                                    stderr_tx.send(ChildMessage::Stderr(String::from(err))).unwrap();
-                                    start_from = 0;
-                                },
-                                Err(_) => {
-                                    start_from = start_from + bytes_read;
-                                    if start_from >= buf.len() {
-                                        buf.resize(buf.len()*2, 0);
-                                    }
-                                }
-                            }
+                    match std::str::from_utf8(&buf[..(start_from + bytes_read)]) {
+                        Ok(out) => {
+                            // The receiving end may already be gone if a spawned process was killed
+                            let _ = tx.send(to_message(String::from(out)));
+                            start_from = 0;
                         }
                         Err(_) => {
-                            break;
+                            start_from = start_from + bytes_read;
+                            if start_from >= buf.len() {
+                                buf.resize(buf.len() * 2, 0);
+                            }
                         }
                     }
                 }
-                stderr_tx.send(ChildMessage::StderrDone).unwrap();
-            });
+                Err(_) => {
+                    break;
+                }
+            }
+        }
+        if start_from > 0 {
+            // Don't drop output that never formed valid utf-8
+            let _ = tx.send(to_message(String::from_utf8_lossy(&buf[..start_from]).into_owned()));
+        }
+        let _ = tx.send(done_message);
+    })
+}
 
-            let mut stdout_buf = String::new();
-            let mut stdout_done = false;
+fn spawn_output_readers(
+    child: &mut Child,
+) -> (Receiver<ChildMessage>, JoinHandle<()>, JoinHandle<()>) {
+    let (tx, rx) = channel();
 
-            let mut stderr_buf = String::new();
-            let mut stderr_done = false;
+    let stdout = child.stdout.take().unwrap();
+    let stdout_thread = spawn_output_reader(
+        stdout,
+        tx.clone(),
+        ChildMessage::Stdout,
+        ChildMessage::StdoutDone,
+    );
 
-            while !stdout_done || !stderr_done {
-                let msg = rx.recv().unwrap();
+    let stderr = child.stderr.take().unwrap();
+    let stderr_thread =
+        spawn_output_reader(stderr, tx, ChildMessage::Stderr, ChildMessage::StderrDone);
 
-                match msg {
-                    ChildMessage::Stdout(out) => {
-                        stdout_buf.push_str(out.as_str());
-                        if let Some(out_fn) = &out_func {
-                            out_fn.call(out)?;
-                        }
-                    }
-                    ChildMessage::StdoutDone => {
-                        stdout_done = true;
-                    }
-                    ChildMessage::Stderr(err) => {
-                        stderr_buf.push_str(err.as_str());
-                        if let Some(err_fn) = &err_func {
-                            err_fn.call(err)?;
-                        }
-                    }
-                    ChildMessage::StderrDone => {
-                        stderr_done = true;
-                    }
+    (rx, stdout_thread, stderr_thread)
+}
+
+/// Receive output from child processes until all of their output streams are closed, forwarding it
+/// to the out and err callbacks.  Returns the collected stdout and stderr output.
+fn receive_output(
+    rx: &Receiver<ChildMessage>,
+    stdout_streams: usize,
+    stderr_streams: usize,
+    options: &CmdOptions,
+) -> mlua::Result<(String, String)> {
+    let mut stdout_buf = String::new();
+    let mut stdout_done = 0;
+
+    let mut stderr_buf = String::new();
+    let mut stderr_done = 0;
+
+    while stdout_done < stdout_streams || stderr_done < stderr_streams {
+        let msg = rx.recv().unwrap();
+
+        match msg {
+            ChildMessage::Stdout(out) => {
+                stdout_buf.push_str(out.as_str());
+                if let Some(out_fn) = &options.out_func {
+                    out_fn.call(out)?;
+                }
+            }
+            ChildMessage::StdoutDone => {
+                stdout_done += 1;
+            }
+            ChildMessage::Stderr(err) => {
+                stderr_buf.push_str(err.as_str());
+                if let Some(err_fn) = &options.err_func {
+                    err_fn.call(err)?;
                 }
             }
+            ChildMessage::StderrDone => {
+                stderr_done += 1;
+            }
+        }
+    }
+
+    Ok((stdout_buf, stderr_buf))
+}
+
+fn exec_shell_command<'lua>(lua: &'lua Lua, args: Table<'lua>) -> mlua::Result<Table<'lua>> {
+    let cmd_args = parse_cmd_args(lua, args)?;
+    let mut cmd = build_command(lua, &cmd_args.cmd_with_args, &cmd_args.options)?;
+    let input_text = set_command_input(lua, &mut cmd, &cmd_args.options.input)?;
+
+    let use_pty = match cmd_args.options.pty {
+        Some(pty) => pty,
+        None => lua
+            .named_registry_value::<Option<bool>>(COBBLE_JOB_PTY_ENABLED)?
+            .unwrap_or(false),
+    };
+    let pty_master = if use_pty { attach_pty(&mut cmd)? } else { None };
+
+    let child_res = cmd.spawn();
+
+    // Close our copies of the pty slave, so that reading from the master ends when the child exits
+    drop(cmd);
+
+    match child_res {
+        Err(e) => Err(Error::runtime(format!("Error executing command '{}': {}", cmd_args.cmd_string(), e))),
+        Ok(mut child) => {
+            let input_thread = spawn_input_writer(&mut child, input_text);
+
+            // A terminal has a single output stream, so all output under a pty is reported as stdout
+            let (rx, reader_threads, stderr_streams) = match pty_master {
+                Some(master) => {
+                    let (tx, rx) = channel();
+                    let master_thread = spawn_output_reader(
+                        master,
+                        tx,
+                        ChildMessage::Stdout,
+                        ChildMessage::StdoutDone,
+                    );
+                    (rx, vec![master_thread], 0)
+                }
+                None => {
+                    let (rx, stdout_thread, stderr_thread) = spawn_output_readers(&mut child);
+                    (rx, vec![stdout_thread, stderr_thread], 1)
+                }
+            };
 
-            stdout_thread.join().unwrap();
-            stderr_thread.join().unwrap();
+            let (stdout_buf, stderr_buf) =
+                receive_output(&rx, 1, stderr_streams, &cmd_args.options)?;
+
+            for reader_thread in reader_threads {
+                reader_thread.join().unwrap();
+            }
+            if let Some(input_thread) = input_thread {
+                input_thread.join().unwrap();
+            }
 
             let status_res = child.wait();
             let status = match status_res {
@@ -238,3 +443,589 @@ fn exec_shell_command<'lua>(lua: &'lua Lua, args: Table<'lua>) -> mlua::Result<T
         }
     }
 }
+
+fn parse_pipe_args<'lua>(
+    lua: &'lua Lua,
+    args: Table<'lua>,
+) -> mlua::Result<(Vec<CmdArgs<'lua>>, CmdOptions<'lua>)> {
+    let mut stages: Vec<CmdArgs> = Vec::new();
+    let mut options = CmdOptions::default();
+
+    for pair in args.clone().pairs() {
+        let (k, v): (Value, Value) = pair?;
+        match k {
+            Value::Integer(_) => { /* stages are read in order below */ }
+            Value::String(s) => parse_cmd_option(lua, s, v, &mut options)?,
+            _ => {
+                return Err(Error::runtime(format!(
+                    "Key type not allowed in pipe input: {}",
+                    k.type_name()
+                )));
+            }
+        }
+    }
+
+    for (i, stage_res) in args.sequence_values::<Table>().enumerate() {
+        let stage = parse_cmd_args(lua, stage_res?)?;
+        if stage.options.out_func.is_some() || stage.options.err_func.is_some() {
+            return Err(Error::runtime(
+                "The 'out' and 'err' keys are only allowed on the pipeline, not on individual commands",
+            ));
+        }
+        if stage.options.pty.is_some() || options.pty.is_some() {
+            return Err(Error::runtime("The 'pty' key is not supported for pipelines"));
+        }
+        if i > 0 && stage.options.input.is_some() {
+            return Err(Error::runtime(
+                "Only the first command in a pipeline can be given input",
+            ));
+        }
+        stages.push(stage);
+    }
+
+    if stages.is_empty() {
+        return Err(Error::runtime("No commands given to pipe"));
+    }
+
+    Ok((stages, options))
+}
+
+/// The processes in a pipeline.  Any that are still running when the pipeline is dropped, (e.g.
+/// because starting a later command failed,) are killed and reaped.
+struct PipelineChildren(Vec<Child>);
+
+impl Drop for PipelineChildren {
+    fn drop(&mut self) {
+        for child in self.0.iter_mut() {
+            // Both do nothing for a process that has already been waited on
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// The status of a command in a pipeline.  Like in a shell, a command killed by a signal gets a
+/// status of 128 plus the signal number, so that every command has a status.
+fn pipeline_status_code(status: std::process::ExitStatus) -> i32 {
+    #[cfg(unix)]
+    if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+        return 128 + signal;
+    }
+    status.code().unwrap_or(-1)
+}
+
+fn exec_pipe_command<'lua>(lua: &'lua Lua, args: Table<'lua>) -> mlua::Result<Table<'lua>> {
+    let (stages, pipe_options) = parse_pipe_args(lua, args)?;
+
+    let pipe_string = stages
+        .iter()
+        .map(|s| s.cmd_string())
+        .collect::<Vec<String>>()
+        .join(" | ");
+
+    let (tx, rx) = channel();
+    let mut children = PipelineChildren(Vec::with_capacity(stages.len()));
+    let mut reader_threads: Vec<JoinHandle<()>> = Vec::with_capacity(stages.len() + 1);
+    let mut input_thread: Option<JoinHandle<()>> = None;
+    let mut prev_stdout: Option<ChildStdout> = None;
+
+    for (i, stage) in stages.iter().enumerate() {
+        // Individual commands inherit the pipeline's options, but can override them
+        let mut env = pipe_options.env.clone();
+        env.extend(stage.options.env.iter().map(|(k, v)| (k.clone(), v.clone())));
+        let stage_options = CmdOptions {
+            cwd: stage.options.cwd.clone().or_else(|| pipe_options.cwd.clone()),
+            env,
+            clear_env: stage.options.clear_env || pipe_options.clear_env,
+            ..Default::default()
+        };
+
+        let mut cmd = build_command(lua, &stage.cmd_with_args, &stage_options)?;
+        let input_text = match prev_stdout.take() {
+            Some(stdout) => {
+                cmd.stdin(Stdio::from(stdout));
+                None
+            }
+            None => {
+                let input = match &stage.options.input {
+                    Some(_) => &stage.options.input,
+                    None => &pipe_options.input,
+                };
+                set_command_input(lua, &mut cmd, input)?
+            }
+        };
+
+        let mut child = cmd.spawn().map_err(|e| {
+            Error::runtime(format!(
+                "Error executing command '{}' in pipeline '{}': {}",
+                stage.cmd_string(),
+                pipe_string,
+                e
+            ))
+        })?;
+
+        if input_text.is_some() {
+            input_thread = spawn_input_writer(&mut child, input_text);
+        }
+
+        let stderr = child.stderr.take().unwrap();
+        reader_threads.push(spawn_output_reader(
+            stderr,
+            tx.clone(),
+            ChildMessage::Stderr,
+            ChildMessage::StderrDone,
+        ));
+
+        if i + 1 < stages.len() {
+            prev_stdout = child.stdout.take();
+        } else {
+            let stdout = child.stdout.take().unwrap();
+            reader_threads.push(spawn_output_reader(
+                stdout,
+                tx.clone(),
+                ChildMessage::Stdout,
+                ChildMessage::StdoutDone,
+            ));
+        }
+
+        children.0.push(child);
+    }
+    drop(tx);
+
+    let (stdout_buf, stderr_buf) = receive_output(&rx, 1, stages.len(), &pipe_options)?;
+
+    for reader_thread in reader_threads {
+        reader_thread.join().unwrap();
+    }
+    if let Some(input_thread) = input_thread {
+        input_thread.join().unwrap();
+    }
+
+    let statuses = lua.create_table()?;
+    for child in children.0.iter_mut() {
+        let status = child
+            .wait()
+            .map_err(|e| Error::runtime(format!("{}", e)))?;
+        statuses.push(pipeline_status_code(status))?;
+    }
+
+    let result = lua.create_table()?;
+    result.set("stdout", lua.create_string(stdout_buf)?)?;
+    result.set("stderr", lua.create_string(stderr_buf)?)?;
+    result.set("status", statuses.get::<_, Value>(statuses.len()?)?)?;
+    result.set("statuses", statuses)?;
+    Ok(result)
+}
+
+/// A handle to a background process started with `cmd.spawn`
+pub struct SpawnedProcess {
+    pid: u32,
+    cmd_string: String,
+    child: Arc<Mutex<SpawnedChild>>,
+    output_rx: Receiver<ChildMessage>,
+    out_func: Option<RegistryKey>,
+    err_func: Option<RegistryKey>,
+    stdout_buf: String,
+    stderr_buf: String,
+    stdout_done: bool,
+    stderr_done: bool,
+    status: Option<Option<i32>>,
+}
+
+impl SpawnedProcess {
+    fn handle_output_message(&mut self, lua: &Lua, msg: ChildMessage) -> mlua::Result<()> {
+        match msg {
+            ChildMessage::Stdout(out) => {
+                self.stdout_buf.push_str(out.as_str());
+                if let Some(out_fn_key) = &self.out_func {
+                    lua.registry_value::<Function>(out_fn_key)?.call(out)?;
+                }
+            }
+            ChildMessage::StdoutDone => {
+                self.stdout_done = true;
+            }
+            ChildMessage::Stderr(err) => {
+                self.stderr_buf.push_str(err.as_str());
+                if let Some(err_fn_key) = &self.err_func {
+                    lua.registry_value::<Function>(err_fn_key)?.call(err)?;
+                }
+            }
+            ChildMessage::StderrDone => {
+                self.stderr_done = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Forward any output received from the process so far
+    fn poll_output(&mut self, lua: &Lua) -> mlua::Result<()> {
+        while let Ok(msg) = self.output_rx.try_recv() {
+            self.handle_output_message(lua, msg)?;
+        }
+        Ok(())
+    }
+
+    /// Wait up to `timeout` for more output from the process.  Returns false if no more output
+    /// will be received.
+    fn wait_for_output_message(&mut self, lua: &Lua, timeout: Duration) -> mlua::Result<bool> {
+        if self.stdout_done && self.stderr_done {
+            thread::sleep(timeout);
+            return Ok(false);
+        }
+
+        match self.output_rx.recv_timeout(timeout) {
+            Ok(msg) => {
+                self.handle_output_message(lua, msg)?;
+                Ok(true)
+            }
+            Err(RecvTimeoutError::Timeout) => Ok(true),
+            Err(RecvTimeoutError::Disconnected) => {
+                self.stdout_done = true;
+                self.stderr_done = true;
+                Ok(false)
+            }
+        }
+    }
+
+    fn try_status(&mut self) -> mlua::Result<Option<Option<i32>>> {
+        if self.status.is_none() {
+            self.status = self
+                .child
+                .lock()
+                .unwrap()
+                .try_status()
+                .map_err(|e| Error::runtime(format!("Error checking process status: {}", e)))?;
+        }
+        Ok(self.status)
+    }
+
+    fn wait(&mut self, lua: &Lua) -> mlua::Result<Option<i32>> {
+        loop {
+            self.poll_output(lua)?;
+            if let Some(status) = self.try_status()? {
+                // Drain any output that was written before the process exited.  Processes that it
+                // started may hold its output streams open, so stop once no more output arrives.
+                while !self.stdout_done || !self.stderr_done {
+                    match self.output_rx.recv_timeout(WAIT_POLL_INTERVAL) {
+                        Ok(msg) => self.handle_output_message(lua, msg)?,
+                        Err(_) => break,
+                    }
+                }
+                return Ok(status);
+            }
+            self.wait_for_output_message(lua, WAIT_POLL_INTERVAL)?;
+        }
+    }
+
+    fn kill(&mut self, lua: &Lua) -> mlua::Result<()> {
+        let status = self
+            .child
+            .lock()
+            .unwrap()
+            .kill()
+            .map_err(|e| Error::runtime(format!("Error waiting for killed process: {}", e)))?;
+        self.status = Some(status);
+        untrack_spawned_process(self.pid);
+        self.poll_output(lua)
+    }
+
+    fn exited_error(&self, waiting_for: &str) -> Error {
+        Error::runtime(format!(
+            "Process '{}' exited with status {} while waiting for {}",
+            self.cmd_string,
+            self.status
+                .flatten()
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| String::from("<none>")),
+            waiting_for
+        ))
+    }
+
+    fn timeout_error(&self, waiting_for: &str, timeout: Duration) -> Error {
+        Error::runtime(format!(
+            "Timed out after {:.1}s waiting for {} from process '{}'",
+            timeout.as_secs_f64(),
+            waiting_for,
+            self.cmd_string
+        ))
+    }
+
+    fn wait_for_text(&mut self, lua: &Lua, text: &str, timeout: Duration) -> mlua::Result<()> {
+        let waiting_for = format!("output '{}'", text);
+        let start = Instant::now();
+        loop {
+            self.poll_output(lua)?;
+            if self.stdout_buf.contains(text) || self.stderr_buf.contains(text) {
+                return Ok(());
+            }
+            if self.try_status()?.is_some() && self.stdout_done && self.stderr_done {
+                return Err(self.exited_error(waiting_for.as_str()));
+            }
+            if start.elapsed() >= timeout {
+                return Err(self.timeout_error(waiting_for.as_str(), timeout));
+            }
+            self.wait_for_output_message(lua, WAIT_POLL_INTERVAL)?;
+        }
+    }
+
+    fn wait_for_port(
+        &mut self,
+        lua: &Lua,
+        host: &str,
+        port: u16,
+        timeout: Duration,
+    ) -> mlua::Result<()> {
+        let waiting_for = format!("port {}:{}", host, port);
+        let addrs: Vec<SocketAddr> = (host, port)
+            .to_socket_addrs()
+            .map_err(|e| Error::runtime(format!("Unable to resolve {}: {}", host, e)))?
+            .collect();
+
+        let start = Instant::now();
+        loop {
+            self.poll_output(lua)?;
+            let connected = addrs
+                .iter()
+                .any(|addr| TcpStream::connect_timeout(addr, WAIT_POLL_INTERVAL).is_ok());
+            if connected {
+                return Ok(());
+            }
+            if self.try_status()?.is_some() {
+                return Err(self.exited_error(waiting_for.as_str()));
+            }
+            if start.elapsed() >= timeout {
+                return Err(self.timeout_error(waiting_for.as_str(), timeout));
+            }
+            self.wait_for_output_message(lua, WAIT_POLL_INTERVAL)?;
+        }
+    }
+}
+
+fn timeout_from_secs(timeout_secs: Option<f64>) -> mlua::Result<Duration> {
+    match timeout_secs {
+        Some(secs) => Duration::try_from_secs_f64(secs)
+            .map_err(|e| Error::runtime(format!("Invalid timeout: {}", e))),
+        None => Ok(Duration::from_secs(30)),
+    }
+}
+
+impl UserData for SpawnedProcess {
+    fn add_fields<'lua, F: mlua::prelude::LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_field_method_get("pid", |_lua, this| Ok(this.pid));
+    }
+
+    fn add_methods<'lua, M: mlua::prelude::LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method_mut("wait", |lua, this, ()| {
+            let status = this.wait(lua)?;
+            let result = lua.create_table()?;
+            result.set("stdout", this.stdout_buf.as_str())?;
+            result.set("stderr", this.stderr_buf.as_str())?;
+            result.set("status", status)?;
+            Ok(result)
+        });
+        methods.add_method_mut("kill", |lua, this, ()| this.kill(lua));
+        methods.add_method_mut("is_running", |lua, this, ()| {
+            this.poll_output(lua)?;
+            Ok(this.try_status()?.is_none())
+        });
+        methods.add_method_mut(
+            "wait_for_output",
+            |lua, this, (text, timeout_secs): (String, Option<f64>)| {
+                let timeout = timeout_from_secs(timeout_secs)?;
+                this.wait_for_text(lua, text.as_str(), timeout)
+            },
+        );
+        methods.add_method_mut(
+            "wait_for_port",
+            |lua, this, (port, timeout_secs, host): (u16, Option<f64>, Option<String>)| {
+                let timeout = timeout_from_secs(timeout_secs)?;
+                let host = host.unwrap_or_else(|| String::from("127.0.0.1"));
+                this.wait_for_port(lua, host.as_str(), port, timeout)
+            },
+        );
+    }
+}
+
+/// A process started with `cmd.spawn`.  On unix, spawned processes lead their own process group.
+/// On linux, an exited process isn't reaped until it is killed, so that its process group id
+/// can't be reused by an unrelated process group before the processes it started are killed.
+struct SpawnedChild {
+    child: Child,
+    reaped: bool,
+}
+
+impl SpawnedChild {
+    /// Returns the exit code of the process if it has exited, (None if it was killed by a signal)
+    #[cfg(target_os = "linux")]
+    fn try_status(&mut self) -> std::io::Result<Option<Option<i32>>> {
+        if self.reaped {
+            return Ok(self.child.try_wait()?.map(|s| s.code()));
+        }
+
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        let res = unsafe {
+            libc::waitid(
+                libc::P_PID,
+                self.child.id() as libc::id_t,
+                &mut info,
+                libc::WEXITED | libc::WNOHANG | libc::WNOWAIT,
+            )
+        };
+        if res != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        if unsafe { info.si_pid() } == 0 {
+            Ok(None)
+        } else if info.si_code == libc::CLD_EXITED {
+            Ok(Some(Some(unsafe { info.si_status() })))
+        } else {
+            Ok(Some(None))
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn try_status(&mut self) -> std::io::Result<Option<Option<i32>>> {
+        let status = self.child.try_wait()?;
+        if status.is_some() {
+            self.reaped = true;
+        }
+        Ok(status.map(|s| s.code()))
+    }
+
+    /// Kill the process, along with any processes it started, (e.g. a server started through
+    /// "sh -c",) even if the process itself has already exited.  Once the process has been
+    /// reaped, its process group id may belong to another process group, so only the process
+    /// itself is killed, which does nothing for an exited process.
+    fn kill(&mut self) -> std::io::Result<Option<i32>> {
+        #[cfg(unix)]
+        if !self.reaped {
+            unsafe {
+                libc::kill(-(self.child.id() as libc::pid_t), libc::SIGKILL);
+            }
+        }
+        // The process may have already exited
+        let _ = self.child.kill();
+
+        let status = self.child.wait()?;
+        self.reaped = true;
+        Ok(status.code())
+    }
+}
+
+fn track_spawned_process(pid: u32, child: Arc<Mutex<SpawnedChild>>) {
+    SPAWNED_PROCESSES
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .insert(pid, child);
+}
+
+fn untrack_spawned_process(pid: u32) {
+    if let Some(processes) = SPAWNED_PROCESSES.lock().unwrap().as_mut() {
+        processes.remove(&pid);
+    }
+}
+
+fn spawn_shell_command<'lua>(lua: &'lua Lua, args: Table<'lua>) -> mlua::Result<AnyUserData<'lua>> {
+    let cmd_args = parse_cmd_args(lua, args)?;
+    let mut cmd = build_command(lua, &cmd_args.cmd_with_args, &cmd_args.options)?;
+
+    // Background processes never attach to cobble's stdin, since they outlive the code that started them
+    let input_text = match &cmd_args.options.input {
+        Some(_) => set_command_input(lua, &mut cmd, &cmd_args.options.input)?,
+        None => {
+            cmd.stdin(Stdio::null());
+            None
+        }
+    };
+
+    // Start the process in its own process group, so that the processes it starts can be killed
+    // along with it
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+
+    let mut child = cmd.spawn().map_err(|e| {
+        Error::runtime(format!("Error executing command '{}': {}", cmd_args.cmd_string(), e))
+    })?;
+
+    let pid = child.id();
+    // The input writer thread exits on its own once the input is written or the process exits
+    spawn_input_writer(&mut child, input_text);
+    // The reader threads exit on their own once the process's output streams are closed
+    let (output_rx, _stdout_thread, _stderr_thread) = spawn_output_readers(&mut child);
+    let child = Arc::new(Mutex::new(SpawnedChild {
+        child,
+        reaped: false,
+    }));
+    track_spawned_process(pid, child.clone());
+
+    let out_func = match cmd_args.options.out_func {
+        Some(f) => Some(lua.create_registry_value(f)?),
+        None => None,
+    };
+    let err_func = match cmd_args.options.err_func {
+        Some(f) => Some(lua.create_registry_value(f)?),
+        None => None,
+    };
+
+    let process = lua.create_userdata(SpawnedProcess {
+        pid,
+        cmd_string: cmd_args.cmd_with_args.join(" "),
+        child,
+        output_rx,
+        out_func,
+        err_func,
+        stdout_buf: String::new(),
+        stderr_buf: String::new(),
+        stdout_done: false,
+        stderr_done: false,
+        status: None,
+    })?;
+
+    // Keep track of processes spawned during the current job so they can be killed when it ends
+    let job_processes: Option<Table> = lua.named_registry_value(COBBLE_JOB_SPAWNED_PROCESSES)?;
+    let job_processes = match job_processes {
+        Some(t) => t,
+        None => {
+            let t = lua.create_table()?;
+            lua.set_named_registry_value(COBBLE_JOB_SPAWNED_PROCESSES, t.clone())?;
+            t
+        }
+    };
+    job_processes.push(process.clone())?;
+
+    Ok(process)
+}
+
+/// Kill any processes spawned with `cmd.spawn` in the given lua environment that are still running
+pub fn kill_spawned_processes(lua: &Lua) -> mlua::Result<()> {
+    let job_processes: Option<Table> = lua.named_registry_value(COBBLE_JOB_SPAWNED_PROCESSES)?;
+    lua.unset_named_registry_value(COBBLE_JOB_SPAWNED_PROCESSES)?;
+
+    let mut result = Ok(());
+    if let Some(processes) = job_processes {
+        for process_res in processes.sequence_values::<AnyUserData>() {
+            let kill_res = process_res
+                .and_then(|process| process.borrow_mut::<SpawnedProcess>()?.kill(lua));
+            // Make sure every process gets killed, even if killing one of them fails
+            if result.is_ok() {
+                result = kill_res;
+            }
+        }
+    }
+    result
+}
+
+/// Kill every process spawned with `cmd.spawn` that is still running, in any lua environment.
+/// Used to clean up background processes when cobble is interrupted.
+pub fn kill_all_spawned_processes() {
+    let processes = SPAWNED_PROCESSES.lock().unwrap().take();
+    if let Some(processes) = processes {
+        for (_pid, child) in processes {
+            if let Ok(mut child) = child.lock() {
+                let _ = child.kill();
+            }
+        }
+    }
+}
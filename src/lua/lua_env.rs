@@ -15,6 +15,10 @@ use crate::lua::script_dir::ScriptDirLib;
 use crate::lua::toml::TomlLib;
 
 pub const COBBLE_JOB_INTERACTIVE_ENABLED: &str = "COBBLE_JOB_INTERACTIVE_ENABLED";
+pub const COBBLE_JOB_ENV_VARS: &str = "COBBLE_JOB_ENV_VARS";
+pub const COBBLE_ENV_PASSTHROUGH: &str = "COBBLE_ENV_PASSTHROUGH";
+pub const COBBLE_JOB_PTY_ENABLED: &str = "COBBLE_JOB_PTY_ENABLED";
+pub const COBBLE_JOB_SPAWNED_PROCESSES: &str = "COBBLE_JOB_SPAWNED_PROCESSES";
 
 pub fn create_lua_env(workspace_dir: &Path) -> mlua::Result<Lua> {
     let lua = unsafe { Lua::unsafe_new() };
@@ -96,6 +100,9 @@ pub fn create_lua_env(workspace_dir: &Path) -> mlua::Result<Lua> {
 
 #[cfg(test)]
 mod tests {
+    #[cfg(unix)]
+    use std::collections::HashMap;
+
     #[cfg(unix)]
     use super::*;
 
@@ -109,4 +116,177 @@ mod tests {
         assert_eq!(result.get::<_, i32>("status").unwrap(), 0);
         assert_eq!(result.get::<_, String>("stdout").unwrap(), "hi!\n");
     }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_shell_command_env() {
+        let lua_env = create_lua_env(Path::new(".")).unwrap();
+        let job_env_vars: HashMap<&str, &str> = vec![("JOB_VAR", "job")].into_iter().collect();
+        lua_env
+            .set_named_registry_value(COBBLE_JOB_ENV_VARS, job_env_vars)
+            .unwrap();
+        let chunk = lua_env.load(r#"
+            require("cmd")({"/bin/sh", "-c", "echo \"$JOB_VAR $CMD_VAR $HOME\"", env = { CMD_VAR = "cmd" }, clear_env = true})
+        "#);
+
+        let result: Table = chunk.eval().unwrap();
+        assert_eq!(result.get::<_, i32>("status").unwrap(), 0);
+        assert_eq!(result.get::<_, String>("stdout").unwrap(), "job cmd \n");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_shell_command_input() {
+        let lua_env = create_lua_env(Path::new(".")).unwrap();
+        let chunk = lua_env.load(r#"require("cmd")({"cat", input = "some input"})"#);
+
+        let result: Table = chunk.eval().unwrap();
+        assert_eq!(result.get::<_, i32>("status").unwrap(), 0);
+        assert_eq!(result.get::<_, String>("stdout").unwrap(), "some input");
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_shell_command_pty() {
+        let lua_env = create_lua_env(Path::new(".")).unwrap();
+        let chunk = lua_env.load(r#"
+            require("cmd")({"/bin/sh", "-c", "test -t 1 && test -t 2 && echo tty", pty = true})
+        "#);
+
+        let result: Table = chunk.eval().unwrap();
+        assert_eq!(result.get::<_, i32>("status").unwrap(), 0);
+        assert_eq!(result.get::<_, String>("stdout").unwrap(), "tty\n");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_pipe_command() {
+        let lua_env = create_lua_env(Path::new(".")).unwrap();
+        let chunk = lua_env.load(r#"
+            require("cmd").pipe({
+                {"cat"},
+                {"sort"},
+                {"head", "-n", "2"},
+                input = "c\nb\na\n"
+            })
+        "#);
+
+        let result: Table = chunk.eval().unwrap();
+        assert_eq!(result.get::<_, i32>("status").unwrap(), 0);
+        assert_eq!(result.get::<_, Vec<i32>>("statuses").unwrap(), vec![0, 0, 0]);
+        assert_eq!(result.get::<_, String>("stdout").unwrap(), "a\nb\n");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_pipe_command_killed_by_signal() {
+        let lua_env = create_lua_env(Path::new(".")).unwrap();
+        let chunk = lua_env.load(r#"
+            require("cmd").pipe({
+                {"/bin/sh", "-c", "kill -9 $$"},
+                {"cat"},
+            })
+        "#);
+
+        let result: Table = chunk.eval().unwrap();
+        assert_eq!(result.get::<_, Vec<i32>>("statuses").unwrap(), vec![137, 0]);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_pipe_command_error_kills_commands() {
+        let lua_env = create_lua_env(Path::new(".")).unwrap();
+        let chunk = lua_env.load(r#"
+            local pid
+            local ok = pcall(require("cmd").pipe, {
+                {"/bin/sh", "-c", "echo $$ >&2; exec sleep 100"},
+                {"cat"},
+                err = function (s) pid = s; error("callback failed") end
+            })
+            assert(not ok)
+            return pid
+        "#);
+
+        let output: String = chunk.eval().unwrap();
+        assert_process_killed(output.trim().parse().unwrap());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_spawn_command() {
+        let lua_env = create_lua_env(Path::new(".")).unwrap();
+        let chunk = lua_env.load(r#"
+            local cmd = require("cmd")
+            local proc = cmd.spawn({"/bin/sh", "-c", "echo ready; sleep 30"})
+            proc:wait_for_output("ready", 5)
+            local was_running = proc:is_running()
+            return proc, was_running
+        "#);
+
+        let (proc, was_running): (mlua::AnyUserData, bool) = chunk.eval().unwrap();
+        assert!(was_running);
+
+        crate::lua::cmd::kill_spawned_processes(&lua_env).unwrap();
+        let is_running: bool = lua_env
+            .load("local proc = ...; return proc:is_running()")
+            .call(proc)
+            .unwrap();
+        assert!(!is_running);
+    }
+
+    /// Wait for a process to be killed.  An orphaned process may take a moment to be cleaned up,
+    /// and may be left as a zombie if nothing reaps it.
+    #[cfg(target_os = "linux")]
+    fn assert_process_killed(pid: u32) {
+        let is_running = || match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+            Ok(stat) => !stat.contains(") Z "),
+            Err(_) => false,
+        };
+        let start = std::time::Instant::now();
+        while is_running() && start.elapsed() < std::time::Duration::from_secs(5) {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        assert!(!is_running());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_kill_spawned_process_group() {
+        let lua_env = create_lua_env(Path::new(".")).unwrap();
+        let chunk = lua_env.load(r#"
+            local cmd = require("cmd")
+            local output = ""
+            local proc = cmd.spawn({
+                "/bin/sh", "-c", "sleep 100 & echo $!; wait",
+                out = function (s) output = output .. s end
+            })
+            proc:wait_for_output("\n", 5)
+            proc:kill()
+            return output
+        "#);
+
+        let output: String = chunk.eval().unwrap();
+        assert_process_killed(output.trim().parse().unwrap());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_kill_exited_spawned_process_group() {
+        let lua_env = create_lua_env(Path::new(".")).unwrap();
+        let chunk = lua_env.load(r#"
+            local cmd = require("cmd")
+            local proc = cmd.spawn({ "/bin/sh", "-c", "sleep 100 > /dev/null 2>&1 & echo $!; exit 3" })
+            local result = proc:wait()
+            -- The processes it started are still killed after it exits, and killing it again
+            -- does nothing
+            proc:kill()
+            proc:kill()
+            assert(not proc:is_running())
+            return result.stdout, result.status
+        "#);
+
+        let (output, status): (String, i32) = chunk.eval().unwrap();
+        assert_eq!(status, 3);
+        assert_process_killed(output.trim().parse().unwrap());
+    }
 }
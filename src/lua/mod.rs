@@ -7,8 +7,10 @@ pub mod detached;
 pub mod lua_env;
 pub mod userdata;
 
-mod cmd;
+pub mod cmd;
 mod path;
+#[cfg(target_os = "linux")]
+mod pty;
 mod json;
 mod script_dir;
 mod toml;
@@ -0,0 +1,97 @@
+// Cobble Build Automation
+// Copyright (C) 2024 Jeremiah Darais
+//
+// This program is licensed under the GPLv3.0 license (https://github.com/jdarais/cobble/blob/main/COPYING)
+
+use std::fs::File;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+const DEFAULT_PTY_ROWS: u16 = 24;
+const DEFAULT_PTY_COLS: u16 = 80;
+
+/// A pseudo-terminal for running a child process in.  The child gets the slave end as its stdout
+/// and stderr, and its output is read from the master end.
+pub struct Pty {
+    pub master: File,
+    pub slave: OwnedFd,
+}
+
+/// Use the size of the terminal cobble is running in, if there is one, so that tools format
+/// progress output for the width it will actually be displayed at
+fn current_terminal_size() -> libc::winsize {
+    let mut size = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    let res = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    if res != 0 || size.ws_row == 0 || size.ws_col == 0 {
+        size.ws_row = DEFAULT_PTY_ROWS;
+        size.ws_col = DEFAULT_PTY_COLS;
+    }
+    size
+}
+
+/// openpty doesn't set close-on-exec, so without it, a child process that another worker thread
+/// starts at the same time would inherit the pty.  A child holding the slave open keeps reading
+/// from the master from ever ending.
+fn set_cloexec(fd: libc::c_int) -> io::Result<()> {
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+    if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFD, flags | libc::FD_CLOEXEC) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+pub fn open_pty() -> io::Result<Pty> {
+    let mut master_fd: libc::c_int = -1;
+    let mut slave_fd: libc::c_int = -1;
+    let size = current_terminal_size();
+
+    let res = unsafe {
+        libc::openpty(
+            &mut master_fd,
+            &mut slave_fd,
+            std::ptr::null_mut(),
+            std::ptr::null(),
+            &size,
+        )
+    };
+    if res != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let master = unsafe { File::from_raw_fd(master_fd) };
+    let slave = unsafe { OwnedFd::from_raw_fd(slave_fd) };
+    set_cloexec(master.as_raw_fd())?;
+    set_cloexec(slave.as_raw_fd())?;
+
+    // Don't translate "\n" into "\r\n", since output is forwarded to cobble's own stdout, which
+    // does its own translation if it is a terminal
+    let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(slave_fd, &mut termios) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    termios.c_oflag &= !libc::ONLCR;
+    if unsafe { libc::tcsetattr(slave_fd, libc::TCSANOW, &termios) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(Pty { master, slave })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_pty_cloexec() {
+        let pty = open_pty().unwrap();
+        for fd in [pty.master.as_raw_fd(), pty.slave.as_raw_fd()] {
+            let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+            assert!(flags & libc::FD_CLOEXEC != 0);
+        }
+    }
+}
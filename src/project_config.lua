@@ -61,6 +61,7 @@ function start_project (name, dir)
         build_envs = {},
         tasks = {},
         tools = {},
+        vars = {},
         child_projects = {},
         project_source_deps = project_source_deps
     }
@@ -98,6 +99,12 @@ function tool (tl)
     table.insert(PROJECT.tools, tl)
 end
 
+function var (v)
+    local status, err = pcall(cxt.validate_var, v)
+    if not status then error(err, 1) end
+    table.insert(PROJECT.vars, v)
+end
+
 function task (tsk)
     local status, err = pcall(cxt.validate_task, tsk)
     if not status then error(err, 1) end
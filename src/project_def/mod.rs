@@ -12,6 +12,7 @@ pub mod task;
 pub mod tool;
 pub mod types;
 pub mod validate;
+pub mod var;
 
 #[allow(unused_imports)]
 pub use action::{Action, ActionCmd};
@@ -21,3 +22,4 @@ pub use dependency::{Dependencies, DependencyListByType};
 pub use project::Project;
 pub use task::TaskDef;
 pub use tool::ExternalTool;
+pub use var::VarDef;
@@ -8,7 +8,7 @@ use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
 
-use crate::project_def::{BuildEnvDef, ExternalTool, TaskDef};
+use crate::project_def::{BuildEnvDef, ExternalTool, TaskDef, VarDef};
 
 #[derive(Debug)]
 pub struct Project {
@@ -17,6 +17,7 @@ pub struct Project {
     pub build_envs: Vec<BuildEnvDef>,
     pub tasks: Vec<TaskDef>,
     pub tools: Vec<ExternalTool>,
+    pub vars: Vec<VarDef>,
     pub child_project_names: Vec<Arc<str>>,
     pub project_source_deps: Vec<Arc<str>>,
 }
@@ -54,6 +55,15 @@ impl fmt::Display for Project {
         }
         f.write_str("],")?;
 
+        f.write_str("vars=[")?;
+        for (i, var) in self.vars.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", var)?;
+        }
+        f.write_str("],")?;
+
         f.write_str("child_projects=[")?;
         for (i, proj) in self.child_project_names.iter().enumerate() {
             if i > 0 {
@@ -98,6 +108,7 @@ impl<'lua> mlua::FromLua<'lua> for Project {
         let build_envs: Vec<BuildEnvDef> = project_table.get("build_envs")?;
         let tasks: Vec<TaskDef> = project_table.get("tasks")?;
         let tools: Vec<ExternalTool> = project_table.get("tools")?;
+        let vars: Vec<VarDef> = project_table.get("vars")?;
 
         let child_projects: Vec<mlua::Table> = project_table.get("child_projects")?;
         let mut child_project_names: Vec<Arc<str>> = Vec::with_capacity(child_projects.len());
@@ -118,6 +129,7 @@ impl<'lua> mlua::FromLua<'lua> for Project {
             build_envs,
             tasks,
             tools,
+            vars,
             child_project_names,
             project_source_deps,
         })
@@ -12,23 +12,29 @@ use crate::project_def::dependency::{validate_dep_list, Dependencies};
 use crate::project_def::artifact::{validate_artifacts, Artifacts};
 use crate::project_def::validate::{
     key_validation_error, push_prop_name_if_exists, validate_is_bool, validate_is_string,
-    validate_is_table, validate_required_key,
+    validate_is_table, validate_required_key, validate_table_is_sequence,
 };
 use crate::project_def::Action;
 
 #[derive(Clone, Debug)]
 pub struct TaskDef {
     pub name: Arc<str>,
+    pub description: Option<Arc<str>>,
+    pub group: Option<Arc<str>>,
     pub is_default: Option<bool>,
+    pub is_private: Option<bool>,
     pub always_run: Option<bool>,
     pub is_interactive: Option<bool>,
+    pub pty: Option<bool>,
     pub show_stdout: Option<TaskOutputCondition>,
     pub show_stderr: Option<TaskOutputCondition>,
     pub build_env: Option<(Arc<str>, Arc<str>)>,
+    pub env_vars: HashMap<String, String>,
     pub actions: Vec<Action>,
     pub clean: Vec<Action>,
     pub deps: Dependencies,
     pub artifacts: Artifacts,
+    pub tags: Vec<Arc<str>>,
 }
 
 fn validate_output_condition<'lua>(
@@ -66,6 +72,42 @@ fn validate_env_table<'lua>(
     Ok(())
 }
 
+fn validate_tags(
+    prop_name: Option<Cow<'static, str>>,
+    value: &mlua::Value,
+    prop_path: &mut Vec<Cow<'static, str>>,
+) -> mlua::Result<()> {
+    let mut prop_path = push_prop_name_if_exists(prop_name, prop_path);
+    let table = validate_is_table(value, None, prop_path.as_mut())?;
+    validate_table_is_sequence(table, None, prop_path.as_mut())?;
+    for tag in table.clone().sequence_values::<mlua::Value>() {
+        let tag = tag?;
+        let tag_str = validate_is_string(&tag, None, prop_path.as_mut())?;
+        if tag_str.to_str()?.contains(|ch: char| ch.is_whitespace()) {
+            return Err(mlua::Error::runtime(format!(
+                "Tags cannot contain whitespace: \"{}\"",
+                tag_str.to_str()?
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn validate_env_vars_table(
+    prop_name: Option<Cow<'static, str>>,
+    value: &mlua::Value,
+    prop_path: &mut Vec<Cow<'static, str>>,
+) -> mlua::Result<()> {
+    let mut prop_path = push_prop_name_if_exists(prop_name, prop_path);
+    let table = validate_is_table(value, None, prop_path.as_mut())?;
+    for pair in table.clone().pairs() {
+        let (var_name, var_value): (mlua::Value, mlua::Value) = pair?;
+        validate_is_string(&var_name, None, prop_path.as_mut())?;
+        validate_is_string(&var_value, None, prop_path.as_mut())?;
+    }
+    Ok(())
+}
+
 pub fn validate_inline_task<'lua>(
     lua: &'lua mlua::Lua,
     prop_name: Option<Cow<'static, str>>,
@@ -85,9 +127,19 @@ pub fn validate_inline_task<'lua>(
             "name" => {
                 validate_is_string(&v, Some(Cow::Borrowed("name")), prop_path.as_mut()).and(Ok(()))
             }
+            "description" => {
+                validate_is_string(&v, Some(Cow::Borrowed("description")), prop_path.as_mut())
+                    .and(Ok(()))
+            }
+            "group" => {
+                validate_is_string(&v, Some(Cow::Borrowed("group")), prop_path.as_mut()).and(Ok(()))
+            }
             "default" => {
                 validate_is_bool(&v, Some(Cow::Borrowed("default")), prop_path.as_mut()).and(Ok(()))
             }
+            "private" => {
+                validate_is_bool(&v, Some(Cow::Borrowed("private")), prop_path.as_mut()).and(Ok(()))
+            }
             "always_run" => {
                 validate_is_bool(&v, Some(Cow::Borrowed("always_run")), prop_path.as_mut())
                     .and(Ok(()))
@@ -96,6 +148,9 @@ pub fn validate_inline_task<'lua>(
                 validate_is_bool(&v, Some(Cow::Borrowed("interactive")), prop_path.as_mut())
                     .and(Ok(()))
             }
+            "pty" => {
+                validate_is_bool(&v, Some(Cow::Borrowed("pty")), prop_path.as_mut()).and(Ok(()))
+            }
             "stdout" => {
                 validate_output_condition(Some(Cow::Borrowed("stdout")), &v, prop_path.as_mut())
             }
@@ -116,6 +171,9 @@ pub fn validate_inline_task<'lua>(
                     v
                 ))),
             },
+            "env_vars" => {
+                validate_env_vars_table(Some(Cow::Borrowed("env_vars")), &v, prop_path.as_mut())
+            }
             "actions" => {
                 validate_action_list(lua, &v, Some(Cow::Borrowed("actions")), prop_path.as_mut())
             }
@@ -124,21 +182,28 @@ pub fn validate_inline_task<'lua>(
             }
             "deps" => validate_dep_list(lua, &v, Some(Cow::Borrowed("deps")), prop_path.as_mut()),
             "artifacts" => validate_artifacts(&v, Some(Cow::Borrowed("artifacts")), prop_path.as_mut()),
+            "tags" => validate_tags(Some(Cow::Borrowed("tags")), &v, prop_path.as_mut()),
             unknown_key => key_validation_error(
                 unknown_key,
                 vec![
                     "name",
+                    "description",
+                    "group",
                     "default",
+                    "private",
                     "always_run",
                     "interactive",
+                    "pty",
                     "stdout",
                     "stderr",
                     "output",
                     "env",
+                    "env_vars",
                     "actions",
                     "clean",
                     "deps",
                     "artifacts",
+                    "tags",
                 ],
                 prop_path.as_mut(),
             ),
@@ -177,6 +242,10 @@ impl fmt::Display for TaskDef {
 
         write!(f, "deps={},", self.deps)?;
 
+        if !self.tags.is_empty() {
+            write!(f, "tags=[{}], ", self.tags.join(", "))?;
+        }
+
         write!(f, "artifacts={}", self.artifacts)
     }
 }
@@ -185,9 +254,13 @@ pub fn dump_inline_task<'lua>(
     task_name: Arc<str>,
     task_table: mlua::Table<'lua>,
 ) -> mlua::Result<TaskDef> {
+    let description: Option<String> = task_table.get("description")?;
+    let group: Option<String> = task_table.get("group")?;
     let is_default: Option<bool> = task_table.get("default")?;
+    let is_private: Option<bool> = task_table.get("private")?;
     let always_run: Option<bool> = task_table.get("always_run")?;
     let is_interactive: Option<bool> = task_table.get("interactive")?;
+    let pty: Option<bool> = task_table.get("pty")?;
 
     let stdout: Option<TaskOutputCondition> = task_table.get("stdout")?;
     let stderr: Option<TaskOutputCondition> = task_table.get("stderr")?;
@@ -223,6 +296,9 @@ pub fn dump_inline_task<'lua>(
         }
     };
 
+    let env_vars_opt: Option<HashMap<String, String>> = task_table.get("env_vars")?;
+    let env_vars = env_vars_opt.unwrap_or_default();
+
     let actions: Vec<Action> = task_table.get("actions")?;
     let clean_opt: Option<Vec<Action>> = task_table.get("clean")?;
     let clean = clean_opt.unwrap_or_default();
@@ -230,19 +306,31 @@ pub fn dump_inline_task<'lua>(
     let deps = deps_opt.unwrap_or_default();
     let artifacts_opt: Option<Artifacts> = task_table.get("artifacts")?;
     let artifacts = artifacts_opt.unwrap_or_default();
+    let tags_opt: Option<Vec<String>> = task_table.get("tags")?;
+    let tags = tags_opt
+        .unwrap_or_default()
+        .into_iter()
+        .map(Arc::<str>::from)
+        .collect();
 
     Ok(TaskDef {
         name: task_name,
+        description: description.map(Arc::<str>::from),
+        group: group.map(Arc::<str>::from),
         is_default,
+        is_private,
         always_run,
         is_interactive,
+        pty,
         show_stdout: stdout.or(output.clone()),
         show_stderr: stderr.or(output),
         build_env,
+        env_vars,
         actions,
         clean,
         deps,
         artifacts,
+        tags,
     })
 }
 
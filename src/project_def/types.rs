@@ -37,11 +37,14 @@ impl<'lua> mlua::FromLua<'lua> for StringOrInt {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 #[serde(untagged)]
 pub enum TaskVar {
     Table(HashMap<String, TaskVar>),
     List(Vec<TaskVar>),
+    Bool(bool),
+    Int(i64),
+    Number(f64),
     String(String),
 }
 
@@ -68,6 +71,9 @@ impl fmt::Display for TaskVar {
                 }
                 f.write_str("]")
             }
+            TaskVar::Bool(b) => write!(f, "{}", b),
+            TaskVar::Int(i) => write!(f, "{}", i),
+            TaskVar::Number(n) => write!(f, "{}", n),
             TaskVar::String(s) => write!(f, "\"{}\"", s),
         }
     }
@@ -77,6 +83,9 @@ impl<'lua> mlua::FromLua<'lua> for TaskVar {
     fn from_lua(value: mlua::Value<'lua>, _lua: &'lua mlua::Lua) -> mlua::Result<Self> {
         match value {
             mlua::Value::String(s) => Ok(TaskVar::String(String::from(s.to_str()?))),
+            mlua::Value::Boolean(b) => Ok(TaskVar::Bool(b)),
+            mlua::Value::Integer(i) => Ok(TaskVar::Int(i)),
+            mlua::Value::Number(n) => Ok(TaskVar::Number(n)),
             mlua::Value::Table(t) => {
                 let mut result: HashMap<String, TaskVar> = HashMap::new();
                 for pair in t.pairs() {
@@ -86,7 +95,7 @@ impl<'lua> mlua::FromLua<'lua> for TaskVar {
                 Ok(TaskVar::Table(result))
             }
             _ => Err(mlua::Error::runtime(format!(
-                "Expected a string or integer, but got a {}: {:?}",
+                "Expected a string, number, boolean or table, but got a {}: {:?}",
                 value.type_name(),
                 value
             ))),
@@ -111,6 +120,9 @@ impl<'lua> mlua::IntoLua<'lua> for TaskVar {
                 }
                 Ok(mlua::Value::Table(lua_table))
             }
+            TaskVar::Bool(b) => Ok(mlua::Value::Boolean(b)),
+            TaskVar::Int(i) => Ok(mlua::Value::Integer(i)),
+            TaskVar::Number(n) => Ok(mlua::Value::Number(n)),
             TaskVar::String(s) => Ok(mlua::Value::String(lua.create_string(s)?)),
         }
     }
@@ -144,6 +156,33 @@ impl From<toml::Value> for TaskVar {
     }
 }
 
+impl TryFrom<serde_json::Value> for TaskVar {
+    type Error = String;
+
+    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+        match value {
+            serde_json::Value::Object(obj) => {
+                let mut tbl_var: HashMap<String, TaskVar> = HashMap::with_capacity(obj.len());
+                for (k, v) in obj {
+                    tbl_var.insert(k, v.try_into()?);
+                }
+                Ok(TaskVar::Table(tbl_var))
+            }
+            serde_json::Value::Array(arr) => {
+                let mut list_var: Vec<TaskVar> = Vec::with_capacity(arr.len());
+                for v in arr {
+                    list_var.push(v.try_into()?);
+                }
+                Ok(TaskVar::List(list_var))
+            }
+            serde_json::Value::String(s) => Ok(TaskVar::String(s)),
+            serde_json::Value::Bool(b) => Ok(TaskVar::String(format!("{}", b))),
+            serde_json::Value::Number(n) => Ok(TaskVar::String(format!("{}", n))),
+            serde_json::Value::Null => Err(String::from("null is not a valid variable value")),
+        }
+    }
+}
+
 pub fn json_to_lua<'lua>(
     lua: &'lua mlua::Lua,
     value: serde_json::Value,
@@ -0,0 +1,305 @@
+// Cobble Build Automation
+// Copyright (C) 2024 Jeremiah Darais
+//
+// This program is licensed under the GPLv3.0 license (https://github.com/jdarais/cobble/blob/main/COPYING)
+
+use std::{borrow::Cow, fmt, sync::Arc};
+
+use crate::project_def::types::TaskVar;
+use crate::project_def::validate::{
+    key_validation_error, validate_is_string, validate_is_table, validate_required_key,
+    validate_table_is_sequence,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VarType {
+    String,
+    Int,
+    Number,
+    Bool,
+    List,
+    Table,
+}
+
+impl fmt::Display for VarType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VarType::String => f.write_str("string"),
+            VarType::Int => f.write_str("int"),
+            VarType::Number => f.write_str("number"),
+            VarType::Bool => f.write_str("bool"),
+            VarType::List => f.write_str("list"),
+            VarType::Table => f.write_str("table"),
+        }
+    }
+}
+
+pub fn parse_var_type(value: &str) -> Result<VarType, String> {
+    match value {
+        "string" => Ok(VarType::String),
+        "int" => Ok(VarType::Int),
+        "number" => Ok(VarType::Number),
+        "bool" => Ok(VarType::Bool),
+        "list" => Ok(VarType::List),
+        "table" => Ok(VarType::Table),
+        _ => Err(format!(
+            "Invalid var type: '{}'. Expected one of [string, int, number, bool, list, table]",
+            value
+        )),
+    }
+}
+
+/// Returns the string form of a scalar var value, or None if the value is a list or table
+pub fn scalar_var_string(value: &TaskVar) -> Option<String> {
+    match value {
+        TaskVar::String(s) => Some(s.clone()),
+        TaskVar::Bool(b) => Some(b.to_string()),
+        TaskVar::Int(i) => Some(i.to_string()),
+        TaskVar::Number(n) => Some(n.to_string()),
+        TaskVar::List(_) | TaskVar::Table(_) => None,
+    }
+}
+
+/// Check that a value is of the given type, and convert it to a value of that type.
+///
+/// Values set on the command line, in the environment or in cobble.toml are strings, so an int
+/// var set to "007" becomes the integer 7, and a bool var set to "yes" becomes true.
+pub fn coerce_var_value(var_type: VarType, value: &TaskVar) -> Result<TaskVar, String> {
+    match (var_type, value) {
+        (VarType::String, TaskVar::String(_)) => Ok(value.clone()),
+        (VarType::String, TaskVar::Bool(_) | TaskVar::Int(_) | TaskVar::Number(_)) => Ok(
+            TaskVar::String(scalar_var_string(value).expect("value is a scalar")),
+        ),
+        (VarType::Int, TaskVar::Int(_)) => Ok(value.clone()),
+        (VarType::Int, TaskVar::String(s)) => s
+            .trim()
+            .parse::<i64>()
+            .map(TaskVar::Int)
+            .map_err(|_| format!("Expected an int, but got \"{}\"", s)),
+        (VarType::Number, TaskVar::Number(n)) if n.is_finite() => Ok(value.clone()),
+        (VarType::Number, TaskVar::Int(i)) => Ok(TaskVar::Number(*i as f64)),
+        (VarType::Number, TaskVar::String(s)) => match s.trim().parse::<f64>() {
+            Ok(n) if n.is_finite() => Ok(TaskVar::Number(n)),
+            _ => Err(format!("Expected a number, but got \"{}\"", s)),
+        },
+        (VarType::Bool, TaskVar::Bool(_)) => Ok(value.clone()),
+        (VarType::Bool, TaskVar::String(s)) => match s.trim().to_lowercase().as_str() {
+            "true" | "yes" | "on" | "1" => Ok(TaskVar::Bool(true)),
+            "false" | "no" | "off" | "0" => Ok(TaskVar::Bool(false)),
+            _ => Err(format!(
+                "Expected a bool, (true/false, yes/no, on/off or 1/0,) but got \"{}\"",
+                s
+            )),
+        },
+        (VarType::List, TaskVar::List(_)) | (VarType::Table, TaskVar::Table(_)) => {
+            Ok(value.clone())
+        }
+        _ => Err(format!("Expected a {}, but got {}", var_type, value)),
+    }
+}
+
+/// A variable declared in a project file
+#[derive(Clone, Debug, PartialEq)]
+pub struct VarDef {
+    pub name: Arc<str>,
+    pub var_type: VarType,
+    pub choices: Option<Vec<String>>,
+    pub default: Option<TaskVar>,
+    pub description: Option<Arc<str>>,
+}
+
+impl VarDef {
+    /// Check that a value is valid for this var, and convert it to its canonical form
+    pub fn coerce(&self, value: &TaskVar) -> Result<TaskVar, String> {
+        let coerced = coerce_var_value(self.var_type, value)?;
+
+        if let (Some(choices), Some(s)) = (&self.choices, scalar_var_string(&coerced)) {
+            if !choices.contains(&s) {
+                return Err(format!(
+                    "Expected one of [{}], but got \"{}\"",
+                    choices.join(", "),
+                    s
+                ));
+            }
+        }
+
+        Ok(coerced)
+    }
+}
+
+/// Convert a lua value given in a var declaration to a var value.  Tables with a sequence part
+/// are converted to lists.
+fn lua_to_var_value(value: mlua::Value) -> mlua::Result<TaskVar> {
+    match value {
+        mlua::Value::Boolean(b) => Ok(TaskVar::Bool(b)),
+        mlua::Value::Integer(i) => Ok(TaskVar::Int(i)),
+        mlua::Value::Number(n) => Ok(TaskVar::Number(n)),
+        mlua::Value::Table(t) => {
+            if t.len()? > 0 {
+                let mut list: Vec<TaskVar> = Vec::new();
+                for v in t.sequence_values::<mlua::Value>() {
+                    list.push(lua_to_var_value(v?)?);
+                }
+                Ok(TaskVar::List(list))
+            } else {
+                let mut tbl = std::collections::HashMap::new();
+                for pair in t.pairs::<String, mlua::Value>() {
+                    let (k, v) = pair?;
+                    tbl.insert(k, lua_to_var_value(v)?);
+                }
+                Ok(TaskVar::Table(tbl))
+            }
+        }
+        mlua::Value::String(s) => Ok(TaskVar::String(String::from(s.to_str()?))),
+        _ => Err(mlua::Error::runtime(format!(
+            "Expected a string, number, boolean or table, but got a {}: {:?}",
+            value.type_name(),
+            value
+        ))),
+    }
+}
+
+pub fn validate_var<'lua>(lua: &'lua mlua::Lua, value: &mlua::Value<'lua>) -> mlua::Result<()> {
+    let mut prop_path: Vec<Cow<str>> = Vec::new();
+
+    let var_tbl = validate_is_table(value, None, &mut prop_path)?;
+
+    validate_required_key(var_tbl, "name", None, &mut prop_path)?;
+
+    for pair in var_tbl.clone().pairs() {
+        let (k, v): (mlua::Value, mlua::Value) = pair?;
+        let k_str = validate_is_string(&k, None, &mut prop_path)?;
+        match k_str.to_str()? {
+            "name" | "description" => {
+                let key = Cow::Owned(String::from(k_str.to_str()?));
+                validate_is_string(&v, Some(key), &mut prop_path).and(Ok(()))
+            }
+            "type" => {
+                let type_str = validate_is_string(&v, Some(Cow::Borrowed("type")), &mut prop_path)?;
+                parse_var_type(type_str.to_str()?)
+                    .map_err(|e| mlua::Error::runtime(format!("In type: {}", e)))?;
+                Ok(())
+            }
+            "choices" => {
+                let choices_tbl =
+                    validate_is_table(&v, Some(Cow::Borrowed("choices")), &mut prop_path)?;
+                validate_table_is_sequence(
+                    choices_tbl,
+                    Some(Cow::Borrowed("choices")),
+                    &mut prop_path,
+                )
+            }
+            "default" => Ok(()),
+            unknown_key => key_validation_error(
+                unknown_key,
+                vec!["name", "type", "choices", "default", "description"],
+                &prop_path,
+            ),
+        }?;
+    }
+
+    // Make sure the choices and default are valid for the declared type
+    let _var_def: VarDef = mlua::FromLua::from_lua(value.clone(), lua)?;
+
+    Ok(())
+}
+
+impl fmt::Display for VarDef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("VarDef(")?;
+        write!(f, "name=\"{}\", type={}", self.name, self.var_type)?;
+
+        if let Some(choices) = self.choices.as_ref() {
+            write!(f, ", choices=[{}]", choices.join(", "))?;
+        }
+
+        if let Some(default) = self.default.as_ref() {
+            write!(f, ", default={}", default)?;
+        }
+
+        if let Some(description) = self.description.as_ref() {
+            write!(f, ", description=\"{}\"", description)?;
+        }
+
+        f.write_str(")")
+    }
+}
+
+impl<'lua> mlua::FromLua<'lua> for VarDef {
+    fn from_lua(value: mlua::Value<'lua>, _lua: &'lua mlua::Lua) -> mlua::Result<Self> {
+        let var_tbl = match value {
+            mlua::Value::Table(tbl) => tbl,
+            _ => {
+                return Err(mlua::Error::runtime(format!(
+                    "Unable to convert value to var: {:?}",
+                    &value
+                )));
+            }
+        };
+
+        let name_str: String = var_tbl.get("name")?;
+        let name = Arc::<str>::from(name_str);
+
+        let type_str: Option<String> = var_tbl.get("type")?;
+        let var_type = match type_str {
+            Some(t) => parse_var_type(t.as_str()).map_err(mlua::Error::runtime)?,
+            None => VarType::String,
+        };
+
+        let choices = match var_tbl.get::<_, Option<mlua::Table>>("choices")? {
+            Some(_) if matches!(var_type, VarType::List | VarType::Table) => {
+                return Err(mlua::Error::runtime(format!(
+                    "In {}: choices can't be given for a {} var",
+                    name, var_type
+                )));
+            }
+            Some(choices_tbl) => {
+                let mut choices: Vec<String> = Vec::new();
+                for choice in choices_tbl.sequence_values::<mlua::Value>() {
+                    let choice_val = lua_to_var_value(choice?)?;
+                    match coerce_var_value(var_type, &choice_val) {
+                        Ok(choice) => match scalar_var_string(&choice) {
+                            Some(s) => choices.push(s),
+                            None => {
+                                return Err(mlua::Error::runtime(format!(
+                                    "In {}.choices: Expected a {}, but got {}",
+                                    name, var_type, choice
+                                )));
+                            }
+                        },
+                        Err(e) => {
+                            return Err(mlua::Error::runtime(format!(
+                                "In {}.choices: {}",
+                                name, e
+                            )));
+                        }
+                    }
+                }
+                Some(choices)
+            }
+            None => None,
+        };
+
+        let description: Option<String> = var_tbl.get("description")?;
+
+        let mut var_def = VarDef {
+            name,
+            var_type,
+            choices,
+            default: None,
+            description: description.map(Arc::<str>::from),
+        };
+
+        var_def.default = match var_tbl.get::<_, mlua::Value>("default")? {
+            mlua::Value::Nil => None,
+            default_val => {
+                let default = var_def.coerce(&lua_to_var_value(default_val)?).map_err(|e| {
+                    mlua::Error::runtime(format!("In {}.default: {}", var_def.name, e))
+                })?;
+                Some(default)
+            }
+        };
+
+        Ok(var_def)
+    }
+}
@@ -3,14 +3,21 @@
 //
 // This program is licensed under the GPLv3.0 license (https://github.com/jdarais/cobble/blob/main/COPYING)
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use wildmatch::WildMatch;
 
 use crate::resolve::NameResolutionError;
-use crate::workspace::Workspace;
+use crate::workspace::{Task, TaskType, Workspace, INTERNAL_PROJECT_NAME};
+
+const TAG_QUERY_PREFIX: &str = "tag:";
+const EXCLUDE_QUERY_PREFIX: &str = "!";
+const DEPS_QUERY_PREFIX: &str = "deps(";
+const RDEPS_QUERY_PREFIX: &str = "rdeps(";
+pub const RECURSIVE_QUERY_SEPARATOR: &str = "...";
+const MAX_CLOSE_MATCHES: usize = 5;
 
 pub fn find_tasks_for_dir<'a>(
     workspace: &'a Workspace,
@@ -29,6 +36,297 @@ pub fn find_tasks_for_dir<'a>(
         .collect()
 }
 
+/// A single term of a task query
+enum QueryTerm {
+    Name(Arc<str>),
+    Pattern(WildMatch),
+    Tag(WildMatch),
+    /// Tasks in a project and all of its subprojects, (e.g. `/libs/...:test`)
+    Recursive { project_name: String, task_pattern: WildMatch },
+    Deps(Box<QueryTerm>),
+    Rdeps(Box<QueryTerm>),
+}
+
+fn parse_query_term(
+    workspace: &Workspace,
+    project_prefix: &str,
+    term: &str,
+) -> Result<QueryTerm, NameResolutionError> {
+    if let Some(inner) = term.strip_prefix(DEPS_QUERY_PREFIX).and_then(|t| t.strip_suffix(')')) {
+        return Ok(QueryTerm::Deps(Box::new(parse_query_term(workspace, project_prefix, inner)?)));
+    }
+
+    if let Some(inner) = term.strip_prefix(RDEPS_QUERY_PREFIX).and_then(|t| t.strip_suffix(')')) {
+        return Ok(QueryTerm::Rdeps(Box::new(parse_query_term(workspace, project_prefix, inner)?)));
+    }
+
+    if term.contains(['(', ')']) {
+        return Err(NameResolutionError::InvalidQuery(format!(
+            "Unable to parse '{}'. Expected deps(<query>) or rdeps(<query>)",
+            term
+        )));
+    }
+
+    if let Some(tag) = term.strip_prefix(TAG_QUERY_PREFIX) {
+        return Ok(QueryTerm::Tag(WildMatch::new(tag)));
+    }
+
+    if let Some((project, task_pattern)) = term.split_once(RECURSIVE_QUERY_SEPARATOR) {
+        let project_name = if project.starts_with('/') {
+            String::from(project.trim_end_matches('/'))
+        } else if project.is_empty() {
+            String::from(&project_prefix[..project_prefix.len() - 1])
+        } else {
+            format!("{}{}", project_prefix, project.trim_end_matches('/'))
+        };
+        let task_pattern = task_pattern.strip_prefix(':').unwrap_or(task_pattern);
+        let task_pattern = if task_pattern.is_empty() { "*" } else { task_pattern };
+
+        return Ok(QueryTerm::Recursive {
+            project_name,
+            task_pattern: WildMatch::new(task_pattern),
+        });
+    }
+
+    if term.contains(['*', '?']) {
+        return Ok(QueryTerm::Pattern(WildMatch::new(term)));
+    }
+
+    let full_task_name: Arc<str> = if term.starts_with('/') {
+        String::from(term).into()
+    } else {
+        format!("{}{}", project_prefix, term).into()
+    };
+
+    if workspace.tasks.contains_key(full_task_name.as_ref()) {
+        Ok(QueryTerm::Name(full_task_name))
+    } else {
+        Err(NameResolutionError::TaskNotFound {
+            name: String::from(term),
+            close_matches: find_close_task_names(workspace, full_task_name.as_ref()),
+        })
+    }
+}
+
+fn resolve_query_term(
+    workspace: &Workspace,
+    project_prefix: &str,
+    term: &QueryTerm,
+    result: &mut Vec<Arc<str>>,
+) {
+    match term {
+        QueryTerm::Name(name) => result.push(name.clone()),
+        QueryTerm::Pattern(pattern) => {
+            for task_name in sorted_task_names(workspace) {
+                if pattern.matches(task_name.as_ref())
+                    || (task_name.starts_with(project_prefix)
+                        && pattern.matches(&task_name[project_prefix.len()..]))
+                {
+                    result.push(task_name.clone());
+                }
+            }
+        }
+        QueryTerm::Tag(tag_pattern) => {
+            for task_name in sorted_task_names(workspace) {
+                if task_has_tag(&workspace.tasks[task_name], tag_pattern) {
+                    result.push(task_name.clone());
+                }
+            }
+        }
+        QueryTerm::Recursive { project_name, task_pattern } => {
+            for task_name in sorted_task_names(workspace) {
+                let task = &workspace.tasks[task_name];
+                if !matches!(task.task_type, TaskType::Task)
+                    || !is_project_or_subproject(&task.project_name, project_name)
+                {
+                    continue;
+                }
+
+                let rel_name = task_name
+                    .strip_prefix(task.project_name.as_ref())
+                    .and_then(|n| n.strip_prefix('/'))
+                    .unwrap_or(task_name.as_ref());
+                if task_pattern.matches(rel_name) {
+                    result.push(task_name.clone());
+                }
+            }
+        }
+        QueryTerm::Deps(inner) => {
+            let mut seeds: Vec<Arc<str>> = Vec::new();
+            resolve_query_term(workspace, project_prefix, inner, &mut seeds);
+            let deps_of = |task_name: &Arc<str>| -> Vec<Arc<str>> {
+                let mut deps: Vec<Arc<str>> = workspace
+                    .tasks
+                    .get(task_name)
+                    .map(|t| t.task_deps.values().cloned().collect())
+                    .unwrap_or_default();
+                deps.sort();
+                deps
+            };
+            collect_transitive(workspace, seeds, deps_of, result);
+        }
+        QueryTerm::Rdeps(inner) => {
+            let mut seeds: Vec<Arc<str>> = Vec::new();
+            resolve_query_term(workspace, project_prefix, inner, &mut seeds);
+            result.extend(find_reverse_dependencies(workspace, seeds));
+        }
+    }
+}
+
+/// Find the given tasks, along with all tasks that depend on them, directly or indirectly
+pub fn find_reverse_dependencies(workspace: &Workspace, tasks: Vec<Arc<str>>) -> Vec<Arc<str>> {
+    let mut rdeps: HashMap<&str, Vec<Arc<str>>> = HashMap::new();
+    for task_name in sorted_task_names(workspace) {
+        for dep in workspace.tasks[task_name].task_deps.values() {
+            rdeps.entry(dep.as_ref()).or_default().push(task_name.clone());
+        }
+    }
+    let rdeps_of = |task_name: &Arc<str>| -> Vec<Arc<str>> {
+        rdeps.get(task_name.as_ref()).cloned().unwrap_or_default()
+    };
+
+    let mut result: Vec<Arc<str>> = Vec::new();
+    collect_transitive(workspace, tasks, rdeps_of, &mut result);
+    result
+}
+
+/// Add the seed tasks and all tasks reachable from them to the result.  Project tasks that are
+/// reached along the way are left out, since every task is a dependency of its project.
+fn collect_transitive<F>(
+    workspace: &Workspace,
+    seeds: Vec<Arc<str>>,
+    next: F,
+    result: &mut Vec<Arc<str>>,
+) where
+    F: Fn(&Arc<str>) -> Vec<Arc<str>>,
+{
+    let mut visited: HashSet<Arc<str>> = HashSet::new();
+    let mut stack: Vec<(Arc<str>, bool)> = seeds.into_iter().rev().map(|s| (s, true)).collect();
+
+    while let Some((task_name, is_seed)) = stack.pop() {
+        if !visited.insert(task_name.clone()) {
+            continue;
+        }
+
+        let is_project = workspace
+            .tasks
+            .get(&task_name)
+            .map(|t| matches!(t.task_type, TaskType::Project))
+            .unwrap_or(false);
+        if is_seed || !is_project {
+            result.push(task_name.clone());
+        }
+
+        for next_name in next(&task_name).into_iter().rev() {
+            stack.push((next_name, false));
+        }
+    }
+}
+
+/// Replace project tasks with the tasks they run, so that exclusions can be applied to them
+pub fn expand_project_tasks(workspace: &Workspace, tasks: Vec<Arc<str>>) -> Vec<Arc<str>> {
+    let mut result: Vec<Arc<str>> = Vec::new();
+    let mut visited: HashSet<Arc<str>> = HashSet::new();
+    let mut stack: Vec<Arc<str>> = tasks.into_iter().rev().collect();
+
+    while let Some(task_name) = stack.pop() {
+        if !visited.insert(task_name.clone()) {
+            continue;
+        }
+
+        match workspace.tasks.get(&task_name) {
+            Some(task) if matches!(task.task_type, TaskType::Project) => {
+                let mut deps: Vec<Arc<str>> = task.task_deps.values().cloned().collect();
+                deps.sort();
+                stack.extend(deps.into_iter().rev());
+            }
+            _ => result.push(task_name),
+        }
+    }
+
+    result
+}
+
+fn sorted_task_names(workspace: &Workspace) -> Vec<&Arc<str>> {
+    let mut task_names: Vec<&Arc<str>> = workspace.tasks.keys().collect();
+    task_names.sort();
+    task_names
+}
+
+fn is_project_or_subproject(project_name: &str, parent_project_name: &str) -> bool {
+    project_name == parent_project_name
+        || project_name
+            .strip_prefix(parent_project_name)
+            .map(|rest| rest.starts_with('/'))
+            .unwrap_or(false)
+}
+
+fn task_has_tag(task: &Task, tag_pattern: &WildMatch) -> bool {
+    task.tags.iter().any(|tag| tag_pattern.matches(tag))
+}
+
+fn parse_query_terms<'i, I>(
+    workspace: &Workspace,
+    project_prefix: &str,
+    queries: I,
+) -> Result<(Vec<QueryTerm>, Vec<QueryTerm>), NameResolutionError>
+where
+    I: Iterator<Item = &'i str>,
+{
+    let mut include_terms: Vec<QueryTerm> = Vec::new();
+    let mut exclude_terms: Vec<QueryTerm> = Vec::new();
+
+    // A single query can hold several terms separated by whitespace, (e.g. 'tag:test !tag:slow'.)
+    for query in queries.flat_map(str::split_whitespace) {
+        match query.strip_prefix(EXCLUDE_QUERY_PREFIX) {
+            Some(excluded) => {
+                exclude_terms.push(parse_query_term(workspace, project_prefix, excluded)?)
+            }
+            None => include_terms.push(parse_query_term(workspace, project_prefix, query)?),
+        }
+    }
+
+    Ok((include_terms, exclude_terms))
+}
+
+fn resolve_query_terms(
+    workspace: &Workspace,
+    project_prefix: &str,
+    terms: &[QueryTerm],
+) -> Vec<Arc<str>> {
+    let mut matches: Vec<Arc<str>> = Vec::new();
+    for term in terms {
+        resolve_query_term(workspace, project_prefix, term, &mut matches);
+    }
+
+    let mut seen: HashSet<Arc<str>> = HashSet::new();
+    matches.retain(|t| seen.insert(t.clone()));
+    matches
+}
+
+fn exclude_tasks(
+    workspace: &Workspace,
+    project_prefix: &str,
+    tasks: Vec<Arc<str>>,
+    exclude_terms: &[QueryTerm],
+) -> Vec<Arc<str>> {
+    if exclude_terms.is_empty() {
+        return tasks;
+    }
+
+    // Excluding a project excludes the tasks it runs
+    let excluded: HashSet<Arc<str>> = expand_project_tasks(
+        workspace,
+        resolve_query_terms(workspace, project_prefix, exclude_terms),
+    )
+    .into_iter()
+    .collect();
+
+    let mut result = expand_project_tasks(workspace, tasks);
+    result.retain(|t| !excluded.contains(t));
+    result
+}
+
 pub fn find_tasks_for_query<'i, I>(
     workspace: &Workspace,
     project_name: &str,
@@ -37,53 +335,111 @@ pub fn find_tasks_for_query<'i, I>(
 where
     I: Iterator<Item = &'i str>,
 {
-    let mut result: Vec<Arc<str>> = Vec::new();
-    let mut direct_name_matches: HashSet<Arc<str>> = HashSet::new();
-
-    let mut query_patterns: Vec<WildMatch> = Vec::new(); // task_queries.map(WildMatch::new).collect();
     let mut project_prefix = project_name.to_owned();
     project_prefix.push('/');
 
-    // Find all direct name matches, and defer patterns for a separate search loop
-    for query in task_queries {
-        if query.contains(|ch| ch == '*' || ch == '?') {
-            query_patterns.push(WildMatch::new(query));
-        } else {
-            let full_task_name: Arc<str> = if query.starts_with("/") {
-                String::from(query).into()
-            } else {
-                let mut s = project_prefix.clone();
-                s.push_str(query);
-                s.into()
-            };
-            
-            if workspace.tasks.contains_key(full_task_name.as_ref()) {
-                direct_name_matches.insert(full_task_name.clone());
-                result.push(full_task_name.clone());
-            } else {
-                return Err(NameResolutionError::InvalidName(String::from(query)));
-            }
-        }
+    let (include_terms, exclude_terms) =
+        parse_query_terms(workspace, &project_prefix, task_queries)?;
+
+    if include_terms.is_empty() && !exclude_terms.is_empty() {
+        return Err(NameResolutionError::InvalidQuery(String::from(
+            "Expected at least one task name, pattern, or tag to select tasks, in addition to excluded tasks",
+        )));
     }
 
-    // Find all pattern matches
-    for task_name in workspace.tasks.keys() {
-        if direct_name_matches.contains(task_name) {
-            continue;
-        }
+    let result = resolve_query_terms(workspace, &project_prefix, &include_terms);
+    Ok(exclude_tasks(workspace, &project_prefix, result, &exclude_terms))
+}
 
-        for query_pattern in &query_patterns {
-            if query_pattern.matches(task_name.as_ref())
-                || (task_name.starts_with(project_prefix.as_str())
-                    && query_pattern.matches(&task_name[project_prefix.len()..]))
+/// Remove tasks matching any of the exclude queries from a task selection.  Any project in the
+/// selection is replaced by the tasks that it runs before the exclusions are applied.
+pub fn exclude_tasks_for_query<'i, I>(
+    workspace: &Workspace,
+    project_name: &str,
+    tasks: Vec<Arc<str>>,
+    exclude_queries: I,
+) -> Result<Vec<Arc<str>>, NameResolutionError>
+where
+    I: Iterator<Item = &'i str>,
+{
+    let mut project_prefix = project_name.to_owned();
+    project_prefix.push('/');
+
+    let (include_terms, mut exclude_terms) = parse_query_terms(
+        workspace,
+        &project_prefix,
+        exclude_queries.map(|q| q.strip_prefix(EXCLUDE_QUERY_PREFIX).unwrap_or(q)),
+    )?;
+    exclude_terms.extend(include_terms);
+
+    Ok(exclude_tasks(workspace, &project_prefix, tasks, &exclude_terms))
+}
+
+/// Find up to a handful of task names that are similar to a name that wasn't found, for
+/// suggesting in error messages
+fn find_close_task_names(workspace: &Workspace, full_task_name: &str) -> Vec<Arc<str>> {
+    let last_segment = |name: &str| -> String {
+        String::from(name.rsplit('/').next().unwrap_or(name))
+    };
+    let query_last_segment = last_segment(full_task_name);
+    let max_distance = usize::max(1, query_last_segment.chars().count() / 3);
+
+    // Compare both the full name, (to catch typos in the project path,) and the last segment of
+    // the name, (to catch tasks with the same or a similar name in other projects.)
+    let mut close_matches: Vec<(usize, usize, &Arc<str>)> = workspace
+        .tasks
+        .keys()
+        .filter(|name| !name.starts_with(INTERNAL_PROJECT_NAME))
+        .filter_map(|name| {
+            let full_distance = edit_distance(full_task_name, name);
+            let distance = usize::min(
+                full_distance,
+                edit_distance(&query_last_segment, &last_segment(name)),
+            );
+            (distance <= max_distance).then_some((distance, full_distance, name))
+        })
+        .collect();
+    close_matches.sort();
+
+    close_matches
+        .into_iter()
+        .take(MAX_CLOSE_MATCHES)
+        .map(|(_, _, name)| name.clone())
+        .collect()
+}
+
+/// Edit distance between two strings, counting insertions, deletions, substitutions, and swaps of
+/// adjacent characters as one edit each
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a_chars: Vec<char> = a.chars().collect();
+    let b_chars: Vec<char> = b.chars().collect();
+    let mut d: Vec<Vec<usize>> = vec![vec![0; b_chars.len() + 1]; a_chars.len() + 1];
+
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a_chars.len() {
+        for j in 1..=b_chars.len() {
+            let substitution_cost = if a_chars[i - 1] == b_chars[j - 1] { 0 } else { 1 };
+            d[i][j] = (d[i - 1][j - 1] + substitution_cost)
+                .min(d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1);
+
+            if i > 1
+                && j > 1
+                && a_chars[i - 1] == b_chars[j - 2]
+                && a_chars[i - 2] == b_chars[j - 1]
             {
-                result.push(task_name.clone());
-                break;
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
             }
         }
     }
 
-    Ok(result)
+    d[a_chars.len()][b_chars.len()]
 }
 
 pub fn find_envs_for_query<'i, I>(
@@ -117,10 +473,6 @@ where
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
-
-    use crate::workspace::Task;
-
     use super::*;
 
     fn create_minimal_workspace() -> Workspace {
@@ -128,12 +480,18 @@ mod tests {
         tasks.insert(String::from("/project1/task1").into(), Arc::new(Default::default()));
         tasks.insert(String::from("/project1/task2").into(), Arc::new(Default::default()));
         tasks.insert(String::from("/project2/task1").into(), Arc::new(Default::default()));
+        tasks.insert(String::from("/project1/unit").into(), Arc::new(Task {
+            tags: vec![Arc::<str>::from("test")],
+            ..Default::default()
+        }));
+        tasks.insert(String::from("/project2/integration").into(), Arc::new(Task {
+            tags: vec![Arc::<str>::from("test"), Arc::<str>::from("slow")],
+            ..Default::default()
+        }));
 
         Workspace {
             tasks: tasks,
-            build_envs: HashMap::new(),
-            tools: HashMap::new(),
-            file_providers: HashMap::new()
+            ..Default::default()
         }
     }
 
@@ -218,4 +576,124 @@ mod tests {
         assert!(matches.contains(&String::from("/project1/task1").into()));
         assert!(matches.contains(&String::from("/project1/task2").into()));
     }
+
+    #[test]
+    fn test_tag_query() {
+        let ws = create_minimal_workspace();
+
+        let mut matches = find_tasks_for_query(
+            &ws,
+            "/project1",
+            vec!["tag:test"].into_iter()
+        ).unwrap();
+        matches.sort();
+
+        assert_eq!(matches, vec![Arc::<str>::from("/project1/unit"), Arc::<str>::from("/project2/integration")]);
+    }
+
+    #[test]
+    fn test_excluded_tag_query() {
+        let ws = create_minimal_workspace();
+
+        let matches = find_tasks_for_query(
+            &ws,
+            "/project1",
+            vec!["tag:test !tag:slow"].into_iter()
+        ).unwrap();
+
+        assert_eq!(matches, vec![Arc::<str>::from("/project1/unit")]);
+
+        find_tasks_for_query(
+            &ws,
+            "/project1",
+            vec!["!tag:slow"].into_iter()
+        ).expect_err("Expected a query with only excluded tags to return an error");
+    }
+
+    fn create_workspace_with_deps() -> Workspace {
+        let task = |name: &str, project_name: &str, deps: Vec<&str>| -> (Arc<str>, Arc<Task>) {
+            (Arc::<str>::from(name), Arc::new(Task {
+                name: Arc::<str>::from(name),
+                project_name: Arc::<str>::from(project_name),
+                task_deps: deps.into_iter().map(|d| (Arc::<str>::from(d), Arc::<str>::from(d))).collect(),
+                ..Default::default()
+            }))
+        };
+
+        let mut tasks: HashMap<Arc<str>, Arc<Task>> = HashMap::from([
+            task("/libs/a/test", "/libs/a", vec![]),
+            task("/libs/b/build", "/libs/b", vec![]),
+            task("/libs/b/test", "/libs/b", vec!["/libs/b/build"]),
+            task("/app/test", "/app", vec!["/libs/a/test"]),
+        ]);
+        tasks.insert(Arc::<str>::from("/libs/b"), Arc::new(Task {
+            task_type: TaskType::Project,
+            name: Arc::<str>::from("/libs/b"),
+            project_name: Arc::<str>::from("/libs/b"),
+            task_deps: HashMap::from([
+                (Arc::<str>::from("/libs/b/build"), Arc::<str>::from("/libs/b/build")),
+                (Arc::<str>::from("/libs/b/test"), Arc::<str>::from("/libs/b/test")),
+            ]),
+            ..Default::default()
+        }));
+
+        Workspace {
+            tasks,
+            ..Default::default()
+        }
+    }
+
+    fn names(names: Vec<&str>) -> Vec<Arc<str>> {
+        names.into_iter().map(Arc::<str>::from).collect()
+    }
+
+    #[test]
+    fn test_recursive_project_query() {
+        let ws = create_workspace_with_deps();
+
+        let matches = find_tasks_for_query(&ws, "/app", vec!["/libs/...:test"].into_iter()).unwrap();
+        assert_eq!(matches, names(vec!["/libs/a/test", "/libs/b/test"]));
+
+        let matches = find_tasks_for_query(&ws, "/libs", vec!["...:build"].into_iter()).unwrap();
+        assert_eq!(matches, names(vec!["/libs/b/build"]));
+    }
+
+    #[test]
+    fn test_deps_and_rdeps_query() {
+        let ws = create_workspace_with_deps();
+
+        let matches = find_tasks_for_query(&ws, "/app", vec!["deps(test)"].into_iter()).unwrap();
+        assert_eq!(matches, names(vec!["/app/test", "/libs/a/test"]));
+
+        let matches = find_tasks_for_query(&ws, "/libs/b", vec!["rdeps(build)"].into_iter()).unwrap();
+        assert_eq!(matches, names(vec!["/libs/b/build", "/libs/b/test"]));
+    }
+
+    #[test]
+    fn test_excluded_query() {
+        let ws = create_workspace_with_deps();
+
+        let matches = find_tasks_for_query(&ws, "/app", vec!["/libs/...:*", "!/libs/b/build"].into_iter()).unwrap();
+        assert_eq!(matches, names(vec!["/libs/a/test", "/libs/b/test"]));
+
+        // Projects are replaced by the tasks they run before exclusions are applied
+        let matches = exclude_tasks_for_query(&ws, "/libs/b", names(vec!["/libs/b"]), vec!["build"].into_iter()).unwrap();
+        assert_eq!(matches, names(vec!["/libs/b/test"]));
+
+        let matches = find_tasks_for_query(&ws, "/app", vec!["/libs/...:*", "!/libs/b"].into_iter()).unwrap();
+        assert_eq!(matches, names(vec!["/libs/a/test"]));
+    }
+
+    #[test]
+    fn test_task_not_found_lists_close_matches() {
+        let ws = create_workspace_with_deps();
+
+        match find_tasks_for_query(&ws, "/libs/a", vec!["tets"].into_iter()) {
+            Err(NameResolutionError::TaskNotFound { name, close_matches }) => {
+                assert_eq!(name, "tets");
+                assert_eq!(close_matches[0].as_ref(), "/libs/a/test");
+            }
+            other => panic!("Expected a TaskNotFound error, but got {:?}", other),
+        }
+    }
 }
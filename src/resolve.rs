@@ -21,6 +21,11 @@ pub enum NameResolutionError {
     InvalidProjectName(String),
     PathToStringError(PathBuf),
     PathToNameError(PathBuf),
+    InvalidQuery(String),
+    TaskNotFound {
+        name: String,
+        close_matches: Vec<Arc<str>>,
+    },
 }
 
 impl Error for NameResolutionError {}
@@ -30,6 +35,14 @@ impl fmt::Display for NameResolutionError {
         use NameResolutionError::*;
         match self {
             InvalidName(s) => write!(f, "Invalid name: {}", s),
+            InvalidQuery(s) => write!(f, "Invalid query: {}", s),
+            TaskNotFound { name, close_matches } => {
+                write!(f, "Task not found: {}", name)?;
+                if !close_matches.is_empty() {
+                    write!(f, ". Did you mean one of: {}?", close_matches.join(", "))?;
+                }
+                Ok(())
+            }
             PathNotInWorkspace(s) => write!(f, "Path is not in the workspace: {}", s),
             InvalidProjectName(s) => write!(f, "Invalid project name: {}", s),
             PathToStringError(p) => write!(
@@ -3,15 +3,21 @@
 //
 // This program is licensed under the GPLv3.0 license (https://github.com/jdarais/cobble/blob/main/COPYING)
 
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::time::Duration;
 use std::{path::Path, sync::Arc};
 
 use crate::config::find_nearest_project_dir;
 use crate::workspace::Workspace;
-use crate::query::{find_envs_for_query, find_tasks_for_query};
+use crate::query::{exclude_tasks_for_query, find_envs_for_query, find_tasks_for_query};
 use crate::resolve::project_path_to_project_name;
 
 pub fn compute_selected_tasks(
     task_queries: &Vec<&str>,
+    exclude_queries: &Vec<&str>,
     workspace: &Workspace,
     cwd: &Path,
     ws_dir: &Path,
@@ -20,7 +26,7 @@ pub fn compute_selected_tasks(
     let project_name = project_path_to_project_name(project_dir.as_path())?;
 
     let selected_tasks = match task_queries.len() {
-        0 => vec![project_name.into()],
+        0 => vec![project_name.as_str().into()],
         _ => find_tasks_for_query(
             &workspace,
             project_name.as_str(),
@@ -28,6 +34,16 @@ pub fn compute_selected_tasks(
         )?,
     };
 
+    let selected_tasks = match exclude_queries.len() {
+        0 => selected_tasks,
+        _ => exclude_tasks_for_query(
+            workspace,
+            project_name.as_str(),
+            selected_tasks,
+            exclude_queries.iter().copied(),
+        )?,
+    };
+
     Ok(selected_tasks)
 }
 
@@ -42,7 +58,7 @@ pub fn compute_selected_envs(
     let project_name = project_path_to_project_name(project_dir.as_path())?;
 
     let selected_tasks = match env_queries.len() {
-        0 => vec![project_name.into()],
+        0 => vec![project_name.as_str().into()],
         _ => find_envs_for_query(
             &workspace,
             project_name.as_str(),
@@ -52,3 +68,173 @@ pub fn compute_selected_envs(
 
     Ok(selected_tasks)
 }
+
+/// One of `count` slices of a task selection, for splitting a run across machines.  `index` is
+/// 1-based.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Shard {
+    pub index: usize,
+    pub count: usize,
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+pub fn parse_shard(value: &str) -> Result<Shard, String> {
+    let invalid_shard = || {
+        format!(
+            "Invalid shard: '{}'. Expected <index>/<count>, where 1 <= index <= count",
+            value
+        )
+    };
+
+    let (index_str, count_str) = value.split_once('/').ok_or_else(invalid_shard)?;
+    let index: usize = index_str.trim().parse().map_err(|_| invalid_shard())?;
+    let count: usize = count_str.trim().parse().map_err(|_| invalid_shard())?;
+
+    if index == 0 || index > count {
+        return Err(invalid_shard());
+    }
+
+    Ok(Shard { index, count })
+}
+
+/// Read task durations written by write_shard_durations.  The file is a JSON object mapping task
+/// names to durations in milliseconds.
+pub fn read_shard_durations(path: &Path) -> io::Result<HashMap<Arc<str>, Duration>> {
+    let file = File::open(path)?;
+    let durations_ms: HashMap<String, u64> = serde_json::from_reader(BufReader::new(file))?;
+    Ok(durations_ms
+        .into_iter()
+        .map(|(task_name, ms)| (Arc::<str>::from(task_name), Duration::from_millis(ms)))
+        .collect())
+}
+
+/// Write task durations to a file that can be shared between shards
+pub fn write_shard_durations(path: &Path, durations: &HashMap<Arc<str>, Duration>) -> io::Result<()> {
+    // Sorted, so that the file doesn't change between runs unless the durations do
+    let durations_ms: BTreeMap<&str, u64> = durations
+        .iter()
+        .map(|(task_name, d)| (task_name.as_ref(), d.as_millis() as u64))
+        .collect();
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut writer, &durations_ms)?;
+    writer.flush()
+}
+
+/// Deterministically partition tasks across shards, and return the tasks assigned to the given
+/// shard.  Tasks are assigned longest first to the shard with the least total duration so far.
+/// Tasks without a known duration are assumed to take the average of the known durations, so
+/// with no durations at all, tasks are dealt out to shards in order of name.
+///
+/// Every shard must be given the same tasks and durations for the shards to line up.
+pub fn select_shard(
+    tasks: Vec<Arc<str>>,
+    shard: Shard,
+    durations: &HashMap<Arc<str>, Duration>,
+) -> Vec<Arc<str>> {
+    let mut tasks = tasks;
+    tasks.sort();
+    tasks.dedup();
+
+    let known_durations: Vec<Duration> = tasks
+        .iter()
+        .filter_map(|t| durations.get(t).copied())
+        .collect();
+    let default_duration = match known_durations.len() {
+        0 => Duration::from_millis(1),
+        n => known_durations.iter().sum::<Duration>() / n as u32,
+    };
+
+    let mut weighted_tasks: Vec<(Duration, Arc<str>)> = tasks
+        .into_iter()
+        .map(|t| (durations.get(&t).copied().unwrap_or(default_duration), t))
+        .collect();
+    // Longest first, then by name so that ties are broken the same way on every machine
+    weighted_tasks.sort_by(|(a_dur, a_name), (b_dur, b_name)| {
+        b_dur.cmp(a_dur).then_with(|| a_name.cmp(b_name))
+    });
+
+    let mut shard_loads: Vec<Duration> = vec![Duration::ZERO; shard.count];
+    let mut result: Vec<Arc<str>> = Vec::new();
+    for (duration, task_name) in weighted_tasks {
+        let (shard_idx, _) = shard_loads
+            .iter()
+            .enumerate()
+            .min_by_key(|(i, load)| (**load, *i))
+            .expect("there should be at least one shard");
+        shard_loads[shard_idx] += duration;
+
+        if shard_idx + 1 == shard.index {
+            result.push(task_name);
+        }
+    }
+
+    result.sort();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: Vec<&str>) -> Vec<Arc<str>> {
+        names.into_iter().map(Arc::<str>::from).collect()
+    }
+
+    #[test]
+    fn test_parse_shard() {
+        assert_eq!(parse_shard("2/5"), Ok(Shard { index: 2, count: 5 }));
+        assert!(parse_shard("0/5").is_err());
+        assert!(parse_shard("6/5").is_err());
+        assert!(parse_shard("2").is_err());
+    }
+
+    #[test]
+    fn test_select_shard_without_durations() {
+        let tasks = names(vec!["/d", "/b", "/a", "/c", "/e"]);
+        let no_durations = HashMap::new();
+
+        let shard1 = select_shard(tasks.clone(), Shard { index: 1, count: 2 }, &no_durations);
+        let shard2 = select_shard(tasks.clone(), Shard { index: 2, count: 2 }, &no_durations);
+
+        assert_eq!(shard1, names(vec!["/a", "/c", "/e"]));
+        assert_eq!(shard2, names(vec!["/b", "/d"]));
+    }
+
+    #[test]
+    fn test_select_shard_balanced_by_duration() {
+        let tasks = names(vec!["/a", "/b", "/c", "/d"]);
+        let durations: HashMap<Arc<str>, Duration> = HashMap::from([
+            (Arc::<str>::from("/a"), Duration::from_secs(60)),
+            (Arc::<str>::from("/b"), Duration::from_secs(20)),
+            (Arc::<str>::from("/c"), Duration::from_secs(20)),
+            (Arc::<str>::from("/d"), Duration::from_secs(20)),
+        ]);
+
+        let shard1 = select_shard(tasks.clone(), Shard { index: 1, count: 2 }, &durations);
+        let shard2 = select_shard(tasks.clone(), Shard { index: 2, count: 2 }, &durations);
+
+        assert_eq!(shard1, names(vec!["/a"]));
+        assert_eq!(shard2, names(vec!["/b", "/c", "/d"]));
+    }
+
+    #[test]
+    fn test_shard_durations_file() {
+        let tmpdir = mktemp::Temp::new_dir().unwrap();
+        let path = tmpdir.as_path().join("durations.json");
+        let durations: HashMap<Arc<str>, Duration> = HashMap::from([
+            (Arc::<str>::from("/a"), Duration::from_millis(1500)),
+            (Arc::<str>::from("/b"), Duration::from_millis(20)),
+        ]);
+
+        write_shard_durations(path.as_path(), &durations).unwrap();
+        assert_eq!(read_shard_durations(path.as_path()).unwrap(), durations);
+
+        std::fs::write(path.as_path(), "[1, 2]").unwrap();
+        assert!(read_shard_durations(path.as_path()).is_err());
+    }
+}
@@ -0,0 +1,165 @@
+// Cobble Build Automation
+// Copyright (C) 2024 Jeremiah Darais
+//
+// This program is licensed under the GPLv3.0 license (https://github.com/jdarais/cobble/blob/main/COPYING)
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use serde_json::json;
+
+/// Trace thread id used for work done on the main thread
+pub const MAIN_THREAD_ID: u64 = 0;
+
+struct TraceSpanEvent {
+    name: String,
+    category: &'static str,
+    thread_id: u64,
+    start_us: u64,
+    duration_us: u64,
+}
+
+/// Records spans of time spent on each part of a run, to be written out in the Chrome Trace Event
+/// format, (which can be viewed in Perfetto or chrome://tracing.)
+pub struct Tracer {
+    start: Instant,
+    events: Mutex<Vec<TraceSpanEvent>>,
+    thread_names: Mutex<BTreeMap<u64, String>>,
+}
+
+/// A span that is recorded when it is dropped
+pub struct TraceSpan<'a> {
+    tracer: &'a Tracer,
+    name: String,
+    category: &'static str,
+    thread_id: u64,
+    start: Instant,
+}
+
+impl Tracer {
+    pub fn new() -> Tracer {
+        let tracer = Tracer {
+            start: Instant::now(),
+            events: Mutex::new(Vec::new()),
+            thread_names: Mutex::new(BTreeMap::new()),
+        };
+        tracer.set_thread_name(MAIN_THREAD_ID, "main");
+        tracer
+    }
+
+    pub fn set_thread_name(&self, thread_id: u64, name: &str) {
+        self.thread_names
+            .lock()
+            .unwrap()
+            .insert(thread_id, String::from(name));
+    }
+
+    /// Start a span that ends when the returned value is dropped
+    pub fn span<'a>(&'a self, name: &str, category: &'static str, thread_id: u64) -> TraceSpan<'a> {
+        TraceSpan {
+            tracer: self,
+            name: String::from(name),
+            category,
+            thread_id,
+            start: Instant::now(),
+        }
+    }
+
+    fn micros_since_start(&self, time: Instant) -> u64 {
+        time.saturating_duration_since(self.start).as_micros() as u64
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut trace_events: Vec<serde_json::Value> = Vec::new();
+
+        for (thread_id, name) in self.thread_names.lock().unwrap().iter() {
+            trace_events.push(json!({
+                "name": "thread_name",
+                "ph": "M",
+                "pid": 1,
+                "tid": thread_id,
+                "args": { "name": name },
+            }));
+        }
+
+        for event in self.events.lock().unwrap().iter() {
+            trace_events.push(json!({
+                "name": event.name,
+                "cat": event.category,
+                "ph": "X",
+                "ts": event.start_us,
+                "dur": event.duration_us,
+                "pid": 1,
+                "tid": event.thread_id,
+            }));
+        }
+
+        json!({
+            "traceEvents": trace_events,
+            "displayTimeUnit": "ms",
+        })
+    }
+
+    pub fn write_to_file(&self, path: &Path) -> io::Result<()> {
+        let file = File::create(path)?;
+        serde_json::to_writer(BufWriter::new(file), &self.to_json())?;
+        Ok(())
+    }
+}
+
+impl Default for Tracer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Drop for TraceSpan<'a> {
+    fn drop(&mut self) {
+        let end = Instant::now();
+        let event = TraceSpanEvent {
+            name: std::mem::take(&mut self.name),
+            category: self.category,
+            thread_id: self.thread_id,
+            start_us: self.tracer.micros_since_start(self.start),
+            duration_us: end.saturating_duration_since(self.start).as_micros() as u64,
+        };
+        self.tracer.events.lock().unwrap().push(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracer() {
+        let tracer = Tracer::new();
+        tracer.set_thread_name(1, "worker 1");
+        {
+            let _outer = tracer.span("load_projects", "phase", MAIN_THREAD_ID);
+            let _inner = tracer.span("/proj/build", "task", 1);
+        }
+
+        let trace = tracer.to_json();
+        let events = trace["traceEvents"].as_array().unwrap();
+        assert_eq!(events.len(), 4);
+
+        let span_names: Vec<&str> = events
+            .iter()
+            .filter(|e| e["ph"] == "X")
+            .map(|e| e["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(span_names, vec!["/proj/build", "load_projects"]);
+
+        let thread_names: Vec<&str> = events
+            .iter()
+            .filter(|e| e["ph"] == "M")
+            .map(|e| e["args"]["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(thread_names, vec!["main", "worker 1"]);
+    }
+}
@@ -5,3 +5,4 @@
 
 pub mod hash;
 pub mod onscopeexit;
+pub mod time;
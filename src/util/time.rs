@@ -0,0 +1,94 @@
+// Cobble Build Automation
+// Copyright (C) 2024 Jeremiah Darais
+//
+// This program is licensed under the GPLv3.0 license (https://github.com/jdarais/cobble/blob/main/COPYING)
+
+use std::time::SystemTime;
+
+/// A calendar date and time in UTC, with millisecond precision
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UtcDateTime {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    pub millisecond: u32,
+}
+
+impl UtcDateTime {
+    pub fn from_system_time(time: SystemTime) -> UtcDateTime {
+        let since_epoch = time
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .expect("Time since unix epoch should not be negative");
+        let secs = since_epoch.as_secs() as i64;
+        let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+        let secs_of_day = secs.rem_euclid(86400) as u32;
+
+        UtcDateTime {
+            year,
+            month,
+            day,
+            hour: secs_of_day / 3600,
+            minute: (secs_of_day % 3600) / 60,
+            second: secs_of_day % 60,
+            millisecond: since_epoch.subsec_millis(),
+        }
+    }
+
+    pub fn now() -> UtcDateTime {
+        UtcDateTime::from_system_time(SystemTime::now())
+    }
+
+    /// Format as a string that sorts in chronological order and is safe to use in file names
+    pub fn to_sortable_string(&self) -> String {
+        format!(
+            "{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second, self.millisecond
+        )
+    }
+
+    pub fn to_time_string(&self) -> String {
+        format!(
+            "{:02}:{:02}:{:02}.{:03}",
+            self.hour, self.minute, self.second, self.millisecond
+        )
+    }
+}
+
+/// Convert a number of days since the unix epoch to a (year, month, day) date.
+/// See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_utc_date_time() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_millis(1718206212123);
+        let date_time = UtcDateTime::from_system_time(time);
+        assert_eq!(date_time.to_sortable_string(), "20240612-153012-123");
+        assert_eq!(date_time.to_time_string(), "15:30:12.123");
+
+        let leap_day = SystemTime::UNIX_EPOCH + Duration::from_secs(951782400);
+        assert_eq!(
+            UtcDateTime::from_system_time(leap_day).to_sortable_string(),
+            "20000229-000000-000"
+        );
+    }
+}
@@ -3,9 +3,11 @@
 //
 // This program is licensed under the GPLv3.0 license (https://github.com/jdarais/cobble/blob/main/COPYING)
 
-use std::{collections::HashMap, error::Error, fmt};
+use std::{collections::HashMap, error::Error, fmt, sync::Arc};
 
+use crate::config::{VarSource, WorkspaceConfig};
 use crate::project_def::types::TaskVar;
+use crate::project_def::VarDef;
 
 #[derive(Debug)]
 pub enum VarLookupError {
@@ -30,6 +32,35 @@ impl fmt::Display for VarLookupError {
     }
 }
 
+#[derive(Debug)]
+pub enum VarDeclarationError {
+    InvalidValue {
+        var_name: String,
+        source: VarSource,
+        message: String,
+    },
+    LookupError(VarLookupError),
+}
+
+impl Error for VarDeclarationError {}
+impl fmt::Display for VarDeclarationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use VarDeclarationError::*;
+        match self {
+            InvalidValue {
+                var_name,
+                source,
+                message,
+            } => write!(
+                f,
+                "Invalid value for variable {} (set from {}): {}",
+                var_name, source, message
+            ),
+            LookupError(e) => write!(f, "Error looking up declared variable: {}", e),
+        }
+    }
+}
+
 pub fn get_var<'a>(
     var_name: &str,
     vars: &'a HashMap<String, TaskVar>,
@@ -126,3 +157,171 @@ fn set_var_at_subpath_in_table(
 
     Ok(())
 }
+
+/// Check the values of declared vars against their declarations, converting each value to the
+/// canonical form for its type, and set declared vars that don't have a value to their default.
+pub fn resolve_declared_vars(
+    var_defs: &HashMap<Arc<str>, Arc<VarDef>>,
+    config: &mut WorkspaceConfig,
+) -> Result<(), VarDeclarationError> {
+    let mut var_names: Vec<&Arc<str>> = var_defs.keys().collect();
+    var_names.sort();
+
+    for var_name in var_names {
+        let var_def = &var_defs[var_name];
+        match get_var(var_name, &config.vars) {
+            Ok(value) => {
+                let coerced = var_def.coerce(value).map_err(|message| {
+                    VarDeclarationError::InvalidValue {
+                        var_name: var_name.to_string(),
+                        source: config
+                            .var_source(var_name)
+                            .unwrap_or(VarSource::WorkspaceConfig),
+                        message: secret_safe_message(config, var_name, message),
+                    }
+                })?;
+                set_var(var_name, coerced, &mut config.vars)
+                    .map_err(VarDeclarationError::LookupError)?;
+            }
+            Err(VarLookupError::PathNotFound(_)) => {
+                if let Some(default) = &var_def.default {
+                    set_var(var_name, default.clone(), &mut config.vars)
+                        .map_err(VarDeclarationError::LookupError)?;
+                    config
+                        .var_sources
+                        .insert(var_name.to_string(), VarSource::Default);
+                }
+            }
+            Err(e) => {
+                return Err(VarDeclarationError::LookupError(e));
+            }
+        }
+    }
+
+    // Vars given on the command line that aren't declared are most likely typos
+    if !var_defs.is_empty() {
+        let mut undeclared_cli_vars: Vec<&String> = config
+            .var_sources
+            .iter()
+            .filter(|(name, source)| {
+                **source == VarSource::CommandLine && !var_defs.contains_key(name.as_str())
+            })
+            .map(|(name, _)| name)
+            .collect();
+        undeclared_cli_vars.sort();
+        for var_name in undeclared_cli_vars {
+            eprintln!(
+                "WARNING: Variable {} was set on the command line, but is not declared in any project",
+                var_name
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn is_same_or_nested_var(parent_name: &str, var_name: &str) -> bool {
+    var_name
+        .strip_prefix(parent_name)
+        .map(|rest| rest.is_empty() || rest.starts_with('.'))
+        .unwrap_or(false)
+}
+
+/// Returns true if the value of a var contains a secret.  Vars are nested tables, so a var is
+/// secret if it is a secret var, is inside a secret var, (e.g. "db.password" when "db" is
+/// secret,) or contains a secret var, (e.g. "db" when "db.password" is secret.)
+pub fn is_secret_var(config: &WorkspaceConfig, var_name: &str) -> bool {
+    config.secret_vars.iter().any(|secret_name| {
+        is_same_or_nested_var(secret_name, var_name) || is_same_or_nested_var(var_name, secret_name)
+    })
+}
+
+/// Keep the value of a secret var out of an error message
+fn secret_safe_message(config: &WorkspaceConfig, var_name: &str, message: String) -> String {
+    if is_secret_var(config, var_name) {
+        String::from("the value is not valid for the declared type")
+    } else {
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_workspace_config;
+    use crate::project_def::var::{coerce_var_value, VarType};
+
+    fn var_def(name: &str, var_type: VarType, choices: Option<Vec<&str>>, default: Option<&str>) -> Arc<VarDef> {
+        Arc::new(VarDef {
+            name: Arc::<str>::from(name),
+            var_type,
+            choices: choices.map(|c| c.into_iter().map(String::from).collect()),
+            default: default.map(|d| coerce_var_value(var_type, &TaskVar::String(String::from(d))).unwrap()),
+            description: None,
+        })
+    }
+
+    #[test]
+    fn test_is_secret_var() {
+        let config = parse_workspace_config(
+            r#"
+            [vars]
+            db.password = "hunter2"
+            db.host = "localhost"
+            dbx = "public"
+
+            [secret_vars]
+            db.password = true
+        "#,
+            std::path::Path::new("cobble.toml"),
+        )
+        .unwrap();
+
+        assert!(is_secret_var(&config, "db.password"));
+        assert!(is_secret_var(&config, "db.password.inner"));
+        assert!(is_secret_var(&config, "db"));
+        assert!(!is_secret_var(&config, "db.host"));
+        assert!(!is_secret_var(&config, "dbx"));
+        assert!(!is_secret_var(&config, "db.pass"));
+    }
+
+    #[test]
+    fn test_resolve_declared_vars() {
+        let mut config = parse_workspace_config(
+            r#"
+            [vars]
+            build.mode = "release"
+            jobs = "08"
+        "#,
+            std::path::Path::new("cobble.toml"),
+        )
+        .unwrap();
+
+        let var_defs: HashMap<Arc<str>, Arc<VarDef>> = vec![
+            var_def("build.mode", VarType::String, Some(vec!["debug", "release"]), Some("debug")),
+            var_def("jobs", VarType::Int, None, None),
+            var_def("verbose", VarType::Bool, None, Some("false")),
+        ]
+        .into_iter()
+        .map(|d| (d.name.clone(), d))
+        .collect();
+
+        resolve_declared_vars(&var_defs, &mut config).unwrap();
+
+        assert_eq!(get_var("build.mode", &config.vars).unwrap(), &TaskVar::String(String::from("release")));
+        assert_eq!(get_var("jobs", &config.vars).unwrap(), &TaskVar::Int(8));
+        assert_eq!(get_var("verbose", &config.vars).unwrap(), &TaskVar::Bool(false));
+        assert_eq!(config.var_source("build.mode"), Some(VarSource::WorkspaceConfig));
+        assert_eq!(config.var_source("verbose"), Some(VarSource::Default));
+
+        set_var("build.mode", TaskVar::String(String::from("fast")), &mut config.vars).unwrap();
+        config.var_sources.insert(String::from("build.mode"), VarSource::CommandLine);
+        match resolve_declared_vars(&var_defs, &mut config) {
+            Err(VarDeclarationError::InvalidValue { var_name, source, .. }) => {
+                assert_eq!(var_name, "build.mode");
+                assert_eq!(source, VarSource::CommandLine);
+            }
+            other => panic!("Expected an invalid value error, but got {:?}", other),
+        }
+    }
+}
@@ -11,9 +11,12 @@ use crate::config::TaskOutputCondition;
 use crate::dependency::compute_file_providers;
 use crate::project_def::build_env::EnvSetupTask;
 use crate::project_def::{
-    Action, Artifacts, BuildEnvDef, Dependencies, ExternalTool, Project, TaskDef,
+    Action, Artifacts, BuildEnvDef, Dependencies, ExternalTool, Project, TaskDef, VarDef,
 };
 
+/// The project that holds built-in tools, (e.g. "cmd",) that are available to every project
+pub const INTERNAL_PROJECT_NAME: &str = "/__COBBLE_INTERNAL__";
+
 #[derive(Clone, Debug)]
 pub enum TaskType {
     Project,
@@ -31,6 +34,8 @@ pub struct FileDependency {
 pub struct Task {
     pub task_type: TaskType,
     pub name: Arc<str>,
+    pub description: Option<Arc<str>>,
+    pub group: Option<Arc<str>>,
     pub project_name: Arc<str>,
     pub project_path: Arc<Path>,
     pub dir: Arc<Path>,
@@ -39,6 +44,7 @@ pub struct Task {
     pub file_deps: HashMap<Arc<str>, FileDependency>,
     pub task_deps: HashMap<Arc<str>, Arc<str>>,
     pub var_deps: HashMap<Arc<str>, Arc<str>>,
+    pub env_vars: HashMap<String, String>,
     pub calc_deps: Vec<Arc<str>>,
     pub execute_after: Vec<Arc<str>>,
     pub actions: Vec<Action>,
@@ -46,9 +52,13 @@ pub struct Task {
     pub artifacts: Artifacts,
     pub always_run: bool,
     pub is_interactive: bool,
+    pub pty: Option<bool>,
     pub show_stdout: Option<TaskOutputCondition>,
     pub show_stderr: Option<TaskOutputCondition>,
     pub project_source_deps: Vec<Arc<str>>,
+    pub tags: Vec<Arc<str>>,
+    /// Private tasks are helpers for other tasks, and are left out of `cobl list` by default
+    pub is_private: bool,
 }
 
 impl Default for Task {
@@ -56,6 +66,8 @@ impl Default for Task {
         Self {
             name: String::new().into(),
             task_type: TaskType::Task,
+            description: None,
+            group: None,
             project_name: String::new().into(),
             project_path: PathBuf::from(".").into(),
             dir: PathBuf::from(".").into(),
@@ -64,6 +76,7 @@ impl Default for Task {
             file_deps: HashMap::new(),
             task_deps: HashMap::new(),
             var_deps: HashMap::new(),
+            env_vars: HashMap::new(),
             calc_deps: Vec::new(),
             execute_after: Vec::new(),
             actions: Vec::new(),
@@ -71,9 +84,12 @@ impl Default for Task {
             artifacts: Default::default(),
             always_run: false,
             is_interactive: false,
+            pty: None,
             show_stdout: None,
             show_stderr: None,
             project_source_deps: Vec::new(),
+            tags: Vec::new(),
+            is_private: false,
         }
     }
 }
@@ -86,11 +102,12 @@ pub struct BuildEnv {
     pub action: Action
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct Workspace {
     pub tasks: HashMap<Arc<str>, Arc<Task>>,
     pub build_envs: HashMap<Arc<str>, Arc<BuildEnv>>,
     pub tools: HashMap<Arc<str>, Arc<ExternalTool>>,
+    pub vars: HashMap<Arc<str>, Arc<VarDef>>,
     pub file_providers: HashMap<Arc<str>, Arc<str>>,
 }
 
@@ -177,17 +194,23 @@ fn add_task_to_workspace(
     let mut task = Task {
         name: task_def.name.clone(),
         task_type: TaskType::Task,
+        description: task_def.description.clone(),
+        group: task_def.group.clone(),
         dir: dir.clone(),
         project_name: project_name.clone(),
         project_path: dir.clone(),
         always_run: task_def.always_run.unwrap_or(false),
         is_interactive: task_def.is_interactive.unwrap_or(false),
+        pty: task_def.pty,
         show_stdout: task_def.show_stdout.clone(),
         show_stderr: task_def.show_stderr.clone(),
         build_envs: task_def.build_env.iter().cloned().collect(),
+        env_vars: task_def.env_vars.clone(),
         artifacts: task_def.artifacts.clone(),
         project_source_deps: project_source_deps.clone(),
         clean_actions: task_def.clean.clone(),
+        tags: task_def.tags.clone(),
+        is_private: task_def.is_private.unwrap_or(false),
         ..Default::default()
     };
 
@@ -203,7 +226,7 @@ fn add_task_to_workspace(
 }
 
 fn add_project_to_workspace(project: &Project, workspace: &mut Workspace) {
-    if project.name.as_ref() != "/__COBBLE_INTERNAL__" {
+    if project.name.as_ref() != INTERNAL_PROJECT_NAME {
         let mut project_task = Task {
             name: project.name.clone(),
             task_type: TaskType::Project,
@@ -263,6 +286,12 @@ fn add_project_to_workspace(project: &Project, workspace: &mut Workspace) {
             .tools
             .insert(tool.name.clone(), Arc::new(tool.clone()));
     }
+
+    for var in project.vars.iter() {
+        workspace
+            .vars
+            .insert(var.name.clone(), Arc::new(var.clone()));
+    }
 }
 
 pub fn create_workspace<'a, P>(all_projects: P) -> Workspace
@@ -273,10 +302,8 @@ where
     let file_providers = compute_file_providers(all_projects_vec.iter().copied());
 
     let mut workspace = Workspace {
-        tasks: HashMap::new(),
-        build_envs: HashMap::new(),
-        tools: HashMap::new(),
         file_providers,
+        ..Default::default()
     };
 
     for project in all_projects_vec.into_iter() {
@@ -0,0 +1,307 @@
+// Cobble Build Automation
+// Copyright (C) 2024 Jeremiah Darais
+//
+// This program is licensed under the GPLv3.0 license (https://github.com/jdarais/cobble/blob/main/COPYING)
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use serde::Serialize;
+
+use crate::project_def::types::TaskVar;
+use crate::project_def::{Action, ActionCmd, ExternalTool, VarDef};
+use crate::workspace::{BuildEnv, Task, TaskType, Workspace};
+
+/// Incremented when a change to the JSON output would break existing consumers
+pub const WORKSPACE_JSON_VERSION: u32 = 1;
+
+fn sorted_map(map: &HashMap<Arc<str>, Arc<str>>) -> BTreeMap<String, String> {
+    map.iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+fn task_type_name(task_type: &TaskType) -> &'static str {
+    match task_type {
+        TaskType::Task => "task",
+        TaskType::Project => "project",
+        TaskType::BuildEnv => "build_env",
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ActionJson {
+    /// Either "cmd" or "function"
+    #[serde(rename = "type")]
+    pub action_type: &'static str,
+    /// The command line, for "cmd" actions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub args: Option<Vec<String>>,
+    pub tools: BTreeMap<String, String>,
+    pub envs: BTreeMap<String, String>,
+}
+
+impl From<&Action> for ActionJson {
+    fn from(action: &Action) -> Self {
+        let (action_type, args) = match &action.cmd {
+            ActionCmd::Cmd(args) => ("cmd", Some(args.iter().map(|a| a.to_string()).collect())),
+            ActionCmd::Func(_) => ("function", None),
+        };
+
+        ActionJson {
+            action_type,
+            args,
+            tools: sorted_map(&action.tools),
+            envs: sorted_map(&action.build_envs),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FileDependencyJson {
+    pub path: String,
+    pub provided_by_task: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ArtifactsJson {
+    pub files: Vec<String>,
+    /// Tasks that calculate additional artifacts
+    pub calc: Vec<String>,
+}
+
+/// A short description of a task, as shown by `cobl list --json`
+#[derive(Clone, Debug, Serialize)]
+pub struct TaskSummaryJson {
+    pub name: String,
+    pub project: String,
+    #[serde(rename = "type")]
+    pub task_type: &'static str,
+    pub description: Option<String>,
+    pub group: Option<String>,
+    pub tags: Vec<String>,
+    pub private: bool,
+}
+
+impl From<&Task> for TaskSummaryJson {
+    fn from(task: &Task) -> Self {
+        TaskSummaryJson {
+            name: task.name.to_string(),
+            project: task.project_name.to_string(),
+            task_type: task_type_name(&task.task_type),
+            description: task.description.as_ref().map(|d| d.to_string()),
+            group: task.group.as_ref().map(|g| g.to_string()),
+            tags: task.tags.iter().map(|t| t.to_string()).collect(),
+            private: task.is_private,
+        }
+    }
+}
+
+/// The full definition of a task, as shown by `cobl show --json` and `cobl dump-workspace`
+#[derive(Clone, Debug, Serialize)]
+pub struct TaskJson {
+    #[serde(flatten)]
+    pub summary: TaskSummaryJson,
+    pub dir: String,
+    pub always_run: bool,
+    pub interactive: bool,
+    pub actions: Vec<ActionJson>,
+    pub clean_actions: Vec<ActionJson>,
+    pub artifacts: ArtifactsJson,
+    pub file_deps: BTreeMap<String, FileDependencyJson>,
+    pub task_deps: BTreeMap<String, String>,
+    pub var_deps: BTreeMap<String, String>,
+    /// Tasks that calculate additional dependencies, if they haven't been resolved yet
+    pub calc_deps: Vec<String>,
+    pub execute_after: Vec<String>,
+    pub envs: BTreeMap<String, String>,
+    pub tools: BTreeMap<String, String>,
+    pub env_vars: BTreeMap<String, String>,
+    pub project_source_deps: Vec<String>,
+}
+
+impl From<&Task> for TaskJson {
+    fn from(task: &Task) -> Self {
+        TaskJson {
+            summary: TaskSummaryJson::from(task),
+            dir: task.dir.display().to_string(),
+            always_run: task.always_run,
+            interactive: task.is_interactive,
+            actions: task.actions.iter().map(ActionJson::from).collect(),
+            clean_actions: task.clean_actions.iter().map(ActionJson::from).collect(),
+            artifacts: ArtifactsJson {
+                files: task.artifacts.files.iter().map(|f| f.to_string()).collect(),
+                calc: task.artifacts.calc.iter().map(|c| c.to_string()).collect(),
+            },
+            file_deps: task
+                .file_deps
+                .iter()
+                .map(|(alias, f)| {
+                    (
+                        alias.to_string(),
+                        FileDependencyJson {
+                            path: f.path.to_string(),
+                            provided_by_task: f.provided_by_task.as_ref().map(|t| t.to_string()),
+                        },
+                    )
+                })
+                .collect(),
+            task_deps: sorted_map(&task.task_deps),
+            var_deps: sorted_map(&task.var_deps),
+            calc_deps: task.calc_deps.iter().map(|c| c.to_string()).collect(),
+            execute_after: task.execute_after.iter().map(|t| t.to_string()).collect(),
+            envs: sorted_map(&task.build_envs),
+            tools: sorted_map(&task.tools),
+            env_vars: task
+                .env_vars
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+            project_source_deps: task
+                .project_source_deps
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct BuildEnvJson {
+    pub name: String,
+    pub dir: String,
+    pub setup_task: Option<String>,
+    pub action: ActionJson,
+}
+
+impl From<&BuildEnv> for BuildEnvJson {
+    fn from(env: &BuildEnv) -> Self {
+        BuildEnvJson {
+            name: env.name.to_string(),
+            dir: env.dir.display().to_string(),
+            setup_task: env.setup_task.as_ref().map(|t| t.to_string()),
+            action: ActionJson::from(&env.action),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ToolJson {
+    pub name: String,
+    pub install: Option<ActionJson>,
+    pub check: Option<ActionJson>,
+    pub action: ActionJson,
+}
+
+impl From<&ExternalTool> for ToolJson {
+    fn from(tool: &ExternalTool) -> Self {
+        ToolJson {
+            name: tool.name.to_string(),
+            install: tool.install.as_ref().map(ActionJson::from),
+            check: tool.check.as_ref().map(ActionJson::from),
+            action: ActionJson::from(&tool.action),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct VarJson {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub var_type: String,
+    pub choices: Option<Vec<String>>,
+    pub default: Option<TaskVar>,
+    pub description: Option<String>,
+}
+
+impl From<&VarDef> for VarJson {
+    fn from(var: &VarDef) -> Self {
+        VarJson {
+            name: var.name.to_string(),
+            var_type: var.var_type.to_string(),
+            choices: var.choices.clone(),
+            default: var.default.clone(),
+            description: var.description.as_ref().map(|d| d.to_string()),
+        }
+    }
+}
+
+/// The resolved workspace, as shown by `cobl dump-workspace`, for tools that need to inspect the
+/// build without evaluating project files.  Maps are serialized with sorted keys, so that the
+/// output is stable from one run to the next.
+#[derive(Clone, Debug, Serialize)]
+pub struct WorkspaceJson {
+    pub version: u32,
+    pub tasks: BTreeMap<String, TaskJson>,
+    pub build_envs: BTreeMap<String, BuildEnvJson>,
+    pub tools: BTreeMap<String, ToolJson>,
+    pub vars: BTreeMap<String, VarJson>,
+    /// Maps file paths to the tasks that produce them
+    pub file_providers: BTreeMap<String, String>,
+}
+
+impl From<&Workspace> for WorkspaceJson {
+    fn from(workspace: &Workspace) -> Self {
+        WorkspaceJson {
+            version: WORKSPACE_JSON_VERSION,
+            tasks: workspace
+                .tasks
+                .iter()
+                .map(|(name, task)| (name.to_string(), TaskJson::from(task.as_ref())))
+                .collect(),
+            build_envs: workspace
+                .build_envs
+                .iter()
+                .map(|(name, env)| (name.to_string(), BuildEnvJson::from(env.as_ref())))
+                .collect(),
+            tools: workspace
+                .tools
+                .iter()
+                .map(|(name, tool)| (name.to_string(), ToolJson::from(tool.as_ref())))
+                .collect(),
+            vars: workspace
+                .vars
+                .iter()
+                .map(|(name, var)| (name.to_string(), VarJson::from(var.as_ref())))
+                .collect(),
+            file_providers: sorted_map(&workspace.file_providers),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::project_def::Artifacts;
+
+    use super::*;
+
+    #[test]
+    fn test_task_json() {
+        let task = Task {
+            name: Arc::<str>::from("/a/test"),
+            project_name: Arc::<str>::from("/a"),
+            description: Some(Arc::<str>::from("Run the tests")),
+            task_deps: HashMap::from([
+                (Arc::<str>::from("z"), Arc::<str>::from("/z/build")),
+                (Arc::<str>::from("b"), Arc::<str>::from("/b/build")),
+            ]),
+            artifacts: Artifacts {
+                files: vec![Arc::<str>::from("a/out.txt")],
+                calc: Vec::new(),
+            },
+            ..Default::default()
+        };
+
+        let task_json = serde_json::to_value(TaskJson::from(&task)).unwrap();
+        assert_eq!(task_json["name"], "/a/test");
+        assert_eq!(task_json["project"], "/a");
+        assert_eq!(task_json["type"], "task");
+        assert_eq!(task_json["description"], "Run the tests");
+        assert_eq!(task_json["artifacts"]["files"][0], "a/out.txt");
+
+        let serialized = serde_json::to_string(&TaskJson::from(&task)).unwrap();
+        let b_pos = serialized.find("\"/b/build\"").unwrap();
+        let z_pos = serialized.find("\"/z/build\"").unwrap();
+        assert!(b_pos < z_pos, "task deps should be sorted by alias");
+    }
+}